@@ -0,0 +1,515 @@
+use crate::{
+    client::from_sdk_error,
+    sdk::types::{
+        GlobalSecondaryIndexUpdate, IndexStatus, ProvisionedThroughput,
+        ProvisionedThroughputDescription, TableStatus, UpdateGlobalSecondaryIndexAction,
+    },
+    Client, Error,
+};
+use std::{
+    collections::HashMap,
+    fmt,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+type ScalingErrorCallback = Arc<dyn Fn(&str, &Error) + Send + Sync>;
+
+/// 流量の下限。これより下げると待ち時間を計算できなくなります
+const MIN_RATE: f64 = 0.001;
+
+impl Client {
+    /// スロットリングに合わせて、クライアント側でリクエストの流量を調整するようにします
+    /// ```no_run
+    /// # use dynamodb_utils::*;
+    /// # tokio_test::block_on(async {
+    /// let client = Client::from_env()
+    ///     .await
+    ///     .with_adaptive_capacity(AdaptiveCapacityConfig {
+    ///         provisioned: Some(ProvisionedScaling {
+    ///             max_read_capacity: 100,
+    ///             max_write_capacity: 100,
+    ///             ..Default::default()
+    ///         }),
+    ///         ..Default::default()
+    ///     });
+    /// # });
+    /// ```
+    pub fn with_adaptive_capacity(
+        self,
+        config: AdaptiveCapacityConfig,
+    ) -> Client<AdaptiveCapacity> {
+        self.with_autoscale(AdaptiveCapacity::new(config))
+    }
+
+    /// 流量の調整方法を指定します
+    pub fn with_autoscale<A: Autoscale>(self, autoscale: A) -> Client<A> {
+        Client {
+            dynamodb: self.dynamodb,
+            autoscale,
+            table_prefix: self.table_prefix,
+            mock: self.mock,
+        }
+    }
+}
+
+/// リクエストの流量の調整方法を規定する
+pub trait Autoscale: Clone + Send + Sync + 'static {
+    /// `table_name`へリクエストを送る前に呼ばれます。送ってよくなるまで待ちます。
+    fn acquire(&self, table_name: &str) -> impl Future<Output = ()> + Send;
+
+    /// `table_name`へのリクエストの結果を受け取ります
+    ///
+    /// - `throttled` スロットリングされたかどうか
+    fn record(
+        &self,
+        dynamodb: &aws_sdk_dynamodb::Client,
+        table_name: &str,
+        throttled: bool,
+    ) -> impl Future<Output = ()> + Send;
+}
+
+/// 調整しない
+impl Autoscale for () {
+    /// noop
+    #[inline]
+    async fn acquire(&self, _table_name: &str) {}
+
+    /// noop
+    #[inline]
+    async fn record(
+        &self,
+        _dynamodb: &aws_sdk_dynamodb::Client,
+        _table_name: &str,
+        _throttled: bool,
+    ) {
+    }
+}
+
+/// [`AdaptiveCapacity`]の設定
+#[derive(Debug, Clone)]
+pub struct AdaptiveCapacityConfig {
+    /// テーブルごとの、1秒あたりの最大リクエスト数
+    pub max_rate: f64,
+    /// スロットリングされても、これ以下には下げない
+    ///
+    /// 0.001未満や`NaN`は0.001として扱います。
+    pub min_rate: f64,
+    /// スロットリングされたときに、流量に掛ける値
+    pub decrease_factor: f64,
+    /// 成功したときに、流量に足す値
+    pub increase_step: f64,
+    /// 設定するとプロビジョニングされたキャパシティも増減させます
+    pub provisioned: Option<ProvisionedScaling>,
+}
+
+impl Default for AdaptiveCapacityConfig {
+    fn default() -> Self {
+        Self {
+            max_rate: 1000.0,
+            min_rate: 1.0,
+            decrease_factor: 0.5,
+            increase_step: 1.0,
+            provisioned: None,
+        }
+    }
+}
+
+/// プロビジョニングされたキャパシティの増減の設定
+///
+/// スロットリングされたら`step_factor`倍に増やし、
+/// `scale_down_after`の間スロットリングされなければ`step_factor`分の1に減らします。
+/// キャパシティは`min`から`max`の範囲に収めます。GSIもテーブルと同じ倍率で増減させます。
+#[derive(Debug, Clone)]
+pub struct ProvisionedScaling {
+    pub min_read_capacity: i64,
+    pub max_read_capacity: i64,
+    pub min_write_capacity: i64,
+    pub max_write_capacity: i64,
+    pub step_factor: f64,
+    /// キャパシティを変更してから、次に変更できるようになるまでの時間
+    pub cooldown: Duration,
+    pub scale_down_after: Duration,
+    /// キャパシティを減らしてから、次に減らせるようになるまでの時間
+    ///
+    /// DynamoDBは1日に減らせる回数を制限しています。直前の1時間に減らしていなければ
+    /// いつでも減らせるので、デフォルトは1時間です。
+    pub scale_down_interval: Duration,
+}
+
+impl Default for ProvisionedScaling {
+    fn default() -> Self {
+        Self {
+            min_read_capacity: 1,
+            max_read_capacity: 1,
+            min_write_capacity: 1,
+            max_write_capacity: 1,
+            step_factor: 1.5,
+            cooldown: Duration::from_secs(60),
+            scale_down_after: Duration::from_secs(15 * 60),
+            scale_down_interval: Duration::from_secs(60 * 60),
+        }
+    }
+}
+
+/// スロットリングに応じて流量を調整する
+///
+/// テーブルごとにtoken bucketを持ち、スロットリングされるたびに流量を
+/// `decrease_factor`倍に下げ、成功するたびに`increase_step`だけ戻します。
+/// query、scanのstreamは対象外です。
+///
+/// キャパシティの変更はリクエストとは別のtaskで行います。
+#[derive(Clone)]
+pub struct AdaptiveCapacity {
+    config: Arc<AdaptiveCapacityConfig>,
+    tables: Arc<Mutex<HashMap<String, TableState>>>,
+    on_scaling_error: Option<ScalingErrorCallback>,
+}
+
+impl fmt::Debug for AdaptiveCapacity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AdaptiveCapacity")
+            .field("config", &self.config)
+            .field("tables", &self.tables)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug)]
+struct TableState {
+    rate: f64,
+    tokens: f64,
+    last_refill: Instant,
+    last_throttled: Option<Instant>,
+    last_scaled: Option<Instant>,
+    last_scaled_down: Option<Instant>,
+}
+
+impl TableState {
+    fn new(rate: f64) -> Self {
+        Self {
+            rate,
+            tokens: rate,
+            last_refill: Instant::now(),
+            last_throttled: None,
+            last_scaled: None,
+            last_scaled_down: None,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.rate.max(1.0));
+        self.last_refill = now;
+    }
+}
+
+enum Scale {
+    Up,
+    Down,
+}
+
+impl AdaptiveCapacity {
+    pub fn new(mut config: AdaptiveCapacityConfig) -> Self {
+        // f64::maxはNaNを無視します
+        config.min_rate = config.min_rate.max(MIN_RATE);
+        config.max_rate = config.max_rate.max(config.min_rate);
+        Self {
+            config: Arc::new(config),
+            tables: Arc::default(),
+            on_scaling_error: None,
+        }
+    }
+
+    /// キャパシティの変更に失敗したときに、テーブル名とエラーで呼ばれます
+    pub fn on_scaling_error(
+        mut self,
+        on_scaling_error: impl Fn(&str, &Error) + Send + Sync + 'static,
+    ) -> Self {
+        self.on_scaling_error = Some(Arc::new(on_scaling_error));
+        self
+    }
+
+    /// `table_name`の現在の1秒あたりの流量
+    pub fn current_rate(&self, table_name: &str) -> f64 {
+        self.tables
+            .lock()
+            .expect("poisoned lock")
+            .get(table_name)
+            .map_or(self.config.max_rate, |state| state.rate)
+    }
+
+    /// 送ってよくなるまでの待ち時間。待たなくてよければtokenを消費します。
+    fn reserve(&self, table_name: &str) -> Option<Duration> {
+        let mut tables = self.tables.lock().expect("poisoned lock");
+        let state = tables
+            .entry(table_name.to_owned())
+            .or_insert_with(|| TableState::new(self.config.max_rate));
+        state.refill(Instant::now());
+        if state.tokens >= 1.0 {
+            state.tokens -= 1.0;
+            None
+        } else {
+            Some(Duration::from_secs_f64(
+                (1.0 - state.tokens) / state.rate.max(MIN_RATE),
+            ))
+        }
+    }
+
+    /// 流量を更新して、キャパシティを変更すべきかを返します
+    fn update(&self, table_name: &str, throttled: bool, now: Instant) -> Option<Scale> {
+        let mut tables = self.tables.lock().expect("poisoned lock");
+        let state = tables
+            .entry(table_name.to_owned())
+            .or_insert_with(|| TableState::new(self.config.max_rate));
+        if throttled {
+            state.rate = (state.rate * self.config.decrease_factor).max(self.config.min_rate);
+            state.tokens = state.tokens.min(state.rate);
+            state.last_throttled = Some(now);
+        } else {
+            state.rate = (state.rate + self.config.increase_step).min(self.config.max_rate);
+        }
+
+        let scaling = self.config.provisioned.as_ref()?;
+        if state
+            .last_scaled
+            .is_some_and(|last| now.duration_since(last) < scaling.cooldown)
+        {
+            return None;
+        }
+        let scale = if throttled {
+            Scale::Up
+        } else if state
+            .last_throttled
+            .max(state.last_scaled)
+            .is_some_and(|last| now.duration_since(last) >= scaling.scale_down_after)
+            && state
+                .last_scaled_down
+                .is_none_or(|last| now.duration_since(last) >= scaling.scale_down_interval)
+        {
+            state.last_scaled_down = Some(now);
+            Scale::Down
+        } else {
+            return None;
+        };
+        state.last_scaled = Some(now);
+        Some(scale)
+    }
+}
+
+impl Autoscale for AdaptiveCapacity {
+    async fn acquire(&self, table_name: &str) {
+        while let Some(wait) = self.reserve(table_name) {
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// キャパシティの変更は別のtaskで行い、失敗は[`on_scaling_error`](Self::on_scaling_error)に渡します
+    ///
+    /// tokioのruntimeの外では変更できないので、[`Error::NoRuntime`]を渡します。
+    async fn record(&self, dynamodb: &aws_sdk_dynamodb::Client, table_name: &str, throttled: bool) {
+        let (Some(scale), Some(scaling)) = (
+            self.update(table_name, throttled, Instant::now()),
+            self.config.provisioned.clone(),
+        ) else {
+            return;
+        };
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            if let Some(on_scaling_error) = &self.on_scaling_error {
+                on_scaling_error(table_name, &Error::NoRuntime);
+            }
+            return;
+        };
+        let dynamodb = dynamodb.clone();
+        let table_name = table_name.to_owned();
+        let on_scaling_error = self.on_scaling_error.clone();
+        runtime.spawn(async move {
+            if let Err(e) =
+                scale_provisioned_throughput(&dynamodb, &table_name, &scaling, scale).await
+            {
+                if let Some(on_scaling_error) = on_scaling_error {
+                    on_scaling_error(&table_name, &e);
+                }
+            }
+        });
+    }
+}
+
+async fn scale_provisioned_throughput(
+    dynamodb: &aws_sdk_dynamodb::Client,
+    table_name: &str,
+    scaling: &ProvisionedScaling,
+    scale: Scale,
+) -> Result<(), Error> {
+    let Some(table) = dynamodb
+        .describe_table()
+        .table_name(table_name)
+        .send()
+        .await
+        .map_err(from_sdk_error)?
+        .table
+    else {
+        return Ok(());
+    };
+    // 変更中は受け付けられないので、次の機会に回します
+    if table.table_status != Some(TableStatus::Active) {
+        return Ok(());
+    }
+    let factor = match scale {
+        Scale::Up => scaling.step_factor,
+        Scale::Down => scaling.step_factor.recip(),
+    };
+    let next = |current: Option<&ProvisionedThroughputDescription>| {
+        let (Some(read), Some(write)) = (
+            current.and_then(|current| current.read_capacity_units),
+            current.and_then(|current| current.write_capacity_units),
+        ) else {
+            return Ok(None);
+        };
+        // オンデマンドのテーブルは0になっている
+        if read == 0 && write == 0 {
+            return Ok(None);
+        }
+        let scaled = |current: i64, min: i64, max: i64| {
+            ((current as f64 * factor).round() as i64).clamp(min, max)
+        };
+        let next_read = scaled(read, scaling.min_read_capacity, scaling.max_read_capacity);
+        let next_write = scaled(
+            write,
+            scaling.min_write_capacity,
+            scaling.max_write_capacity,
+        );
+        if next_read == read && next_write == write {
+            return Ok(None);
+        }
+        ProvisionedThroughput::builder()
+            .read_capacity_units(next_read)
+            .write_capacity_units(next_write)
+            .build()
+            .map(Some)
+    };
+    let throughput = next(table.provisioned_throughput.as_ref())?;
+    let index_updates = table
+        .global_secondary_indexes
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|index| index.index_status == Some(IndexStatus::Active))
+        .filter_map(|index| {
+            let throughput = next(index.provisioned_throughput.as_ref()).transpose()?;
+            Some(throughput.and_then(|throughput| {
+                UpdateGlobalSecondaryIndexAction::builder()
+                    .set_index_name(index.index_name)
+                    .provisioned_throughput(throughput)
+                    .build()
+            }))
+        })
+        .map(|update| {
+            update.map(|update| GlobalSecondaryIndexUpdate::builder().update(update).build())
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if throughput.is_none() && index_updates.is_empty() {
+        return Ok(());
+    }
+    dynamodb
+        .update_table()
+        .table_name(table_name)
+        .set_provisioned_throughput(throughput)
+        .set_global_secondary_index_updates(
+            Some(index_updates).filter(|updates| !updates.is_empty()),
+        )
+        .send()
+        .await
+        .map_err(from_sdk_error)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Autoscale;
+    use futures_util::FutureExt;
+
+    fn provisioned() -> AdaptiveCapacity {
+        AdaptiveCapacity::new(AdaptiveCapacityConfig {
+            provisioned: Some(ProvisionedScaling::default()),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn scale_down_is_rate_limited() {
+        let capacity = provisioned();
+        let start = Instant::now();
+        let at = |minutes: u64| start + Duration::from_secs(minutes * 60);
+        assert!(matches!(capacity.update("t", true, at(0)), Some(Scale::Up)));
+        // cooldown中は増やしません
+        assert!(capacity.update("t", true, at(0)).is_none());
+        assert!(capacity.update("t", false, at(10)).is_none());
+        assert!(matches!(
+            capacity.update("t", false, at(15)),
+            Some(Scale::Down)
+        ));
+        // scale_down_afterが過ぎても、scale_down_intervalの間は減らしません
+        assert!(capacity.update("t", false, at(30)).is_none());
+        assert!(capacity.update("t", false, at(60)).is_none());
+        assert!(matches!(
+            capacity.update("t", false, at(75)),
+            Some(Scale::Down)
+        ));
+        // 増やすのはscale_down_intervalに関係なくできます
+        assert!(matches!(
+            capacity.update("t", true, at(77)),
+            Some(Scale::Up)
+        ));
+    }
+
+    #[test]
+    fn scaling_errors_are_reported() {
+        tokio_test::block_on(async {
+            let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+            let capacity = provisioned().on_scaling_error(move |table_name, error| {
+                let _ = sender.send((table_name.to_owned(), format!("{error:?}")));
+            });
+            let client = Client::mock();
+            capacity.record(client.raw_client(), "t", true).await;
+            let (table_name, error) = receiver.recv().await.unwrap();
+            assert_eq!(table_name, "t");
+            assert!(error.contains("not supported by the mock client"));
+        });
+    }
+
+    #[test]
+    fn zero_min_rate_does_not_panic() {
+        let capacity = AdaptiveCapacity::new(AdaptiveCapacityConfig {
+            min_rate: 0.0,
+            decrease_factor: 0.0,
+            ..Default::default()
+        });
+        capacity.reserve("t");
+        capacity.update("t", true, Instant::now());
+        assert_eq!(capacity.current_rate("t"), MIN_RATE);
+        let wait = capacity.reserve("t").unwrap();
+        assert!(wait > Duration::ZERO);
+    }
+
+    #[test]
+    fn scaling_without_runtime_is_reported() {
+        let errors = Arc::new(Mutex::new(vec![]));
+        let capacity = provisioned().on_scaling_error({
+            let errors = errors.clone();
+            move |table_name, error| {
+                errors
+                    .lock()
+                    .unwrap()
+                    .push((table_name.to_owned(), matches!(error, Error::NoRuntime)));
+            }
+        });
+        let client = Client::mock();
+        capacity
+            .record(client.raw_client(), "t", true)
+            .now_or_never()
+            .unwrap();
+        assert_eq!(*errors.lock().unwrap(), [("t".to_owned(), true)]);
+    }
+}
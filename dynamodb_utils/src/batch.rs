@@ -0,0 +1,619 @@
+use crate::{
+    sdk::types::{AttributeValue, DeleteRequest, KeysAndAttributes, PutRequest, WriteRequest},
+    Autoscale, Backoff, Client, Error, Key,
+};
+use futures_util::{
+    future::BoxFuture, stream, FutureExt, Sink, StreamExt, TryStream, TryStreamExt,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    pin::Pin,
+    task::{ready, Context, Poll},
+};
+
+/// BatchGetItemの1リクエストで取得できるkeyの上限
+const BATCH_GET_LIMIT: usize = 100;
+/// BatchWriteItemの1リクエストで書き込めるitemの上限
+pub(crate) const BATCH_WRITE_LIMIT: usize = 25;
+
+/// [`Client::batch_get_item`]で取得するitemのkeyの一覧
+///
+/// 複数のテーブルのkeyを混ぜて指定できます。
+/// ```
+/// # use dynamodb_utils::BatchGet;
+/// let request = BatchGet::new()
+///     .table("users", [("id", "a"), ("id", "b")])
+///     .key("orders", ("user_id", "a", "order_id", 1));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct BatchGet {
+    keys: Vec<(String, Key)>,
+    backoff: Backoff,
+}
+
+impl BatchGet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `table_name`のitemのkeyを追加します
+    pub fn table<K: Into<Key>>(
+        mut self,
+        table_name: impl Into<String>,
+        keys: impl IntoIterator<Item = K>,
+    ) -> Self {
+        let table_name = table_name.into();
+        self.keys
+            .extend(keys.into_iter().map(|key| (table_name.clone(), key.into())));
+        self
+    }
+
+    /// `table_name`のitemのkeyを1つ追加します
+    pub fn key(mut self, table_name: impl Into<String>, key: impl Into<Key>) -> Self {
+        self.keys.push((table_name.into(), key.into()));
+        self
+    }
+
+    /// `UnprocessedKeys`を再送するときの待ち時間を指定します
+    pub fn backoff(mut self, backoff: Backoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+}
+
+impl<A: Autoscale> Client<A> {
+    /// BatchGetItemでまとめてitemを取得します
+    /// 具体的な型で受けたいなら[`batch_get_item`](`Self::batch_get_item`)があります。
+    ///
+    /// 100件ごとにリクエストを分け、`UnprocessedKeys`はbackoffしながら再送します。
+    /// テーブル名とitemの組が返ります。取得順はkeyの順番と一致しません。
+    /// 存在しないkeyは無視されます。同じkeyを重複して指定するとエラーになります。
+    pub fn batch_get_item_raw(
+        &self,
+        request: BatchGet,
+    ) -> impl TryStream<Ok = (String, HashMap<String, AttributeValue>), Error = Error> {
+        let client = self.clone();
+        let backoff = request.backoff;
+        let mut chunks = vec![];
+        let mut keys = request.keys.into_iter().peekable();
+        while keys.peek().is_some() {
+            chunks.push(keys.by_ref().take(BATCH_GET_LIMIT).collect::<Vec<_>>());
+        }
+        stream::iter(chunks)
+            .then(move |chunk| batch_get_chunk(client.clone(), backoff.clone(), chunk))
+            .map_ok(|items| stream::iter(items.into_iter().map(Ok)))
+            .try_flatten()
+    }
+
+    /// BatchGetItemでまとめてitemを取得します
+    ///
+    /// 複数のテーブルを指定した場合も、全て`T`としてデシリアライズされます。
+    pub fn batch_get_item<T>(&self, request: BatchGet) -> impl TryStream<Ok = T, Error = Error>
+    where
+        for<'de> T: Deserialize<'de>,
+    {
+        self.batch_get_item_raw(request).and_then(|(_, item)| {
+            futures_util::future::ready(
+                crate::serde_dynamo::aws_sdk_dynamodb_1::from_item(item).map_err(Into::into),
+            )
+        })
+    }
+}
+
+/// 100件以下のkeyを、全て処理されるまで取得します
+async fn batch_get_chunk<A: Autoscale>(
+    client: Client<A>,
+    backoff: Backoff,
+    chunk: Vec<(String, Key)>,
+) -> Result<Vec<(String, HashMap<String, AttributeValue>)>, Error> {
+    if let Some(mock) = &client.mock {
+        let mut items = vec![];
+        for (table_name, key) in chunk {
+            if let Some(item) = mock.get_item(&table_name, key.into_item())?.item {
+                items.push((table_name, item));
+            }
+        }
+        return Ok(items);
+    }
+    let mut grouped = HashMap::<String, Vec<HashMap<String, AttributeValue>>>::new();
+    for (table_name, key) in chunk {
+        grouped.entry(table_name).or_default().push(key.into_item());
+    }
+    let mut request_items = grouped
+        .into_iter()
+        .map(|(table_name, keys)| {
+            let keys = KeysAndAttributes::builder().set_keys(Some(keys)).build()?;
+            Ok((table_name, keys))
+        })
+        .collect::<Result<HashMap<_, _>, Error>>()?;
+
+    let mut items = vec![];
+    let mut attempt = 0;
+    loop {
+        let table_names = request_items.keys().cloned().collect::<Vec<_>>();
+        let table_names = table_names.iter().map(String::as_str).collect::<Vec<_>>();
+        let output = client
+            .send_with_autoscale_by(
+                &table_names,
+                client
+                    .dynamodb
+                    .batch_get_item()
+                    .set_request_items(Some(request_items))
+                    .send(),
+                |output, table_name| {
+                    output
+                        .unprocessed_keys
+                        .as_ref()
+                        .is_some_and(|unprocessed| unprocessed.contains_key(table_name))
+                },
+            )
+            .await?;
+        for (table_name, table_items) in output.responses.unwrap_or_default() {
+            items.extend(
+                table_items
+                    .into_iter()
+                    .map(|item| (table_name.clone(), item)),
+            );
+        }
+        request_items = output.unprocessed_keys.unwrap_or_default();
+        if request_items.is_empty() {
+            return Ok(items);
+        }
+        attempt += 1;
+        if !backoff.wait(attempt).await {
+            return Err(Error::RetryLimitExceeded);
+        }
+    }
+}
+
+/// BatchWriteItemで行う1件の書き込み
+#[derive(Debug, Clone, PartialEq)]
+pub enum WriteOperation {
+    Put {
+        table_name: String,
+        item: HashMap<String, AttributeValue>,
+    },
+    Delete {
+        table_name: String,
+        key: HashMap<String, AttributeValue>,
+    },
+}
+
+impl WriteOperation {
+    /// itemを登録します。シリアライズされます。
+    pub fn put<T: Serialize>(table_name: impl Into<String>, item: T) -> Result<Self, Error> {
+        Ok(Self::put_raw(
+            table_name,
+            crate::serde_dynamo::aws_sdk_dynamodb_1::to_item(item)?,
+        ))
+    }
+
+    /// 生のitemを登録します
+    pub fn put_raw(table_name: impl Into<String>, item: HashMap<String, AttributeValue>) -> Self {
+        Self::Put {
+            table_name: table_name.into(),
+            item,
+        }
+    }
+
+    /// itemを削除します
+    pub fn delete(table_name: impl Into<String>, key: impl Into<Key>) -> Self {
+        Self::Delete {
+            table_name: table_name.into(),
+            key: key.into().into_item(),
+        }
+    }
+
+    pub fn table_name(&self) -> &str {
+        match self {
+            Self::Put { table_name, .. } | Self::Delete { table_name, .. } => table_name,
+        }
+    }
+
+    fn into_request(self) -> Result<(String, WriteRequest), Error> {
+        Ok(match self {
+            Self::Put { table_name, item } => (
+                table_name,
+                WriteRequest::builder()
+                    .put_request(PutRequest::builder().set_item(Some(item)).build()?)
+                    .build(),
+            ),
+            Self::Delete { table_name, key } => (
+                table_name,
+                WriteRequest::builder()
+                    .delete_request(DeleteRequest::builder().set_key(Some(key)).build()?)
+                    .build(),
+            ),
+        })
+    }
+
+    fn from_request(table_name: String, request: WriteRequest) -> Option<Self> {
+        match (request.put_request, request.delete_request) {
+            (Some(put), _) => Some(Self::Put {
+                table_name,
+                item: put.item,
+            }),
+            (None, Some(delete)) => Some(Self::Delete {
+                table_name,
+                key: delete.key,
+            }),
+            (None, None) => None,
+        }
+    }
+}
+
+/// [`Client::batch_write`]、[`Client::batch_writer`]で行う書き込みの一覧と設定
+/// ```
+/// # use dynamodb_utils::{BatchWrite, Error};
+/// # #[derive(serde::Serialize)]
+/// # struct User { id: String }
+/// # fn main() -> Result<(), Error> {
+/// let request = BatchWrite::new()
+///     .put("users", User { id: "a".into() })?
+///     .delete("users", ("id", "b"))
+///     .concurrency(4);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct BatchWrite {
+    operations: Vec<WriteOperation>,
+    concurrency: usize,
+    backoff: Backoff,
+}
+
+impl Default for BatchWrite {
+    fn default() -> Self {
+        Self {
+            operations: vec![],
+            concurrency: 1,
+            backoff: Backoff::default(),
+        }
+    }
+}
+
+impl BatchWrite {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// itemを登録します。シリアライズされます。
+    pub fn put<T: Serialize>(self, table_name: impl Into<String>, item: T) -> Result<Self, Error> {
+        Ok(self.push(WriteOperation::put(table_name, item)?))
+    }
+
+    /// 生のitemを登録します
+    pub fn put_raw(
+        self,
+        table_name: impl Into<String>,
+        item: HashMap<String, AttributeValue>,
+    ) -> Self {
+        self.push(WriteOperation::put_raw(table_name, item))
+    }
+
+    /// itemを削除します
+    pub fn delete(self, table_name: impl Into<String>, key: impl Into<Key>) -> Self {
+        self.push(WriteOperation::delete(table_name, key))
+    }
+
+    /// 書き込みを追加します
+    pub fn push(mut self, operation: WriteOperation) -> Self {
+        self.operations.push(operation);
+        self
+    }
+
+    /// 同時に送るリクエスト数を指定します。デフォルトは1です。
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// `UnprocessedItems`を再送するときの待ち時間を指定します
+    pub fn backoff(mut self, backoff: Backoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+}
+
+/// BatchWriteItemの結果
+#[derive(Debug, Default)]
+pub struct BatchWriteOutput {
+    /// 書き込めた件数
+    pub written: usize,
+    /// 書き込めなかったもの
+    pub failures: Vec<BatchWriteFailure>,
+}
+
+impl BatchWriteOutput {
+    /// 全て書き込めたかどうか
+    pub fn is_success(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    /// 書き込めなかった書き込みの一覧
+    pub fn failed_operations(&self) -> impl Iterator<Item = &WriteOperation> {
+        self.failures
+            .iter()
+            .flat_map(|failure| failure.operations.iter())
+    }
+
+    fn merge(&mut self, other: BatchWriteOutput) {
+        self.written += other.written;
+        self.failures.extend(other.failures);
+    }
+}
+
+/// 同じ原因で書き込めなかったものの一覧
+#[derive(Debug)]
+pub struct BatchWriteFailure {
+    pub error: Error,
+    pub operations: Vec<WriteOperation>,
+}
+
+impl<A: Autoscale> Client<A> {
+    /// BatchWriteItemでまとめて書き込みます
+    ///
+    /// 25件ごとにリクエストを分け、`UnprocessedItems`はbackoffしながら再送します。
+    /// 書き込めなかったものは[`BatchWriteOutput::failures`]に入ります。
+    /// 同じitemへの書き込みが同じリクエストに入るとそのリクエスト全体が失敗します。
+    pub async fn batch_write(&self, request: BatchWrite) -> BatchWriteOutput {
+        write_all(
+            self.clone(),
+            request.backoff,
+            request.concurrency,
+            request.operations,
+        )
+        .await
+    }
+
+    /// 書き込みを少しずつ流し込める[`BatchWriter`]を作ります
+    ///
+    /// `request`に入っている書き込みは、最初から流し込まれたものとして扱います。
+    pub fn batch_writer(&self, request: BatchWrite) -> BatchWriter<A> {
+        BatchWriter {
+            client: self.clone(),
+            backoff: request.backoff,
+            concurrency: request.concurrency,
+            buffer: request.operations,
+            in_flight: None,
+            output: BatchWriteOutput::default(),
+        }
+    }
+}
+
+/// [`WriteOperation`]を受け取る[`Sink`]
+///
+/// `25 * concurrency`件溜まるごとに書き込みます。
+/// 最後に[`close`](`futures_util::SinkExt::close`)してから、
+/// [`into_output`](`Self::into_output`)で結果を受け取ってください。
+/// ```no_run
+/// # use dynamodb_utils::*;
+/// # tokio_test::block_on(async {
+/// use futures_util::{stream, SinkExt, StreamExt};
+/// let client = Client::from_env().await;
+/// let mut writer = client.batch_writer(BatchWrite::new().concurrency(4));
+/// let mut operations =
+///     stream::iter((0..1000).map(|i| Ok(WriteOperation::delete("users", ("id", i)))));
+/// writer.send_all(&mut operations).await?;
+/// writer.close().await?;
+/// let output = writer.into_output();
+/// # Ok::<(), Error>(())
+/// # });
+/// ```
+pub struct BatchWriter<A = ()> {
+    client: Client<A>,
+    backoff: Backoff,
+    concurrency: usize,
+    buffer: Vec<WriteOperation>,
+    in_flight: Option<BoxFuture<'static, BatchWriteOutput>>,
+    output: BatchWriteOutput,
+}
+
+impl<A: Autoscale> BatchWriter<A> {
+    /// これまでの書き込みの結果を受け取ります
+    ///
+    /// まだ書き込まれていないものは含まれません。
+    pub fn into_output(self) -> BatchWriteOutput {
+        self.output
+    }
+
+    fn start(&mut self) {
+        let operations = std::mem::take(&mut self.buffer);
+        self.in_flight = Some(
+            write_all(
+                self.client.clone(),
+                self.backoff.clone(),
+                self.concurrency,
+                operations,
+            )
+            .boxed(),
+        );
+    }
+
+    fn poll_in_flight(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        if let Some(in_flight) = &mut self.in_flight {
+            let output = ready!(in_flight.poll_unpin(cx));
+            self.output.merge(output);
+            self.in_flight = None;
+        }
+        Poll::Ready(())
+    }
+}
+
+// 中身をpinして使うことはないので、`A`によらずUnpinにできる
+impl<A> Unpin for BatchWriter<A> {}
+
+impl<A: Autoscale> Sink<WriteOperation> for BatchWriter<A> {
+    type Error = Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        ready!(self.poll_in_flight(cx));
+        if self.buffer.len() >= BATCH_WRITE_LIMIT * self.concurrency {
+            self.start();
+            ready!(self.poll_in_flight(cx));
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: WriteOperation) -> Result<(), Error> {
+        self.buffer.push(item);
+        Ok(())
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        ready!(self.poll_in_flight(cx));
+        if !self.buffer.is_empty() {
+            self.start();
+            ready!(self.poll_in_flight(cx));
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        self.poll_flush(cx)
+    }
+}
+
+/// 25件ずつに分けて、`concurrency`並列で書き込みます
+async fn write_all<A: Autoscale>(
+    client: Client<A>,
+    backoff: Backoff,
+    concurrency: usize,
+    operations: Vec<WriteOperation>,
+) -> BatchWriteOutput {
+    let mut chunks = vec![];
+    let mut operations = operations.into_iter().peekable();
+    while operations.peek().is_some() {
+        chunks.push(
+            operations
+                .by_ref()
+                .take(BATCH_WRITE_LIMIT)
+                .collect::<Vec<_>>(),
+        );
+    }
+    stream::iter(chunks)
+        .map(|chunk| batch_write_chunk(client.clone(), backoff.clone(), chunk))
+        .buffer_unordered(concurrency)
+        .fold(
+            BatchWriteOutput::default(),
+            |mut output, chunk| async move {
+                output.merge(chunk);
+                output
+            },
+        )
+        .await
+}
+
+/// 25件以下の書き込みを、全て処理されるか再送の上限になるまで行います
+async fn batch_write_chunk<A: Autoscale>(
+    client: Client<A>,
+    backoff: Backoff,
+    chunk: Vec<WriteOperation>,
+) -> BatchWriteOutput {
+    if let Some(mock) = &client.mock {
+        let mut output = BatchWriteOutput::default();
+        for operation in chunk {
+            let result = match operation.clone() {
+                WriteOperation::Put { table_name, item } => {
+                    mock.put_item(&table_name, item, None, None).map(drop)
+                }
+                WriteOperation::Delete { table_name, key } => {
+                    mock.delete_item(&table_name, key, None, None).map(drop)
+                }
+            };
+            match result {
+                Ok(()) => output.written += 1,
+                Err(error) => output.failures.push(BatchWriteFailure {
+                    error,
+                    operations: vec![operation],
+                }),
+            }
+        }
+        return output;
+    }
+    // リクエストにできなかったものだけを失敗にして、残りは送ります
+    let mut output = BatchWriteOutput::default();
+    let mut request_items = HashMap::<String, Vec<WriteRequest>>::new();
+    let mut total = 0;
+    for operation in chunk {
+        match operation.clone().into_request() {
+            Ok((table_name, request)) => {
+                request_items.entry(table_name).or_default().push(request);
+                total += 1;
+            }
+            Err(error) => output.failures.push(BatchWriteFailure {
+                error,
+                operations: vec![operation],
+            }),
+        }
+    }
+    if !request_items.is_empty() {
+        output.merge(send_chunk(&client, &backoff, total, request_items).await);
+    }
+    output
+}
+
+/// `UnprocessedItems`が無くなるか再送の上限になるまで送ります。`total`は書き込みの件数です。
+async fn send_chunk<A: Autoscale>(
+    client: &Client<A>,
+    backoff: &Backoff,
+    total: usize,
+    mut request_items: HashMap<String, Vec<WriteRequest>>,
+) -> BatchWriteOutput {
+    let mut attempt = 0;
+    loop {
+        let table_names = request_items.keys().cloned().collect::<Vec<_>>();
+        let table_names = table_names.iter().map(String::as_str).collect::<Vec<_>>();
+        let result = client
+            .send_with_autoscale_by(
+                &table_names,
+                client
+                    .dynamodb
+                    .batch_write_item()
+                    .set_request_items(Some(request_items.clone()))
+                    .send(),
+                |output, table_name| {
+                    output
+                        .unprocessed_items
+                        .as_ref()
+                        .is_some_and(|unprocessed| unprocessed.contains_key(table_name))
+                },
+            )
+            .await;
+        let unprocessed = match result {
+            Ok(output) => output.unprocessed_items.unwrap_or_default(),
+            Err(error) => return failed(total, error, request_items),
+        };
+        request_items = unprocessed;
+        if request_items.is_empty() {
+            return BatchWriteOutput {
+                written: total,
+                failures: vec![],
+            };
+        }
+        attempt += 1;
+        if !backoff.wait(attempt).await {
+            return failed(total, Error::RetryLimitExceeded, request_items);
+        }
+    }
+}
+
+fn failed(
+    total: usize,
+    error: Error,
+    request_items: HashMap<String, Vec<WriteRequest>>,
+) -> BatchWriteOutput {
+    let operations = request_items
+        .into_iter()
+        .flat_map(|(table_name, requests)| {
+            requests.into_iter().filter_map(move |request| {
+                WriteOperation::from_request(table_name.clone(), request)
+            })
+        })
+        .collect::<Vec<_>>();
+    BatchWriteOutput {
+        written: total - operations.len(),
+        failures: vec![BatchWriteFailure { error, operations }],
+    }
+}
@@ -1,22 +1,32 @@
 use crate::{
+    expression::ExpressionAttributes,
     into_values::Number,
+    mock::{self, MockDatabase},
     sdk::{
         operation::{
             delete_item::DeleteItemOutput, delete_table::DeleteTableOutput,
             get_item::GetItemOutput, put_item::PutItemOutput, update_item::UpdateItemOutput,
             update_table::UpdateTableOutput,
         },
-        types::{AttributeValue, ProvisionedThroughput},
+        types::{
+            AttributeValue, ProvisionedThroughput, ReturnValue, ReturnValuesOnConditionCheckFailure,
+        },
         PaginationStreamExt,
     },
     utils::deserialize_stream,
-    IntoValue,
+    Autoscale, CancellationReason, Condition, IntoValue, Key, Path, Query, Scan, TableDefinition,
+    Update, WaitConfig,
+};
+use aws_sdk_dynamodb::{
+    error::{ProvideErrorMetadata, SdkError},
+    operation::create_table::CreateTableOutput,
+    types::ScalarAttributeType,
 };
-use aws_sdk_dynamodb::{operation::create_table::CreateTableOutput, types::{AttributeDefinition, BillingMode, KeySchemaElement, KeyType, ScalarAttributeType}};
-use futures_util::{TryStream, TryStreamExt};
+use futures_util::{StreamExt, TryStream, TryStreamExt};
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, fmt::Display};
+use std::{collections::HashMap, fmt::Display, future::Future, sync::Arc};
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TableType {
     OnDemand,
     Provisioned(i64, i64),
@@ -26,9 +36,10 @@ pub enum TableType {
 /// 低レベルな操作は[`raw_client`](`Client::raw_client`)を使って取得したものを使ってください
 #[derive(Debug, Clone)]
 pub struct Client<A = ()> {
-    dynamodb: aws_sdk_dynamodb::Client,
-    #[allow(dead_code)] // Todo: 後でautoscale対応を足す
-    autoscale: A,
+    pub(crate) dynamodb: aws_sdk_dynamodb::Client,
+    pub(crate) autoscale: A,
+    pub(crate) table_prefix: Option<String>,
+    pub(crate) mock: Option<Arc<MockDatabase>>,
 }
 
 impl Client {
@@ -37,6 +48,8 @@ impl Client {
         Self {
             dynamodb: dynamo,
             autoscale: (),
+            table_prefix: None,
+            mock: None,
         }
     }
 
@@ -58,42 +71,94 @@ impl<A> Client<A> {
         &self.dynamodb
     }
 
+    /// 流量の調整方法を取得する
+    pub fn autoscale(&self) -> &A {
+        &self.autoscale
+    }
+}
+
+impl<A: Autoscale> Client<A> {
+    /// `table_names`へ送ってよくなるまで待ちます
+    pub(crate) async fn acquire(&self, table_names: &[&str]) {
+        for table_name in table_names {
+            self.autoscale.acquire(table_name).await;
+        }
+    }
+
+    /// [`Autoscale`]で流量を調整しながら`request`を送ります
+    pub(crate) async fn send_with_autoscale<T, E, R>(
+        &self,
+        table_names: &[&str],
+        request: impl Future<Output = Result<T, SdkError<E, R>>>,
+    ) -> Result<T, Error>
+    where
+        SdkError<E, R>: Into<aws_sdk_dynamodb::Error>,
+    {
+        self.send_with_autoscale_by(table_names, request, |_, _| false)
+            .await
+    }
+
+    /// [`send_with_autoscale`](Self::send_with_autoscale)と同じですが、
+    /// 成功した場合もテーブルごとに`throttled`でスロットリングされたかを判定します
+    pub(crate) async fn send_with_autoscale_by<T, E, R>(
+        &self,
+        table_names: &[&str],
+        request: impl Future<Output = Result<T, SdkError<E, R>>>,
+        throttled: impl Fn(&T, &str) -> bool,
+    ) -> Result<T, Error>
+    where
+        SdkError<E, R>: Into<aws_sdk_dynamodb::Error>,
+    {
+        self.acquire(table_names).await;
+        let result = request.await.map_err(from_sdk_error);
+        for table_name in table_names {
+            let throttled = match &result {
+                Ok(output) => throttled(output, table_name),
+                Err(e) => e.is_throttling(),
+            };
+            self.autoscale
+                .record(&self.dynamodb, table_name, throttled)
+                .await;
+        }
+        result
+    }
+
     /// itemを取得します
     ///
     /// 生の値を取得します
     pub async fn get_item_raw(
         &self,
         table_name: impl Into<String>,
-        key_name: impl Into<String>,
-        key_value: impl IntoValue,
+        key: impl Into<Key>,
     ) -> Result<GetItemOutput, Error> {
-        self.dynamodb
-            .get_item()
-            .table_name(table_name)
-            .key(key_name, key_value.into_value())
-            .send()
-            .await
-            .map_err(from_aws_sdk_dynamodb_error)
+        let table_name = table_name.into();
+        if let Some(mock) = &self.mock {
+            return mock.get_item(&table_name, key.into().into_item());
+        }
+        self.send_with_autoscale(
+            &[&table_name],
+            self.dynamodb
+                .get_item()
+                .table_name(&table_name)
+                .set_key(Some(key.into().into_item()))
+                .send(),
+        )
+        .await
     }
 
     /// itemを取得して、デシリアライズされた形にします
     pub async fn get_item<T>(
         &self,
         table_name: impl Into<String>,
-        key_name: impl Into<String>,
-        key_value: impl IntoValue,
+        key: impl Into<Key>,
     ) -> Result<T, Error>
     where
         for<'de> T: Deserialize<'de>,
     {
-        self.get_item_raw(table_name, key_name, key_value)
-            .await
-            .and_then(|value| {
-                crate::serde_dynamo::aws_sdk_dynamodb_1::from_item(
-                    value.item.ok_or(Error::NotFound)?,
-                )
+        self.get_item_raw(table_name, key).await.and_then(|value| {
+            crate::serde_dynamo::aws_sdk_dynamodb_1::from_item(value.item.ok_or(Error::NotFound)?)
                 .map_err(Into::into)
-            })
+        })
     }
 
     /// itemを登録します
@@ -103,13 +168,7 @@ impl<A> Client<A> {
         table_name: impl Into<String>,
         item: HashMap<String, AttributeValue>,
     ) -> Result<PutItemOutput, Error> {
-        self.dynamodb
-            .put_item()
-            .table_name(table_name)
-            .set_item(Some(item))
-            .send()
-            .await
-            .map_err(from_aws_sdk_dynamodb_error)
+        self.put_item_inner(table_name, item, None, None).await
     }
 
     /// itemを登録します
@@ -126,80 +185,373 @@ impl<A> Client<A> {
         .await
     }
 
+    /// 条件を満たす場合のみ生のitemを登録します。
+    /// 条件を満たさない場合は[`Error::ConditionalCheckFailed`]になります。
+    pub async fn put_item_raw_if(
+        &self,
+        table_name: impl Into<String>,
+        item: HashMap<String, AttributeValue>,
+        condition: Condition,
+    ) -> Result<PutItemOutput, Error> {
+        self.put_item_inner(table_name, item, Some(condition), None)
+            .await
+    }
+
+    /// 条件を満たす場合のみitemを登録します。
+    /// 条件を満たさない場合は[`Error::ConditionalCheckFailed`]になります。
+    pub async fn put_item_if<T: Serialize>(
+        &self,
+        table_name: impl Into<String>,
+        data: T,
+        condition: Condition,
+    ) -> Result<PutItemOutput, Error> {
+        self.put_item_raw_if(
+            table_name,
+            crate::serde_dynamo::aws_sdk_dynamodb_1::to_item(data)?,
+            condition,
+        )
+        .await
+    }
+
+    /// itemを登録し、上書きされる前のitemを返します。
+    /// 新規登録だった場合は`None`になります。
+    /// ```
+    /// # use dynamodb_utils::*;
+    /// #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    /// struct User {
+    ///     id: String,
+    ///     name: String,
+    /// }
+    ///
+    /// # tokio_test::block_on(async {
+    /// let client = Client::mock();
+    /// client
+    ///     .create_table("users", "id", None::<String>, TableType::OnDemand)
+    ///     .await?;
+    /// let alice = User { id: "u1".into(), name: "alice".into() };
+    /// assert_eq!(client.put_item_returning_old::<User>("users", alice).await?, None);
+    /// let bob = User { id: "u1".into(), name: "bob".into() };
+    /// let old = client.put_item_returning_old::<User>("users", bob).await?;
+    /// assert_eq!(old.unwrap().name, "alice");
+    ///
+    /// let deleted: Option<User> = client.delete_item_returning("users", ("id", "u1")).await?;
+    /// assert_eq!(deleted.unwrap().name, "bob");
+    /// # Ok::<(), Error>(())
+    /// # });
+    /// ```
+    pub async fn put_item_returning_old<T>(
+        &self,
+        table_name: impl Into<String>,
+        data: T,
+    ) -> Result<Option<T>, Error>
+    where
+        T: Serialize,
+        for<'de> T: Deserialize<'de>,
+    {
+        let item = crate::serde_dynamo::aws_sdk_dynamodb_1::to_item(data)?;
+        let output = self
+            .put_item_inner(table_name, item, None, Some(ReturnValue::AllOld))
+            .await?;
+        deserialize_attributes(output.attributes)
+    }
+
+    /// version項目を使った楽観ロック付きでitemを登録します。
+    ///
+    /// `version_attribute`の値が保存されているものと一致するときだけ登録し、
+    /// versionを1つ進めて保存します。新しいversionを返します。
+    /// versionが無い、または`0`のitemは新規登録として扱います。
+    /// 他から更新されていた場合は[`Error::ConditionalCheckFailed`]になります。
+    pub async fn put_item_versioned<T: Serialize>(
+        &self,
+        table_name: impl Into<String>,
+        data: T,
+        version_attribute: impl Into<String>,
+    ) -> Result<i64, Error> {
+        let version_attribute = version_attribute.into();
+        let mut item = crate::serde_dynamo::aws_sdk_dynamodb_1::to_item(data)?;
+        let current = match item.get(&version_attribute) {
+            Some(AttributeValue::N(n)) => n.parse::<i64>().map_err(|_| Error::InvalidVersion)?,
+            Some(AttributeValue::Null(_)) | None => 0,
+            Some(_) => return Err(Error::InvalidVersion),
+        };
+        let next = current.checked_add(1).ok_or(Error::InvalidVersion)?;
+        let condition = expected_version_condition(&version_attribute, current);
+        item.insert(version_attribute, next.into_value());
+        self.put_item_raw_if(table_name, item, condition).await?;
+        Ok(next)
+    }
+
+    async fn put_item_inner(
+        &self,
+        table_name: impl Into<String>,
+        item: HashMap<String, AttributeValue>,
+        condition: Option<Condition>,
+        return_values: Option<ReturnValue>,
+    ) -> Result<PutItemOutput, Error> {
+        if let Some(mock) = &self.mock {
+            return mock.put_item(
+                &table_name.into(),
+                item,
+                condition.as_ref(),
+                return_values.as_ref(),
+            );
+        }
+        let mut attributes = ExpressionAttributes::default();
+        let condition = condition.map(|condition| condition.expression(&mut attributes));
+        let (names, values) = attributes.into_parts();
+        let table_name = table_name.into();
+        self.send_with_autoscale(
+            &[&table_name],
+            self.dynamodb
+                .put_item()
+                .table_name(&table_name)
+                .set_item(Some(item))
+                .set_return_values(return_values)
+                .set_return_values_on_condition_check_failure(old_item_on_failure(&condition))
+                .set_condition_expression(condition)
+                .set_expression_attribute_names(names)
+                .set_expression_attribute_values(values)
+                .send(),
+        )
+        .await
+    }
+
     /// itemを削除します。
     pub async fn delete_item(
         &self,
         table_name: impl Into<String>,
-        key_name: impl Into<String>,
-        key_value: impl IntoValue,
+        key: impl Into<Key>,
     ) -> Result<DeleteItemOutput, Error> {
-        self.dynamodb
-            .delete_item()
-            .table_name(table_name)
-            .key(key_name, key_value.into_value())
-            .send()
+        self.delete_item_inner(table_name, key, None, None).await
+    }
+
+    /// 条件を満たす場合のみitemを削除します。
+    /// 条件を満たさない場合は[`Error::ConditionalCheckFailed`]になります。
+    pub async fn delete_item_if(
+        &self,
+        table_name: impl Into<String>,
+        key: impl Into<Key>,
+        condition: Condition,
+    ) -> Result<DeleteItemOutput, Error> {
+        self.delete_item_inner(table_name, key, Some(condition), None)
             .await
-            .map_err(from_aws_sdk_dynamodb_error)
+    }
+
+    /// itemを削除し、削除したitemを返します。
+    /// itemが無かった場合は`None`になります。
+    pub async fn delete_item_returning<T>(
+        &self,
+        table_name: impl Into<String>,
+        key: impl Into<Key>,
+    ) -> Result<Option<T>, Error>
+    where
+        for<'de> T: Deserialize<'de>,
+    {
+        let output = self
+            .delete_item_inner(table_name, key, None, Some(ReturnValue::AllOld))
+            .await?;
+        deserialize_attributes(output.attributes)
+    }
+
+    async fn delete_item_inner(
+        &self,
+        table_name: impl Into<String>,
+        key: impl Into<Key>,
+        condition: Option<Condition>,
+        return_values: Option<ReturnValue>,
+    ) -> Result<DeleteItemOutput, Error> {
+        if let Some(mock) = &self.mock {
+            return mock.delete_item(
+                &table_name.into(),
+                key.into().into_item(),
+                condition.as_ref(),
+                return_values.as_ref(),
+            );
+        }
+        let mut attributes = ExpressionAttributes::default();
+        let condition = condition.map(|condition| condition.expression(&mut attributes));
+        let (names, values) = attributes.into_parts();
+        let table_name = table_name.into();
+        self.send_with_autoscale(
+            &[&table_name],
+            self.dynamodb
+                .delete_item()
+                .table_name(&table_name)
+                .set_key(Some(key.into().into_item()))
+                .set_return_values(return_values)
+                .set_return_values_on_condition_check_failure(old_item_on_failure(&condition))
+                .set_condition_expression(condition)
+                .set_expression_attribute_names(names)
+                .set_expression_attribute_values(values)
+                .send(),
+        )
+        .await
+    }
+
+    /// itemを更新します
+    /// [`Update`]に積んだ変更がatomicに適用されます。
+    ///
+    /// [`Update::condition`]を満たさない場合は[`Error::ConditionalCheckFailed`]になります。
+    /// 変更が一つも無い[`Update`]は[`Error::EmptyUpdate`]になります。
+    pub async fn update_item(
+        &self,
+        table_name: impl Into<String>,
+        key: impl Into<Key>,
+        update: Update,
+    ) -> Result<UpdateItemOutput, Error> {
+        if update.is_empty() {
+            return Err(Error::EmptyUpdate);
+        }
+        if let Some(mock) = &self.mock {
+            return mock.update_item(&table_name.into(), key.into().into_item(), &update);
+        }
+        let mut attributes = ExpressionAttributes::default();
+        let update_expression = update.expression(&mut attributes);
+        let condition = update
+            .condition
+            .map(|condition| condition.expression(&mut attributes));
+        let (names, values) = attributes.into_parts();
+        let table_name = table_name.into();
+        self.send_with_autoscale(
+            &[&table_name],
+            self.dynamodb
+                .update_item()
+                .table_name(&table_name)
+                .set_key(Some(key.into().into_item()))
+                .update_expression(update_expression)
+                .set_return_values_on_condition_check_failure(old_item_on_failure(&condition))
+                .set_condition_expression(condition)
+                .set_return_values(update.return_values)
+                .set_expression_attribute_names(names)
+                .set_expression_attribute_values(values)
+                .send(),
+        )
+        .await
+    }
+
+    /// itemを更新し、`return_value`で指定した内容を返します
+    ///
+    /// - [`ReturnValue::AllOld`] 更新前のitem全体
+    /// - [`ReturnValue::AllNew`] 更新後のitem全体
+    /// - [`ReturnValue::UpdatedOld`] 更新した項目の更新前の値
+    /// - [`ReturnValue::UpdatedNew`] 更新した項目の更新後の値
+    ///
+    /// 返す内容が無い場合は`None`になります。
+    /// ```
+    /// # use dynamodb_utils::{sdk::types::ReturnValue, *};
+    /// #[derive(Debug, serde::Deserialize)]
+    /// struct Stock {
+    ///     count: i64,
+    /// }
+    ///
+    /// # tokio_test::block_on(async {
+    /// let client = Client::mock();
+    /// client
+    ///     .create_table("stocks", "id", None::<String>, TableType::OnDemand)
+    ///     .await?;
+    /// let update = Update::new().add("count", 5);
+    /// let stock: Option<Stock> = client
+    ///     .update_item_returning("stocks", ("id", "s1"), update, ReturnValue::AllNew)
+    ///     .await?;
+    /// assert_eq!(stock.unwrap().count, 5);
+    /// # Ok::<(), Error>(())
+    /// # });
+    /// ```
+    pub async fn update_item_returning<T>(
+        &self,
+        table_name: impl Into<String>,
+        key: impl Into<Key>,
+        update: Update,
+        return_value: ReturnValue,
+    ) -> Result<Option<T>, Error>
+    where
+        for<'de> T: Deserialize<'de>,
+    {
+        let output = self
+            .update_item(table_name, key, update.return_values(return_value))
+            .await?;
+        deserialize_attributes(output.attributes)
+    }
+
+    /// version項目を使った楽観ロック付きでitemを更新します。
+    ///
+    /// `version_attribute`の値が`expected_version`と一致するときだけ更新し、
+    /// versionを1つ進めます。新しいversionを返します。
+    /// 他から更新されていた場合は[`Error::ConditionalCheckFailed`]になります。
+    pub async fn update_item_versioned(
+        &self,
+        table_name: impl Into<String>,
+        key: impl Into<Key>,
+        update: Update,
+        version_attribute: impl Into<String>,
+        expected_version: i64,
+    ) -> Result<i64, Error> {
+        let version_attribute = version_attribute.into();
+        let next = expected_version
+            .checked_add(1)
+            .ok_or(Error::InvalidVersion)?;
+        let update = update
+            .condition(expected_version_condition(
+                &version_attribute,
+                expected_version,
+            ))
+            .set(Path::attribute(version_attribute), next);
+        self.update_item(table_name, key, update).await?;
+        Ok(next)
     }
 
     /// 特定のアイテムの特定の項目の値を登録、更新します
     /// この操作はatomicであることが保証されています。
     ///
-    /// - `key_name` 更新対象のitemの、keyの項目名
-    /// - `key_value` 更新対象のitemの、keyの値
-    /// - `update_target` 更新対象の値の項目名
+    /// - `key` 更新対象のitemのkey
+    /// - `update_target` 更新対象の値の項目名。[`Path`]と同じく`a.b[0]`の形で解釈します
     /// - `value` 更新対象の値
     pub async fn set_value(
         &self,
         table_name: impl Into<String>,
-        key_name: impl Into<String>,
-        key_value: impl IntoValue,
+        key: impl Into<Key>,
         update_target: impl Display,
         value: impl IntoValue,
     ) -> Result<UpdateItemOutput, Error> {
-        self.dynamodb
-            .update_item()
-            .table_name(table_name)
-            .key(key_name, key_value.into_value())
-            .update_expression(format!("SET {update_target} = :val"))
-            .expression_attribute_values("val", value.into_value())
-            .send()
-            .await
-            .map_err(from_aws_sdk_dynamodb_error)
+        self.update_item(
+            table_name,
+            key,
+            Update::new().set(update_target.to_string(), value),
+        )
+        .await
     }
 
     /// 特定のアイテムの特定の項目の数値を加算します。
     /// この操作はatomicであることが保証されています。
     ///
-    /// - `key_name` 更新対象のitemの、keyの項目名
-    /// - `key_value` 更新対象のitemの、keyの値
-    /// - `update_target` 更新対象の値の項目名
+    /// - `key` 更新対象のitemのkey
+    /// - `update_target` 更新対象の値の項目名。[`Path`]と同じく`a.b[0]`の形で解釈します
     /// - `value` 更新対象の加算値
     pub async fn add_value(
         &self,
         table_name: impl Into<String>,
-        key_name: impl Into<String>,
-        key_value: impl IntoValue,
+        key: impl Into<Key>,
         update_target: impl Display,
         value: impl Number,
     ) -> Result<UpdateItemOutput, Error> {
-        self.dynamodb
-            .update_item()
-            .table_name(table_name)
-            .key(key_name, key_value.into_value())
-            .update_expression(format!("ADD {update_target} :val"))
-            .expression_attribute_values("val", value.into_value())
-            .send()
-            .await
-            .map_err(from_aws_sdk_dynamodb_error)
+        self.update_item(
+            table_name,
+            key,
+            Update::new().add(update_target.to_string(), value),
+        )
+        .await
     }
 
     /// scanを掛けます
     /// 具体的な型で受けたいなら[`scan_item`](`Self::scan_item`)があります。
+    /// 条件の指定や並列のscanは[`scan_item_raw_with`](`Self::scan_item_raw_with`)を使ってください。
     pub fn scan_item_raw(
         &self,
         table_name: impl Into<String>,
     ) -> impl TryStream<Ok = HashMap<String, AttributeValue>, Error = Error> {
+        if let Some(mock) = &self.mock {
+            return mock::into_stream(mock.scan(&table_name.into(), &Scan::new())).left_stream();
+        }
         self.dynamodb
             .scan()
             .table_name(table_name)
@@ -207,7 +559,8 @@ impl<A> Client<A> {
             .items()
             .send()
             .into_stream_03x()
-            .map_err(from_aws_sdk_dynamodb_error)
+            .map_err(from_sdk_error)
+            .right_stream()
     }
 
     /// scanを掛けます
@@ -221,6 +574,44 @@ impl<A> Client<A> {
         deserialize_stream(self.scan_item_raw(table_name))
     }
 
+    /// queryを掛けます
+    /// 具体的な型で受けたいなら[`query_item`](`Self::query_item`)があります。
+    ///
+    /// [`Query::limit`]を指定した場合は、その件数でstreamが終わります。
+    pub fn query_item_raw(
+        &self,
+        table_name: impl Into<String>,
+        query: Query,
+    ) -> impl TryStream<Ok = HashMap<String, AttributeValue>, Error = Error> {
+        let limit = query
+            .limit
+            .map_or(usize::MAX, |limit| limit.max(0) as usize);
+        let items = match &self.mock {
+            Some(mock) => mock::into_stream(mock.query(&table_name.into(), &query)).left_stream(),
+            None => query
+                .apply(self.dynamodb.query().table_name(table_name))
+                .into_paginator()
+                .items()
+                .send()
+                .into_stream_03x()
+                .map_err(from_sdk_error)
+                .right_stream(),
+        };
+        items.take(limit)
+    }
+
+    /// queryを掛けます
+    pub fn query_item<T>(
+        &self,
+        table_name: impl Into<String>,
+        query: Query,
+    ) -> impl TryStream<Ok = T, Error = Error>
+    where
+        for<'de> T: Deserialize<'de>,
+    {
+        deserialize_stream(self.query_item_raw(table_name, query))
+    }
+
     /// テーブルのスループット値を更新します
     pub async fn update_provisioned_throughput(
         &self,
@@ -228,6 +619,9 @@ impl<A> Client<A> {
         read_capacity: i64,
         write_capacity: i64,
     ) -> Result<UpdateTableOutput, Error> {
+        if let Some(mock) = &self.mock {
+            return mock.update_table(&table_name.into());
+        }
         self.dynamodb
             .update_table()
             .table_name(table_name)
@@ -240,7 +634,7 @@ impl<A> Client<A> {
             )
             .send()
             .await
-            .map_err(from_aws_sdk_dynamodb_error)
+            .map_err(from_sdk_error)
     }
 
     // テーブルを削除します
@@ -248,12 +642,15 @@ impl<A> Client<A> {
         &self,
         table_name: impl Into<String>,
     ) -> Result<DeleteTableOutput, Error> {
+        if let Some(mock) = &self.mock {
+            return mock.delete_table(&table_name.into());
+        }
         self.dynamodb
             .delete_table()
             .table_name(table_name)
             .send()
             .await
-            .map_err(from_aws_sdk_dynamodb_error)
+            .map_err(from_sdk_error)
     }
 
     pub async fn create_table(
@@ -263,60 +660,88 @@ impl<A> Client<A> {
         sort_key: Option<impl Into<String>>,
         table_type: TableType,
     ) -> Result<CreateTableOutput, Error> {
-        let key = key.into();
-        let ad = AttributeDefinition::builder()
-            .attribute_name(&key)
-            .attribute_type(ScalarAttributeType::S)
-            .build()?;
-
-        let ks = KeySchemaElement::builder()
-            .attribute_name(key)
-            .key_type(KeyType::Hash)
-            .build()?;
-
-        let (ads, kss) = if let Some(sort_key) = sort_key {
-            let sort_key = sort_key.into();
-            let pair1= {
-                let sort_key = AttributeDefinition::builder()
-                    .attribute_name(&sort_key)
-                    .attribute_type(ScalarAttributeType::S)
-                    .build()?;
-                vec![ad, sort_key]
-            };
-            let pair2 = {
-                let sort_key = KeySchemaElement::builder()
-                .attribute_name(&sort_key)
-                .key_type(KeyType::Range)
-                .build()?;
-                vec![ks, sort_key]
-            };
-            (pair1, pair2)
-        } else {
-            (vec![ad], vec![ks])
+        let definition = TableDefinition::new(table_name, key, ScalarAttributeType::S);
+        let definition = match sort_key {
+            Some(sort_key) => definition.sort_key(sort_key, ScalarAttributeType::S),
+            None => definition,
         };
+        self.create_table_with(definition.table_type(table_type))
+            .await
+    }
 
-        let table_builder = self
-            .dynamodb
-            .create_table()
-            .table_name(table_name)
-            .set_key_schema(Some(kss))
-            .set_attribute_definitions(Some(ads));
-
-        match table_type {
-            TableType::OnDemand => {
-                table_builder
-                    .billing_mode(BillingMode::PayPerRequest)
-                    .send()
-                    .await
-            }
-            TableType::Provisioned(read_capacity, write_capacity) => {
-                let pt = ProvisionedThroughput::builder()
-                    .read_capacity_units(read_capacity)
-                    .write_capacity_units(write_capacity)
-                    .build()?;
-                table_builder.provisioned_throughput(pt).send().await
-            }
-        }.map_err(|e| e.into())
+    /// 定義に従ってテーブルを作ります
+    ///
+    /// [`TableDefinition::ttl`]を指定した場合は、ACTIVEになるのを待ってからTTLを有効にします。
+    pub async fn create_table_with(
+        &self,
+        definition: TableDefinition,
+    ) -> Result<CreateTableOutput, Error> {
+        let output = self.create_table_only(&definition).await?;
+        self.apply_ttl(&definition, WaitConfig::default()).await?;
+        Ok(output)
+    }
+
+    /// TTLは設定せずにテーブルを作ります
+    pub(crate) async fn create_table_only(
+        &self,
+        definition: &TableDefinition,
+    ) -> Result<CreateTableOutput, Error> {
+        if let Some(mock) = &self.mock {
+            return mock.create_table(definition);
+        }
+        definition
+            .apply(self.dynamodb.create_table())?
+            .send()
+            .await
+            .map_err(from_sdk_error)
+    }
+
+    /// 作ったテーブルがACTIVEになるのを待って、定義にあるTTLを有効にします
+    pub(crate) async fn apply_ttl(
+        &self,
+        definition: &TableDefinition,
+        config: WaitConfig,
+    ) -> Result<(), Error> {
+        let Some(attribute) = definition.get_ttl() else {
+            return Ok(());
+        };
+        self.wait_until_active(definition.get_table_name(), config)
+            .await?;
+        self.enable_ttl(definition.get_table_name(), attribute)
+            .await?;
+        Ok(())
+    }
+}
+
+/// 条件を満たさなかったときに、[`Error::ConditionalCheckFailed`]へ元のitemを入れてもらいます
+pub(crate) fn old_item_on_failure(
+    condition: &Option<String>,
+) -> Option<ReturnValuesOnConditionCheckFailure> {
+    condition
+        .as_ref()
+        .map(|_| ReturnValuesOnConditionCheckFailure::AllOld)
+}
+
+/// ReturnValuesで返ってきたitemをデシリアライズします
+fn deserialize_attributes<T>(
+    attributes: Option<HashMap<String, AttributeValue>>,
+) -> Result<Option<T>, Error>
+where
+    for<'de> T: Deserialize<'de>,
+{
+    attributes
+        .filter(|attributes| !attributes.is_empty())
+        .map(crate::serde_dynamo::aws_sdk_dynamodb_1::from_item)
+        .transpose()
+        .map_err(Into::into)
+}
+
+/// 楽観ロックで保存されているはずのversionの条件
+fn expected_version_condition(version_attribute: &str, version: i64) -> Condition {
+    if version == 0 {
+        Condition::attribute_not_exists(Path::attribute(version_attribute))
+    } else {
+        Condition::eq(Path::attribute(version_attribute), version)
     }
 }
 
@@ -330,12 +755,226 @@ pub enum Error {
     BuildError(#[from] aws_sdk_dynamodb::error::BuildError),
     #[error("No Item")]
     NotFound,
-    #[error("CreateTableError {0}")]
-    CreateTableError(#[from] aws_sdk_dynamodb::error::SdkError<aws_sdk_dynamodb::operation::create_table::CreateTableError>),
+    /// 条件を満たさなかった。`item`は条件を確認したときに保存されていたitemです。
+    #[error("Condition check failed")]
+    ConditionalCheckFailed {
+        item: Option<HashMap<String, AttributeValue>>,
+    },
+    /// スロットリングされた
+    #[error("Throttled: {0}")]
+    Throttled(Box<aws_sdk_dynamodb::Error>),
+    /// テーブルやindexが見つからない
+    #[error("Resource not found: {0}")]
+    ResourceNotFound(Box<aws_sdk_dynamodb::Error>),
+    /// itemが400KBを超えた
+    #[error("Item too large: {0}")]
+    ItemTooLarge(Box<aws_sdk_dynamodb::Error>),
+    /// リクエストが不正
+    #[error("Validation error: {0}")]
+    Validation(Box<aws_sdk_dynamodb::Error>),
+    #[error("Version attribute is not a number or cannot be incremented")]
+    InvalidVersion,
+    /// keyの値がテーブルのkeyの定義と合わない
+    #[error("Invalid key: {0}")]
+    InvalidKey(String),
+    #[error("Missing attribute {0}")]
+    MissingAttribute(String),
+    #[error("Attribute {attribute} is {found}, expected {expected}")]
+    InvalidType {
+        attribute: String,
+        expected: &'static str,
+        found: &'static str,
+    },
+    #[error("Retry limit exceeded")]
+    RetryLimitExceeded,
+    #[error("Transaction canceled {0:?}")]
+    TransactionCanceled(Vec<CancellationReason>),
+    #[error("Item count mismatch: expected {expected}, got {actual}")]
+    ItemCountMismatch { expected: usize, actual: usize },
+    #[error("Timed out waiting for table {0}")]
+    WaitTimeout(String),
+    #[error("IoError {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid JSON at line {line}: {message}")]
+    InvalidJson { line: usize, message: String },
+    /// itemを指定した形式のJSONで表せない
+    #[error("Item at line {line} cannot be written as {format:?}")]
+    Unencodable {
+        line: usize,
+        format: crate::JsonFormat,
+    },
+    #[error("Import failed, resume from line {resume_from}: {source}")]
+    Import {
+        resume_from: usize,
+        source: Box<Error>,
+    },
+    /// リクエストが時間内に終わらなかった
+    #[error("Timed out: {0}")]
+    Timeout(Box<aws_sdk_dynamodb::Error>),
+    /// 接続できないなど、リクエストを送れなかった
+    #[error("Dispatch failure: {0}")]
+    Dispatch(Box<aws_sdk_dynamodb::Error>),
+    #[error("No tokio runtime is running")]
+    NoRuntime,
+    #[error("Update has no actions")]
+    EmptyUpdate,
+    #[error("Invalid cursor")]
+    InvalidCursor,
+    #[error("{0} is not supported by the mock client")]
+    UnsupportedByMock(&'static str),
+    #[error("Timed out acquiring lock {0}")]
+    LockTimeout(String),
+    #[error("Request {0} is already in progress")]
+    IdempotencyInProgress(String),
+    #[cfg(feature = "streams")]
+    #[error(transparent)]
+    Streams(Box<aws_sdk_dynamodbstreams::Error>),
+}
+
+impl Error {
+    /// 時間をおいて再実行すれば成功する可能性があるかどうか
+    ///
+    /// スロットリングや、DynamoDB内部のエラー、トランザクションの競合、
+    /// タイムアウトや接続の失敗が該当します。
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            Error::Throttled(_) | Error::Timeout(_) | Error::Dispatch(_) => true,
+            Error::DynamoDb(e) => {
+                matches!(
+                    **e,
+                    aws_sdk_dynamodb::Error::InternalServerError(_)
+                        | aws_sdk_dynamodb::Error::TransactionConflictException(_)
+                        | aws_sdk_dynamodb::Error::TransactionInProgressException(_)
+                ) || e.code() == Some("ServiceUnavailable")
+            }
+            Error::TransactionCanceled(reasons) => {
+                let mut causes = reasons.iter().filter(|reason| reason.is_cause()).peekable();
+                causes.peek().is_some()
+                    && causes.all(|reason| {
+                        matches!(
+                            reason.code.as_deref(),
+                            Some(
+                                "TransactionConflict"
+                                    | "ThrottlingError"
+                                    | "ProvisionedThroughputExceeded"
+                            )
+                        )
+                    })
+            }
+            Error::Import { source, .. } => source.is_retryable(),
+            #[cfg(feature = "streams")]
+            Error::Streams(e) => {
+                matches!(**e, aws_sdk_dynamodbstreams::Error::InternalServerError(_))
+            }
+            _ => false,
+        }
+    }
+
+    /// itemやテーブルが見つからなかったかどうか
+    /// ```
+    /// # use dynamodb_utils::*;
+    /// # tokio_test::block_on(async {
+    /// let client = Client::mock();
+    /// let error = client.describe_table("missing").await.unwrap_err();
+    /// assert!(matches!(error, Error::ResourceNotFound(_)));
+    /// assert!(error.is_not_found());
+    /// assert!(!error.is_retryable());
+    /// # Ok::<(), Error>(())
+    /// # });
+    /// ```
+    pub fn is_not_found(&self) -> bool {
+        matches!(self, Error::NotFound | Error::ResourceNotFound(_))
+    }
+
+    /// スロットリングされたかどうか
+    pub(crate) fn is_throttling(&self) -> bool {
+        matches!(self, Error::Throttled(_))
+    }
+
+    /// DynamoDB Streamsのエラーを変換します
+    ///
+    /// スロットリングは[`Error::Throttled`]にそろえます。
+    #[cfg(feature = "streams")]
+    pub(crate) fn from_streams_error(e: impl Into<aws_sdk_dynamodbstreams::Error>) -> Self {
+        match e.into() {
+            aws_sdk_dynamodbstreams::Error::LimitExceededException(e) => {
+                Error::Throttled(Box::new(aws_sdk_dynamodb::Error::LimitExceededException(
+                    aws_sdk_dynamodb::types::error::LimitExceededException::builder()
+                        .meta(e.meta().clone())
+                        .set_message(e.message)
+                        .build(),
+                )))
+            }
+            e if e.code() == Some("ThrottlingException") => Error::Throttled(Box::new(
+                aws_sdk_dynamodb::operation::describe_table::DescribeTableError::generic(
+                    e.meta().clone(),
+                )
+                .into(),
+            )),
+            e => Error::Streams(Box::new(e)),
+        }
+    }
+
+    /// 作ろうとしたテーブルが既にあるかどうか
+    pub(crate) fn is_resource_in_use(&self) -> bool {
+        matches!(
+            self,
+            Error::DynamoDb(e) if matches!(**e, aws_sdk_dynamodb::Error::ResourceInUseException(_))
+        )
+    }
+}
+
+/// 送ったリクエストのエラーを変換します
+///
+/// タイムアウトと送信の失敗は、[`aws_sdk_dynamodb::Error`]にすると区別できなくなるので先に分けます。
+pub(crate) fn from_sdk_error<E, R>(e: SdkError<E, R>) -> Error
+where
+    SdkError<E, R>: Into<aws_sdk_dynamodb::Error>,
+{
+    match &e {
+        SdkError::TimeoutError(_) => Error::Timeout(Box::new(e.into())),
+        SdkError::DispatchFailure(failure) if failure.is_timeout() => {
+            Error::Timeout(Box::new(e.into()))
+        }
+        // 設定の誤りなど、送り直しても変わらないものは除きます
+        SdkError::DispatchFailure(failure) if !failure.is_user() => {
+            Error::Dispatch(Box::new(e.into()))
+        }
+        _ => from_aws_sdk_dynamodb_error(e),
+    }
 }
 
 pub(crate) fn from_aws_sdk_dynamodb_error(e: impl Into<aws_sdk_dynamodb::Error>) -> Error {
-    Error::DynamoDb(Box::new(e.into()))
+    match e.into() {
+        aws_sdk_dynamodb::Error::ConditionalCheckFailedException(e) => {
+            Error::ConditionalCheckFailed { item: e.item }
+        }
+        aws_sdk_dynamodb::Error::TransactionCanceledException(e) => Error::TransactionCanceled(
+            e.cancellation_reasons
+                .unwrap_or_default()
+                .into_iter()
+                .map(Into::into)
+                .collect(),
+        ),
+        e @ (aws_sdk_dynamodb::Error::ProvisionedThroughputExceededException(_)
+        | aws_sdk_dynamodb::Error::RequestLimitExceeded(_)) => Error::Throttled(Box::new(e)),
+        e @ aws_sdk_dynamodb::Error::ResourceNotFoundException(_) => {
+            Error::ResourceNotFound(Box::new(e))
+        }
+        e => match e.code() {
+            Some("ThrottlingException") => Error::Throttled(Box::new(e)),
+            // itemのサイズ超過もValidationExceptionで返ってきます
+            Some("ValidationException")
+                if e.message().is_some_and(|message| {
+                    message.contains("exceeded the maximum allowed size")
+                }) =>
+            {
+                Error::ItemTooLarge(Box::new(e))
+            }
+            Some("ValidationException") => Error::Validation(Box::new(e)),
+            _ => Error::DynamoDb(Box::new(e)),
+        },
+    }
 }
 
 impl From<aws_sdk_dynamodb::Error> for Error {
@@ -346,6 +985,141 @@ impl From<aws_sdk_dynamodb::Error> for Error {
 
 impl From<crate::serde_dynamo::Error> for Error {
     fn from(value: crate::serde_dynamo::Error) -> Self {
-        Self::Serde(Box::new(value.into()))
+        Self::Serde(Box::new(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aws_sdk_dynamodb::config::{
+        retry::RetryConfig, timeout::TimeoutConfig, BehaviorVersion, Credentials, Region,
+    };
+    use std::time::Duration;
+
+    fn client(endpoint: String) -> Client {
+        Client::from_conf(
+            aws_sdk_dynamodb::Config::builder()
+                .behavior_version(BehaviorVersion::latest())
+                .region(Region::from_static("us-east-1"))
+                .credentials_provider(Credentials::new("test", "test", None, None, "test"))
+                .endpoint_url(endpoint)
+                .retry_config(RetryConfig::disabled())
+                .timeout_config(
+                    TimeoutConfig::builder()
+                        .operation_timeout(Duration::from_millis(200))
+                        .build(),
+                )
+                .build(),
+        )
+    }
+
+    #[test]
+    fn max_version_is_rejected() {
+        tokio_test::block_on(async {
+            let client = Client::mock();
+            client
+                .create_table("t", "id", None::<String>, TableType::OnDemand)
+                .await
+                .unwrap();
+            let item = serde_json::json!({ "id": "a", "version": i64::MAX });
+            let error = client
+                .put_item_versioned("t", item, "version")
+                .await
+                .unwrap_err();
+            assert!(matches!(error, Error::InvalidVersion), "{error:?}");
+            let error = client
+                .update_item_versioned(
+                    "t",
+                    Key::new("id", "a"),
+                    Update::new().set("name", "x"),
+                    "version",
+                    i64::MAX,
+                )
+                .await
+                .unwrap_err();
+            assert!(matches!(error, Error::InvalidVersion), "{error:?}");
+        });
+    }
+
+    #[test]
+    fn empty_update_is_rejected() {
+        tokio_test::block_on(async {
+            let error = Client::mock()
+                .update_item("t", Key::new("id", "a"), Update::new())
+                .await
+                .unwrap_err();
+            assert!(matches!(error, Error::EmptyUpdate), "{error:?}");
+        });
+    }
+
+    #[test]
+    fn dispatch_failure_is_retryable() {
+        tokio_test::block_on(async {
+            // 閉じたportに送ります
+            let port = std::net::TcpListener::bind("127.0.0.1:0")
+                .unwrap()
+                .local_addr()
+                .unwrap()
+                .port();
+            let error = client(format!("http://127.0.0.1:{port}"))
+                .get_item_raw("t", Key::new("id", "a"))
+                .await
+                .unwrap_err();
+            assert!(matches!(error, Error::Dispatch(_)), "{error:?}");
+            assert!(error.is_retryable());
+        });
+    }
+
+    #[test]
+    fn timeout_is_retryable() {
+        tokio_test::block_on(async {
+            // 接続は受けるが、応答しないserver
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            let endpoint = format!("http://{}", listener.local_addr().unwrap());
+            let error = client(endpoint)
+                .get_item_raw("t", Key::new("id", "a"))
+                .await
+                .unwrap_err();
+            assert!(matches!(error, Error::Timeout(_)), "{error:?}");
+            assert!(error.is_retryable());
+        });
+    }
+
+    #[cfg(feature = "streams")]
+    #[test]
+    fn streams_throttling_is_retryable() {
+        use aws_sdk_dynamodbstreams::{
+            error::ErrorMetadata, operation::get_records::GetRecordsError,
+            types::error::LimitExceededException,
+        };
+
+        let error = Error::from_streams_error(aws_sdk_dynamodbstreams::Error::from(
+            GetRecordsError::LimitExceededException(
+                LimitExceededException::builder()
+                    .message("too many readers")
+                    .build(),
+            ),
+        ));
+        assert!(matches!(error, Error::Throttled(_)), "{error:?}");
+        assert!(error.is_retryable());
+        assert!(error.to_string().contains("too many readers"), "{error}");
+
+        let error = Error::from_streams_error(GetRecordsError::generic(
+            ErrorMetadata::builder()
+                .code("ThrottlingException")
+                .message("rate exceeded")
+                .build(),
+        ));
+        assert!(matches!(error, Error::Throttled(_)), "{error:?}");
+        assert!(error.is_retryable());
+
+        let error = Error::from_streams_error(GetRecordsError::generic(
+            ErrorMetadata::builder()
+                .code("AccessDeniedException")
+                .build(),
+        ));
+        assert!(matches!(error, Error::Streams(_)), "{error:?}");
+        assert!(!error.is_retryable());
     }
 }
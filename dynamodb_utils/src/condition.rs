@@ -0,0 +1,193 @@
+use crate::{
+    expression::{ExpressionAttributes, Path},
+    sdk::types::AttributeValue,
+    IntoValue,
+};
+use std::ops::Not;
+
+/// ConditionExpressionのbuilder
+///
+/// 条件を満たさない場合、書き込みは行われず[`Error::ConditionalCheckFailed`](`crate::Error::ConditionalCheckFailed`)になります。
+/// ```
+/// # use dynamodb_utils::Condition;
+/// let condition = Condition::attribute_not_exists("id")
+///     .or(Condition::eq("status", "draft").and(Condition::lt("version", 3)));
+/// let negated = !Condition::begins_with("name", "tmp_");
+/// ```
+#[derive(Debug, Clone)]
+pub struct Condition(pub(crate) ConditionExpr);
+
+#[derive(Debug, Clone)]
+pub(crate) enum ConditionExpr {
+    AttributeExists(Path),
+    AttributeNotExists(Path),
+    Compare(Path, Comparator, AttributeValue),
+    Between(Path, AttributeValue, AttributeValue),
+    BeginsWith(Path, AttributeValue),
+    Contains(Path, AttributeValue),
+    And(Box<ConditionExpr>, Box<ConditionExpr>),
+    Or(Box<ConditionExpr>, Box<ConditionExpr>),
+    Not(Box<ConditionExpr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Comparator {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Comparator {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Comparator::Eq => "=",
+            Comparator::Ne => "<>",
+            Comparator::Lt => "<",
+            Comparator::Le => "<=",
+            Comparator::Gt => ">",
+            Comparator::Ge => ">=",
+        }
+    }
+}
+
+impl Condition {
+    /// `attribute_exists(path)`
+    pub fn attribute_exists(path: impl Into<Path>) -> Self {
+        Self(ConditionExpr::AttributeExists(path.into()))
+    }
+
+    /// `attribute_not_exists(path)`
+    pub fn attribute_not_exists(path: impl Into<Path>) -> Self {
+        Self(ConditionExpr::AttributeNotExists(path.into()))
+    }
+
+    /// `path = value`
+    pub fn eq(path: impl Into<Path>, value: impl IntoValue) -> Self {
+        Self::compare(path, Comparator::Eq, value)
+    }
+
+    /// `path <> value`
+    pub fn ne(path: impl Into<Path>, value: impl IntoValue) -> Self {
+        Self::compare(path, Comparator::Ne, value)
+    }
+
+    /// `path < value`
+    pub fn lt(path: impl Into<Path>, value: impl IntoValue) -> Self {
+        Self::compare(path, Comparator::Lt, value)
+    }
+
+    /// `path <= value`
+    pub fn le(path: impl Into<Path>, value: impl IntoValue) -> Self {
+        Self::compare(path, Comparator::Le, value)
+    }
+
+    /// `path > value`
+    pub fn gt(path: impl Into<Path>, value: impl IntoValue) -> Self {
+        Self::compare(path, Comparator::Gt, value)
+    }
+
+    /// `path >= value`
+    pub fn ge(path: impl Into<Path>, value: impl IntoValue) -> Self {
+        Self::compare(path, Comparator::Ge, value)
+    }
+
+    /// `path BETWEEN low AND high`
+    pub fn between(path: impl Into<Path>, low: impl IntoValue, high: impl IntoValue) -> Self {
+        Self(ConditionExpr::Between(
+            path.into(),
+            low.into_value(),
+            high.into_value(),
+        ))
+    }
+
+    /// `begins_with(path, prefix)`
+    pub fn begins_with(path: impl Into<Path>, prefix: impl IntoValue) -> Self {
+        Self(ConditionExpr::BeginsWith(path.into(), prefix.into_value()))
+    }
+
+    /// `contains(path, value)`
+    pub fn contains(path: impl Into<Path>, value: impl IntoValue) -> Self {
+        Self(ConditionExpr::Contains(path.into(), value.into_value()))
+    }
+
+    /// `self AND other`
+    pub fn and(self, other: Condition) -> Self {
+        Self(ConditionExpr::And(Box::new(self.0), Box::new(other.0)))
+    }
+
+    /// `self OR other`
+    pub fn or(self, other: Condition) -> Self {
+        Self(ConditionExpr::Or(Box::new(self.0), Box::new(other.0)))
+    }
+
+    fn compare(path: impl Into<Path>, comparator: Comparator, value: impl IntoValue) -> Self {
+        Self(ConditionExpr::Compare(
+            path.into(),
+            comparator,
+            value.into_value(),
+        ))
+    }
+
+    /// ConditionExpressionを組み立てます
+    pub(crate) fn expression(&self, attributes: &mut ExpressionAttributes) -> String {
+        self.0.expression(attributes)
+    }
+}
+
+impl Not for Condition {
+    type Output = Condition;
+
+    /// `NOT self`
+    fn not(self) -> Self::Output {
+        Self(ConditionExpr::Not(Box::new(self.0)))
+    }
+}
+
+impl ConditionExpr {
+    fn expression(&self, attributes: &mut ExpressionAttributes) -> String {
+        match self {
+            ConditionExpr::AttributeExists(path) => {
+                format!("attribute_exists({})", attributes.path(path))
+            }
+            ConditionExpr::AttributeNotExists(path) => {
+                format!("attribute_not_exists({})", attributes.path(path))
+            }
+            ConditionExpr::Compare(path, comparator, value) => format!(
+                "{} {} {}",
+                attributes.path(path),
+                comparator.as_str(),
+                attributes.value(value.clone())
+            ),
+            ConditionExpr::Between(path, low, high) => format!(
+                "{} BETWEEN {} AND {}",
+                attributes.path(path),
+                attributes.value(low.clone()),
+                attributes.value(high.clone())
+            ),
+            ConditionExpr::BeginsWith(path, prefix) => format!(
+                "begins_with({}, {})",
+                attributes.path(path),
+                attributes.value(prefix.clone())
+            ),
+            ConditionExpr::Contains(path, value) => format!(
+                "contains({}, {})",
+                attributes.path(path),
+                attributes.value(value.clone())
+            ),
+            ConditionExpr::And(left, right) => format!(
+                "({}) AND ({})",
+                left.expression(attributes),
+                right.expression(attributes)
+            ),
+            ConditionExpr::Or(left, right) => format!(
+                "({}) OR ({})",
+                left.expression(attributes),
+                right.expression(attributes)
+            ),
+            ConditionExpr::Not(inner) => format!("NOT ({})", inner.expression(attributes)),
+        }
+    }
+}
@@ -0,0 +1,249 @@
+use crate::{
+    from_values::type_of,
+    into_values::Number,
+    sdk::{operation::update_item::UpdateItemOutput, types::ReturnValue},
+    Autoscale, BatchGet, Client, Error, FromValue, Key, Path, Update,
+};
+use futures_util::TryStreamExt;
+use std::{ops::Range, sync::Arc};
+use tokio::sync::Mutex;
+
+/// 返ってきた項目から`path`の値を取り出します
+fn counter_value<N: FromValue>(output: UpdateItemOutput, path: &Path) -> Result<Option<N>, Error> {
+    let Some(attributes) = output.attributes else {
+        return Ok(None);
+    };
+    let Some(value) = path.resolve(&attributes) else {
+        return Ok(None);
+    };
+    N::from_value(value)
+        .map(Some)
+        .ok_or_else(|| Error::InvalidType {
+            attribute: path.to_string(),
+            expected: N::TYPE_NAME,
+            found: type_of(value),
+        })
+}
+
+impl<A: Autoscale> Client<A> {
+    /// `path`の数値に`by`をatomicに加算し、加算後の値を返します
+    ///
+    /// itemや項目が無い場合は0から加算します。
+    /// ```
+    /// # use dynamodb_utils::*;
+    /// # tokio_test::block_on(async {
+    /// let client = Client::mock();
+    /// client
+    ///     .create_table("counters", "id", None::<String>, TableType::OnDemand)
+    ///     .await?;
+    /// assert_eq!(client.increment("counters", ("id", "views"), "count", 1).await?, 1);
+    /// assert_eq!(client.increment("counters", ("id", "views"), "count", 2).await?, 3);
+    /// assert_eq!(
+    ///     client
+    ///         .increment_returning_old("counters", ("id", "views"), "count", 1)
+    ///         .await?,
+    ///     Some(3)
+    /// );
+    /// # Ok::<(), Error>(())
+    /// # });
+    /// ```
+    pub async fn increment<N: Number + FromValue>(
+        &self,
+        table_name: impl Into<String>,
+        key: impl Into<Key>,
+        path: impl Into<Path>,
+        by: N,
+    ) -> Result<N, Error> {
+        let path = path.into();
+        let update = Update::new()
+            .add(path.clone(), by)
+            .return_values(ReturnValue::UpdatedNew);
+        let output = self.update_item(table_name, key, update).await?;
+        counter_value(output, &path)?.ok_or_else(|| Error::MissingAttribute(path.to_string()))
+    }
+
+    /// `path`の数値に`by`をatomicに加算し、加算前の値を返します
+    ///
+    /// 加算前に項目が無かった場合は`None`になります。
+    pub async fn increment_returning_old<N: Number + FromValue>(
+        &self,
+        table_name: impl Into<String>,
+        key: impl Into<Key>,
+        path: impl Into<Path>,
+        by: N,
+    ) -> Result<Option<N>, Error> {
+        let path = path.into();
+        let update = Update::new()
+            .add(path.clone(), by)
+            .return_values(ReturnValue::UpdatedOld);
+        let output = self.update_item(table_name, key, update).await?;
+        counter_value(output, &path)
+    }
+
+    /// `count`個の連番を予約します
+    ///
+    /// 連番は1から始まり、予約した範囲は他の呼び出しと重なりません。
+    pub async fn allocate_ids(
+        &self,
+        table_name: impl Into<String>,
+        key: impl Into<Key>,
+        path: impl Into<Path>,
+        count: u64,
+    ) -> Result<Range<u64>, Error> {
+        let end = self.increment(table_name, key, path, count).await? + 1;
+        Ok(end - count..end)
+    }
+
+    /// まとめて予約しながら連番を払い出す[`Sequence`]を作ります
+    pub fn sequence(
+        &self,
+        table_name: impl Into<String>,
+        key: impl Into<Key>,
+        path: impl Into<Path>,
+        block_size: u64,
+    ) -> Sequence<A> {
+        Sequence {
+            client: self.clone(),
+            table_name: table_name.into(),
+            key: key.into(),
+            path: path.into(),
+            block_size: block_size.max(1),
+            reserved: Arc::default(),
+        }
+    }
+
+    /// 書き込みを`shards`個のitemに分散する[`ShardedCounter`]を作ります
+    ///
+    /// テーブルはpartition keyのみで、`partition_key`はS型にしてください。
+    pub fn sharded_counter(
+        &self,
+        table_name: impl Into<String>,
+        partition_key: impl Into<String>,
+        counter_id: impl Into<String>,
+        shards: u32,
+    ) -> ShardedCounter<A> {
+        ShardedCounter {
+            client: self.clone(),
+            table_name: table_name.into(),
+            partition_key: partition_key.into(),
+            counter_id: counter_id.into(),
+            shards: shards.max(1),
+        }
+    }
+}
+
+/// 連番の払い出し
+///
+/// `block_size`個ずつDynamoDBで予約し、予約した範囲を使い切るまではリクエストを送りません。
+/// cloneしたものとは予約した範囲を共有します。
+/// 使われなかった範囲は欠番になります。
+/// ```
+/// # use dynamodb_utils::*;
+/// # tokio_test::block_on(async {
+/// let client = Client::mock();
+/// client
+///     .create_table("sequences", "id", None::<String>, TableType::OnDemand)
+///     .await?;
+/// let sequence = client.sequence("sequences", ("id", "orders"), "next", 100);
+/// assert_eq!(sequence.next().await?, 1);
+/// assert_eq!(sequence.next().await?, 2);
+/// # Ok::<(), Error>(())
+/// # });
+/// ```
+#[derive(Debug, Clone)]
+pub struct Sequence<A = ()> {
+    client: Client<A>,
+    table_name: String,
+    key: Key,
+    path: Path,
+    block_size: u64,
+    reserved: Arc<Mutex<Range<u64>>>,
+}
+
+impl<A: Autoscale> Sequence<A> {
+    /// 次の番号
+    pub async fn next(&self) -> Result<u64, Error> {
+        let mut reserved = self.reserved.lock().await;
+        if reserved.is_empty() {
+            *reserved = self
+                .client
+                .allocate_ids(
+                    &self.table_name,
+                    self.key.clone(),
+                    self.path.clone(),
+                    self.block_size,
+                )
+                .await?;
+        }
+        let id = reserved.start;
+        reserved.start += 1;
+        Ok(id)
+    }
+}
+
+/// 書き込みの多いカウンタ
+///
+/// `{counter_id}#{n}`をkeyとするitemのどれかに加算し、読み込み時に合計します。
+/// ```
+/// # use dynamodb_utils::*;
+/// # tokio_test::block_on(async {
+/// let client = Client::mock();
+/// client
+///     .create_table("counters", "id", None::<String>, TableType::OnDemand)
+///     .await?;
+/// let likes = client.sharded_counter("counters", "id", "likes", 8);
+/// for _ in 0..10 {
+///     likes.add(1).await?;
+/// }
+/// assert_eq!(likes.get().await?, 10);
+/// # Ok::<(), Error>(())
+/// # });
+/// ```
+#[derive(Debug, Clone)]
+pub struct ShardedCounter<A = ()> {
+    client: Client<A>,
+    table_name: String,
+    partition_key: String,
+    counter_id: String,
+    shards: u32,
+}
+
+/// 値を保存する項目名
+const SHARDED_COUNTER_ATTRIBUTE: &str = "count";
+
+impl<A: Autoscale> ShardedCounter<A> {
+    fn shard_key(&self, shard: u32) -> Key {
+        Key::new(
+            self.partition_key.as_str(),
+            format!("{}#{shard}", self.counter_id),
+        )
+    }
+
+    /// ランダムに選んだshardに加算します
+    pub async fn add(&self, by: i64) -> Result<(), Error> {
+        let shard = uuid::Uuid::new_v4().as_u128() % self.shards as u128;
+        self.client
+            .update_item(
+                &self.table_name,
+                self.shard_key(shard as u32),
+                Update::new().add(Path::attribute(SHARDED_COUNTER_ATTRIBUTE), by),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// すべてのshardの合計
+    pub async fn get(&self) -> Result<i64, Error> {
+        let keys = (0..self.shards).map(|shard| self.shard_key(shard));
+        self.client
+            .batch_get_item_raw(BatchGet::new().table(&self.table_name, keys))
+            .try_fold(0, |total, (_, item)| async move {
+                Ok(total
+                    + item
+                        .get(SHARDED_COUNTER_ATTRIBUTE)
+                        .and_then(i64::from_value)
+                        .unwrap_or_default())
+            })
+            .await
+    }
+}
@@ -0,0 +1,122 @@
+use crate::{
+    sdk::{
+        operation::{
+            create_table::CreateTableOutput, delete_item::DeleteItemOutput, put_item::PutItemOutput,
+        },
+        types::ScalarAttributeType,
+    },
+    Autoscale, Client, Error, Key, KeySchema, KeyValue, Table, TableDefinition, TableType,
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+/// テーブル名とkeyが決まっているitemの型
+///
+/// 通常は`#[derive(DynamoEntity)]`で実装します。
+/// 項目名は`#[serde(rename = "...")]`と`#[serde(rename_all = "...")]`に従います。
+/// ```no_run
+/// # use dynamodb_utils::*;
+/// #[derive(serde::Serialize, serde::Deserialize, DynamoEntity)]
+/// #[dynamo(table = "users")]
+/// struct User {
+///     #[dynamo(hash_key)]
+///     id: String,
+///     name: String,
+/// }
+///
+/// # tokio_test::block_on(async {
+/// let client = Client::from_env().await;
+/// client.create_entity_table::<User>(TableType::OnDemand).await?;
+/// client.put(User { id: "u1".into(), name: "alice".into() }).await?;
+/// let user = client.get::<User>("u1").await?;
+/// client.delete::<User>(user.id.as_str()).await?;
+/// # Ok::<(), Error>(())
+/// # });
+/// ```
+pub trait DynamoEntity {
+    /// prefixを含まないテーブル名
+    const TABLE_NAME: &'static str;
+
+    /// keyの項目名
+    fn key_schema() -> KeySchema;
+
+    /// partition key、sort keyの型
+    fn key_attribute_types() -> (ScalarAttributeType, Option<ScalarAttributeType>);
+
+    /// このitemのkey
+    fn key(&self) -> Key;
+}
+
+impl<E: DynamoEntity> DynamoEntity for &E {
+    const TABLE_NAME: &'static str = E::TABLE_NAME;
+
+    fn key_schema() -> KeySchema {
+        E::key_schema()
+    }
+
+    fn key_attribute_types() -> (ScalarAttributeType, Option<ScalarAttributeType>) {
+        E::key_attribute_types()
+    }
+
+    fn key(&self) -> Key {
+        (*self).key()
+    }
+}
+
+impl<A: Autoscale> Client<A> {
+    /// `E`のテーブルの[`Table`]を作ります
+    pub fn entity_table<E: DynamoEntity>(&self) -> Table<E, A> {
+        self.table(E::TABLE_NAME, E::key_schema())
+    }
+
+    /// `E`のテーブルからitemを取得します
+    pub async fn get<E>(&self, key: impl Into<KeyValue>) -> Result<E, Error>
+    where
+        E: DynamoEntity + DeserializeOwned,
+    {
+        self.get_item(
+            self.prefixed_table_name(E::TABLE_NAME),
+            E::key_schema().key(key)?,
+        )
+        .await
+    }
+
+    /// `E`のテーブルにitemを登録します
+    pub async fn put<E>(&self, entity: E) -> Result<PutItemOutput, Error>
+    where
+        E: DynamoEntity + Serialize,
+    {
+        self.put_item(self.prefixed_table_name(E::TABLE_NAME), entity)
+            .await
+    }
+
+    /// `E`のテーブルからitemを削除します
+    pub async fn delete<E: DynamoEntity>(
+        &self,
+        key: impl Into<KeyValue>,
+    ) -> Result<DeleteItemOutput, Error> {
+        self.delete_item(
+            self.prefixed_table_name(E::TABLE_NAME),
+            E::key_schema().key(key)?,
+        )
+        .await
+    }
+
+    /// `entity`と同じkeyのitemを削除します
+    pub async fn delete_entity<E: DynamoEntity>(
+        &self,
+        entity: &E,
+    ) -> Result<DeleteItemOutput, Error> {
+        self.delete_item(self.prefixed_table_name(E::TABLE_NAME), entity.key())
+            .await
+    }
+
+    /// `E`のkeyの定義でテーブルを作ります
+    pub async fn create_entity_table<E: DynamoEntity>(
+        &self,
+        table_type: TableType,
+    ) -> Result<CreateTableOutput, Error> {
+        let mut definition = TableDefinition::from_entity::<E>().table_type(table_type);
+        definition.table_name = self.prefixed_table_name(E::TABLE_NAME);
+        self.create_table_with(definition).await
+    }
+}
@@ -0,0 +1,182 @@
+use crate::sdk::types::AttributeValue;
+use std::{collections::HashMap, fmt::Display};
+
+/// itemの項目を指す document path
+///
+/// `"a.b[0].c"`のような文字列から変換でき、`.`で区切られた項目名と`[n]`のindexとして解釈されます。
+/// 項目名に`.`を含む場合は[`Path::attribute`]を使ってください。
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Path(pub(crate) Vec<PathElement>);
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum PathElement {
+    Name(String),
+    Index(usize),
+}
+
+impl Path {
+    /// トップレベルの項目を、項目名をそのまま使って指定します
+    pub fn attribute(name: impl Into<String>) -> Self {
+        Self(vec![PathElement::Name(name.into())])
+    }
+
+    /// 子要素の項目を追加します
+    pub fn field(mut self, name: impl Into<String>) -> Self {
+        self.0.push(PathElement::Name(name.into()));
+        self
+    }
+
+    /// listの要素を追加します
+    pub fn index(mut self, index: usize) -> Self {
+        self.0.push(PathElement::Index(index));
+        self
+    }
+
+    /// itemの中でこのpathが指す値
+    pub(crate) fn resolve<'a>(
+        &self,
+        item: &'a HashMap<String, AttributeValue>,
+    ) -> Option<&'a AttributeValue> {
+        let (PathElement::Name(first), rest) = self.0.split_first()? else {
+            return None;
+        };
+        rest.iter().try_fold(item.get(first)?, |current, element| {
+            match (element, current) {
+                (PathElement::Name(name), AttributeValue::M(map)) => map.get(name),
+                (PathElement::Index(i), AttributeValue::L(list)) => list.get(*i),
+                _ => None,
+            }
+        })
+    }
+}
+
+impl From<&str> for Path {
+    fn from(value: &str) -> Self {
+        let mut elements = vec![];
+        for part in value.split('.') {
+            let (name, mut rest) = part.split_once('[').unwrap_or((part, ""));
+            elements.push(PathElement::Name(name.to_owned()));
+            while let Some((index, next)) = rest.split_once(']') {
+                match index.parse() {
+                    Ok(index) => elements.push(PathElement::Index(index)),
+                    // indexとして読めないものは項目名の一部として扱う
+                    Err(_) => return Self::attribute(value),
+                }
+                rest = match next.strip_prefix('[') {
+                    Some(next) => next,
+                    None if next.is_empty() => next,
+                    // `]`の後に続く項目名は読めないので、全体を項目名として扱う
+                    None => return Self::attribute(value),
+                };
+            }
+            if !rest.is_empty() {
+                return Self::attribute(value);
+            }
+        }
+        Self(elements)
+    }
+}
+
+impl From<String> for Path {
+    fn from(value: String) -> Self {
+        value.as_str().into()
+    }
+}
+
+impl From<&String> for Path {
+    fn from(value: &String) -> Self {
+        value.as_str().into()
+    }
+}
+
+impl Display for Path {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, element) in self.0.iter().enumerate() {
+            match element {
+                PathElement::Name(name) if i == 0 => write!(f, "{name}")?,
+                PathElement::Name(name) => write!(f, ".{name}")?,
+                PathElement::Index(index) => write!(f, "[{index}]")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// expressionで使う`#name`、`:value`のplaceholderを払い出します
+///
+/// 予約語や`.`を含む項目名でも問題なく使えるように、項目名は必ずplaceholderに置き換えます。
+#[derive(Debug, Default)]
+pub(crate) struct ExpressionAttributes {
+    names: HashMap<String, String>,
+    values: HashMap<String, AttributeValue>,
+}
+
+impl ExpressionAttributes {
+    /// 項目名のplaceholderを取得します。同じ項目名には同じplaceholderを返します。
+    pub(crate) fn name(&mut self, name: &str) -> String {
+        if let Some((placeholder, _)) = self.names.iter().find(|(_, n)| *n == name) {
+            return placeholder.clone();
+        }
+        let placeholder = format!("#n{}", self.names.len());
+        self.names.insert(placeholder.clone(), name.to_owned());
+        placeholder
+    }
+
+    /// document pathの各項目名をplaceholderに置き換えたものを取得します
+    pub(crate) fn path(&mut self, path: &Path) -> String {
+        let mut expression = String::new();
+        for element in &path.0 {
+            match element {
+                PathElement::Name(name) => {
+                    if !expression.is_empty() {
+                        expression.push('.');
+                    }
+                    expression.push_str(&self.name(name));
+                }
+                PathElement::Index(index) => expression.push_str(&format!("[{index}]")),
+            }
+        }
+        expression
+    }
+
+    /// 値のplaceholderを取得します
+    pub(crate) fn value(&mut self, value: AttributeValue) -> String {
+        let placeholder = format!(":v{}", self.values.len());
+        self.values.insert(placeholder.clone(), value);
+        placeholder
+    }
+
+    /// `ExpressionAttributeNames`と`ExpressionAttributeValues`に分解します
+    #[allow(clippy::type_complexity)]
+    pub(crate) fn into_parts(
+        self,
+    ) -> (
+        Option<HashMap<String, String>>,
+        Option<HashMap<String, AttributeValue>>,
+    ) {
+        (
+            Some(self.names).filter(|names| !names.is_empty()),
+            Some(self.values).filter(|values| !values.is_empty()),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_nested_path() {
+        assert_eq!(
+            Path::from("a.b[0][1].c"),
+            Path::attribute("a").field("b").index(0).index(1).field("c")
+        );
+    }
+
+    #[test]
+    fn unparsable_path_is_single_attribute() {
+        for value in ["a[0]b", "a[0]1]", "a[0", "a[x]"] {
+            assert_eq!(Path::from(value), Path::attribute(value), "{value}");
+        }
+    }
+}
@@ -0,0 +1,251 @@
+use crate::{Error, ListElement};
+use aws_sdk_dynamodb::types::AttributeValue;
+use serde::de::DeserializeOwned;
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    hash::{BuildHasher, Hash},
+};
+
+/// [`AttributeValue`]から変換できる型
+///
+/// [`IntoValue`](`crate::IntoValue`)の逆の変換です。
+/// 型が合わない場合は`None`を返します。
+pub trait FromValue: Sized {
+    /// 変換元のDynamoDBの型名。[`Error::InvalidType`]の`expected`になります
+    ///
+    /// 複数の型から変換できる場合は`|`で区切ります。
+    const TYPE_NAME: &'static str;
+
+    fn from_value(value: &AttributeValue) -> Option<Self>;
+}
+
+impl FromValue for AttributeValue {
+    const TYPE_NAME: &'static str = "any";
+
+    fn from_value(value: &AttributeValue) -> Option<Self> {
+        Some(value.clone())
+    }
+}
+
+impl FromValue for String {
+    const TYPE_NAME: &'static str = "S";
+
+    fn from_value(value: &AttributeValue) -> Option<Self> {
+        value.as_s().ok().cloned()
+    }
+}
+
+macro_rules! num_from_value {
+($($t: ty),*) => {
+    $(
+        impl FromValue for $t {
+            const TYPE_NAME: &'static str = "N";
+
+            fn from_value(value: &AttributeValue) -> Option<Self> {
+                value.as_n().ok()?.parse().ok()
+            }
+        }
+    )*
+};
+}
+
+num_from_value!(i8, i16, i32, i64, i128, u8, u16, u32, u64, u128, f32, f64);
+
+impl FromValue for Vec<u8> {
+    const TYPE_NAME: &'static str = "B";
+
+    fn from_value(value: &AttributeValue) -> Option<Self> {
+        value.as_b().ok().map(|blob| blob.as_ref().to_vec())
+    }
+}
+
+impl FromValue for bool {
+    const TYPE_NAME: &'static str = "BOOL";
+
+    fn from_value(value: &AttributeValue) -> Option<Self> {
+        value.as_bool().ok().copied()
+    }
+}
+
+/// NULLは`None`になります
+impl<T: FromValue> FromValue for Option<T> {
+    const TYPE_NAME: &'static str = T::TYPE_NAME;
+
+    fn from_value(value: &AttributeValue) -> Option<Self> {
+        match value {
+            AttributeValue::Null(_) => Some(None),
+            value => T::from_value(value).map(Some),
+        }
+    }
+}
+
+/// L型から変換します
+impl<T: FromValue + ListElement> FromValue for Vec<T> {
+    const TYPE_NAME: &'static str = "L";
+
+    fn from_value(value: &AttributeValue) -> Option<Self> {
+        value.as_l().ok()?.iter().map(T::from_value).collect()
+    }
+}
+
+/// setの要素を1つずつ[`AttributeValue`]にします
+fn set_elements(value: &AttributeValue) -> Option<Vec<AttributeValue>> {
+    Some(match value {
+        AttributeValue::Ss(values) => values.iter().cloned().map(AttributeValue::S).collect(),
+        AttributeValue::Ns(values) => values.iter().cloned().map(AttributeValue::N).collect(),
+        AttributeValue::Bs(values) => values.iter().cloned().map(AttributeValue::B).collect(),
+        _ => return None,
+    })
+}
+
+/// SS、NS、BS型から変換します
+impl<T, S> FromValue for HashSet<T, S>
+where
+    T: FromValue + Eq + Hash,
+    S: BuildHasher + Default,
+{
+    const TYPE_NAME: &'static str = "SS|NS|BS";
+    fn from_value(value: &AttributeValue) -> Option<Self> {
+        set_elements(value)?.iter().map(T::from_value).collect()
+    }
+}
+
+/// SS、NS、BS型から変換します
+impl<T: FromValue + Ord> FromValue for BTreeSet<T> {
+    const TYPE_NAME: &'static str = "SS|NS|BS";
+
+    fn from_value(value: &AttributeValue) -> Option<Self> {
+        set_elements(value)?.iter().map(T::from_value).collect()
+    }
+}
+
+impl<T: FromValue, S: BuildHasher + Default> FromValue for HashMap<String, T, S> {
+    const TYPE_NAME: &'static str = "M";
+
+    fn from_value(value: &AttributeValue) -> Option<Self> {
+        value
+            .as_m()
+            .ok()?
+            .iter()
+            .map(|(key, value)| Some((key.clone(), T::from_value(value)?)))
+            .collect()
+    }
+}
+
+impl<T: FromValue> FromValue for BTreeMap<String, T> {
+    const TYPE_NAME: &'static str = "M";
+
+    fn from_value(value: &AttributeValue) -> Option<Self> {
+        value
+            .as_m()
+            .ok()?
+            .iter()
+            .map(|(key, value)| Some((key.clone(), T::from_value(value)?)))
+            .collect()
+    }
+}
+
+/// setはarrayになり、B型は変換できません
+impl FromValue for serde_json::Value {
+    const TYPE_NAME: &'static str = "NULL|BOOL|N|S|L|SS|NS|M";
+
+    fn from_value(value: &AttributeValue) -> Option<Self> {
+        Some(match value {
+            AttributeValue::Null(_) => serde_json::Value::Null,
+            AttributeValue::Bool(value) => serde_json::Value::Bool(*value),
+            AttributeValue::N(value) => serde_json::Value::Number(value.parse().ok()?),
+            AttributeValue::S(value) => serde_json::Value::String(value.clone()),
+            AttributeValue::L(_) | AttributeValue::Ss(_) | AttributeValue::Ns(_) => {
+                serde_json::Value::Array(Vec::from_value(value).or_else(|| {
+                    set_elements(value)?
+                        .iter()
+                        .map(serde_json::Value::from_value)
+                        .collect()
+                })?)
+            }
+            AttributeValue::M(_) => serde_json::Value::Object(
+                BTreeMap::<String, serde_json::Value>::from_value(value)?
+                    .into_iter()
+                    .collect(),
+            ),
+            _ => return None,
+        })
+    }
+}
+
+/// serde_dynamoでデシリアライズして読み込みます
+///
+/// [`Serialized`](`crate::Serialized`)で保存した値を読むときに使います。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct Deserialized<T>(pub T);
+
+impl<T: DeserializeOwned> FromValue for Deserialized<T> {
+    const TYPE_NAME: &'static str = "any";
+
+    fn from_value(value: &AttributeValue) -> Option<Self> {
+        crate::serde_dynamo::from_attribute_value(value.clone())
+            .ok()
+            .map(Deserialized)
+    }
+}
+
+/// 生のitemから項目を型付きで取り出します
+/// ```
+/// # use dynamodb_utils::*;
+/// # use std::collections::HashMap;
+/// let item = HashMap::from([("count".to_string(), 3.into_value())]);
+/// assert_eq!(item.get_as::<i64>("count")?, 3);
+/// assert_eq!(item.get_opt_as::<String>("name")?, None);
+/// assert!(matches!(
+///     item.get_as::<String>("count"),
+///     Err(Error::InvalidType { expected: "S", found: "N", .. })
+/// ));
+/// # Ok::<(), Error>(())
+/// ```
+pub trait ItemExt {
+    /// `name`の項目を`T`に変換します
+    ///
+    /// 項目が無い場合は[`Error::MissingAttribute`]、
+    /// 型が合わない場合は[`Error::InvalidType`]になります。
+    fn get_as<T: FromValue>(&self, name: &str) -> Result<T, Error>;
+
+    /// `name`の項目を`T`に変換します。項目が無いかNULLの場合は`None`になります。
+    fn get_opt_as<T: FromValue>(&self, name: &str) -> Result<Option<T>, Error>;
+}
+
+impl<S: BuildHasher> ItemExt for HashMap<String, AttributeValue, S> {
+    fn get_as<T: FromValue>(&self, name: &str) -> Result<T, Error> {
+        let value = self
+            .get(name)
+            .ok_or_else(|| Error::MissingAttribute(name.to_owned()))?;
+        T::from_value(value).ok_or_else(|| Error::InvalidType {
+            attribute: name.to_owned(),
+            expected: T::TYPE_NAME,
+            found: type_of(value),
+        })
+    }
+
+    fn get_opt_as<T: FromValue>(&self, name: &str) -> Result<Option<T>, Error> {
+        match self.get(name) {
+            None | Some(AttributeValue::Null(_)) => Ok(None),
+            Some(_) => self.get_as(name).map(Some),
+        }
+    }
+}
+
+/// DynamoDBでの型名
+pub(crate) fn type_of(value: &AttributeValue) -> &'static str {
+    match value {
+        AttributeValue::B(_) => "B",
+        AttributeValue::Bool(_) => "BOOL",
+        AttributeValue::Bs(_) => "BS",
+        AttributeValue::L(_) => "L",
+        AttributeValue::M(_) => "M",
+        AttributeValue::N(_) => "N",
+        AttributeValue::Ns(_) => "NS",
+        AttributeValue::Null(_) => "NULL",
+        AttributeValue::S(_) => "S",
+        AttributeValue::Ss(_) => "SS",
+        _ => "unknown",
+    }
+}
@@ -0,0 +1,317 @@
+use crate::{
+    lock::{now_millis, random_id},
+    sdk::types::{AttributeValue, ScalarAttributeType},
+    Autoscale, Client, Condition, Error, Expiry, IntoValue, ItemExt, Key, TableDefinition, Update,
+};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, future::Future, time::Duration};
+
+const IDEMPOTENCY_KEY: &str = "idempotency_key";
+const STATUS: &str = "status";
+const TOKEN: &str = "token";
+const RESULT: &str = "result";
+/// UNIX時間のミリ秒
+const IN_PROGRESS_UNTIL: &str = "in_progress_until";
+/// TTLの項目。UNIX時間の秒
+const EXPIRES_AT: &str = "expires_at";
+
+const IN_PROGRESS: &str = "IN_PROGRESS";
+const COMPLETED: &str = "COMPLETED";
+
+/// 同じリクエストを1度だけ処理するための記録
+///
+/// 呼び出し側が決めたidempotency keyごとに、処理中の印を条件付きで書き込んでから処理し、
+/// 終わったら結果を保存します。同じkeyでの呼び出しには保存した結果を返します。
+/// 処理中のまま`in_progress_timeout`を過ぎた記録は、処理が落ちたものとして取り直します。
+///
+/// 結果は`ttl`の間保持されます。テーブルのTTLは`expires_at`項目で有効にしてください。
+/// ```
+/// # use dynamodb_utils::*;
+/// # use std::sync::atomic::{AtomicUsize, Ordering};
+/// # tokio_test::block_on(async {
+/// let client = Client::mock();
+/// client.create_table_with(IdempotencyStore::table_definition("requests")).await?;
+/// client.enable_ttl("requests", "expires_at").await?;
+/// let store = client.idempotency_store("requests");
+///
+/// let calls = AtomicUsize::new(0);
+/// let charge = || async {
+///     calls.fetch_add(1, Ordering::Relaxed);
+///     Ok::<_, Error>("charged".to_owned())
+/// };
+/// assert_eq!(store.run("payment-1", charge).await?, "charged");
+/// assert_eq!(store.run("payment-1", charge).await?, "charged");
+/// assert_eq!(calls.load(Ordering::Relaxed), 1);
+/// # Ok::<(), Error>(())
+/// # });
+/// ```
+#[derive(Debug, Clone)]
+pub struct IdempotencyStore<A = ()> {
+    client: Client<A>,
+    table_name: String,
+    ttl: Duration,
+    in_progress_timeout: Duration,
+}
+
+impl IdempotencyStore {
+    /// 記録を保存するテーブルの定義。partition keyは`idempotency_key`です。
+    pub fn table_definition(table_name: impl Into<String>) -> TableDefinition {
+        TableDefinition::new(table_name, IDEMPOTENCY_KEY, ScalarAttributeType::S)
+    }
+}
+
+impl<A: Autoscale> Client<A> {
+    /// `table_name`に記録を保存する[`IdempotencyStore`]を作ります。prefixは付きません。
+    pub fn idempotency_store(&self, table_name: impl Into<String>) -> IdempotencyStore<A> {
+        IdempotencyStore {
+            client: self.clone(),
+            table_name: table_name.into(),
+            ttl: Duration::from_secs(24 * 60 * 60),
+            in_progress_timeout: Duration::from_secs(60),
+        }
+    }
+}
+
+impl<A: Autoscale> IdempotencyStore<A> {
+    /// 結果を保持する期間を指定します。デフォルトは24時間です。
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// 処理中の記録を有効とみなす期間を指定します。デフォルトは60秒です。
+    ///
+    /// 処理にかかる時間より長くしてください。
+    pub fn in_progress_timeout(mut self, in_progress_timeout: Duration) -> Self {
+        self.in_progress_timeout = in_progress_timeout;
+        self
+    }
+
+    /// `idempotency_key`で1度だけ`f`を実行し、その結果を返します
+    ///
+    /// 完了済みのkeyでは`f`を実行せず、保存した結果を返します。
+    /// 他で処理中の場合は[`Error::IdempotencyInProgress`]になります。
+    /// `f`が失敗した場合は記録を消すので、同じkeyで再実行できます。
+    pub async fn run<T, E, F, Fut>(&self, idempotency_key: impl Into<String>, f: F) -> Result<T, E>
+    where
+        T: Serialize,
+        for<'de> T: Deserialize<'de>,
+        E: From<Error>,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        let idempotency_key = idempotency_key.into();
+        let token = random_id();
+        if let Some(result) = self.begin(&idempotency_key, &token).await? {
+            return Ok(crate::serde_dynamo::from_attribute_value(result).map_err(Error::from)?);
+        }
+        match f().await {
+            Ok(result) => {
+                self.complete(&idempotency_key, &token, &result).await?;
+                Ok(result)
+            }
+            Err(e) => {
+                // 消せなくても、in_progress_timeoutを過ぎれば再実行できます
+                let _ = self.abandon(&idempotency_key, &token).await;
+                Err(e)
+            }
+        }
+    }
+
+    /// 処理中の印を書き込みます。完了済みの場合は保存した結果を返します。
+    async fn begin(
+        &self,
+        idempotency_key: &str,
+        token: &str,
+    ) -> Result<Option<AttributeValue>, Error> {
+        loop {
+            let now = now_millis();
+            let item = HashMap::from([
+                (IDEMPOTENCY_KEY.to_owned(), idempotency_key.into_value()),
+                (STATUS.to_owned(), IN_PROGRESS.into_value()),
+                (TOKEN.to_owned(), token.into_value()),
+                (
+                    IN_PROGRESS_UNTIL.to_owned(),
+                    (now + self.in_progress_timeout.as_millis() as i64).into_value(),
+                ),
+                (EXPIRES_AT.to_owned(), Expiry::from(self.ttl).into_value()),
+            ]);
+            // DynamoDBはTTLの期限が過ぎたitemをすぐには削除しないので、期限も確認します
+            let condition = Condition::attribute_not_exists(IDEMPOTENCY_KEY)
+                .or(Condition::eq(STATUS, IN_PROGRESS).and(Condition::lt(IN_PROGRESS_UNTIL, now)))
+                .or(Condition::lt(EXPIRES_AT, Expiry::now()));
+            match self
+                .client
+                .put_item_raw_if(&self.table_name, item, condition)
+                .await
+            {
+                Ok(_) => return Ok(None),
+                Err(Error::ConditionalCheckFailed { .. }) => {}
+                Err(e) => return Err(e),
+            }
+            let Some(mut current) = self
+                .client
+                .get_item_raw(&self.table_name, Key::new(IDEMPOTENCY_KEY, idempotency_key))
+                .await?
+                .item
+            else {
+                // 読み込むまでの間に消えたので、書き込みからやり直します
+                continue;
+            };
+            if current.get_as::<String>(STATUS)? != COMPLETED {
+                return Err(Error::IdempotencyInProgress(idempotency_key.to_owned()));
+            }
+            return current
+                .remove(RESULT)
+                .map(Some)
+                .ok_or_else(|| Error::MissingAttribute(RESULT.to_owned()));
+        }
+    }
+
+    /// 結果を保存します
+    async fn complete<T: Serialize>(
+        &self,
+        idempotency_key: &str,
+        token: &str,
+        result: &T,
+    ) -> Result<(), Error> {
+        let result: AttributeValue = crate::serde_dynamo::to_attribute_value(result)?;
+        self.client
+            .update_item(
+                &self.table_name,
+                Key::new(IDEMPOTENCY_KEY, idempotency_key),
+                Update::new()
+                    .set(STATUS, COMPLETED)
+                    .set(RESULT, result)
+                    .set(EXPIRES_AT, Expiry::from(self.ttl))
+                    .remove(IN_PROGRESS_UNTIL)
+                    .condition(Condition::eq(TOKEN, token)),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// 自分が書き込んだ処理中の印を消します
+    async fn abandon(&self, idempotency_key: &str, token: &str) -> Result<(), Error> {
+        self.client
+            .delete_item_if(
+                &self.table_name,
+                Key::new(IDEMPOTENCY_KEY, idempotency_key),
+                Condition::eq(TOKEN, token).and(Condition::eq(STATUS, IN_PROGRESS)),
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    async fn store(client: &Client) -> IdempotencyStore {
+        client
+            .create_table_with(IdempotencyStore::table_definition("requests"))
+            .await
+            .unwrap();
+        client.idempotency_store("requests")
+    }
+
+    #[test]
+    fn concurrent_run_is_rejected_while_in_progress() {
+        tokio_test::block_on(async {
+            let client = Client::mock();
+            let store = store(&client).await;
+            let result = store
+                .run("k", || async {
+                    let error = store
+                        .run("k", || async { Ok::<_, Error>(2) })
+                        .await
+                        .unwrap_err();
+                    assert!(
+                        matches!(error, Error::IdempotencyInProgress(_)),
+                        "{error:?}"
+                    );
+                    Ok::<_, Error>(1)
+                })
+                .await
+                .unwrap();
+            assert_eq!(result, 1);
+        });
+    }
+
+    #[test]
+    fn failed_run_can_be_retried() {
+        tokio_test::block_on(async {
+            let client = Client::mock();
+            let store = store(&client).await;
+            let error = store
+                .run("k", || async { Err::<i32, _>(Error::NotFound) })
+                .await
+                .unwrap_err();
+            assert!(matches!(error, Error::NotFound));
+            let result = store.run("k", || async { Ok::<_, Error>(1) }).await;
+            assert_eq!(result.unwrap(), 1);
+        });
+    }
+
+    #[test]
+    fn stale_in_progress_record_is_taken_over() {
+        tokio_test::block_on(async {
+            let client = Client::mock();
+            let store = store(&client)
+                .await
+                .in_progress_timeout(Duration::from_millis(500));
+            let calls = AtomicUsize::new(0);
+            let slow = store.run("k", || async {
+                calls.fetch_add(1, Ordering::Relaxed);
+                tokio::time::sleep(Duration::from_millis(2000)).await;
+                Ok::<_, Error>("slow".to_owned())
+            });
+            let takeover = async {
+                tokio::time::sleep(Duration::from_millis(1000)).await;
+                store
+                    .run("k", || async {
+                        calls.fetch_add(1, Ordering::Relaxed);
+                        Ok::<_, Error>("takeover".to_owned())
+                    })
+                    .await
+            };
+            let (slow, takeover) = futures_util::join!(slow, takeover);
+            assert_eq!(takeover.unwrap(), "takeover");
+            assert_eq!(calls.load(Ordering::Relaxed), 2);
+            // 取り直された側は結果を保存できません
+            let error = slow.unwrap_err();
+            assert!(
+                matches!(error, Error::ConditionalCheckFailed { .. }),
+                "{error:?}"
+            );
+            let result = store
+                .run("k", || async { Ok::<_, Error>("again".to_owned()) })
+                .await;
+            assert_eq!(result.unwrap(), "takeover");
+        });
+    }
+
+    #[test]
+    fn expired_result_is_run_again() {
+        tokio_test::block_on(async {
+            let client = Client::mock();
+            let store = store(&client).await;
+            let item = HashMap::from([
+                (IDEMPOTENCY_KEY.to_owned(), "k".into_value()),
+                (STATUS.to_owned(), COMPLETED.into_value()),
+                (RESULT.to_owned(), "old".into_value()),
+                (
+                    EXPIRES_AT.to_owned(),
+                    (Expiry::now().epoch_seconds() - 1).into_value(),
+                ),
+            ]);
+            client.put_item_raw("requests", item).await.unwrap();
+            let result = store
+                .run("k", || async { Ok::<_, Error>("new".to_owned()) })
+                .await;
+            assert_eq!(result.unwrap(), "new");
+        });
+    }
+}
@@ -1,5 +1,26 @@
-use aws_sdk_dynamodb::{primitives::Blob, types::AttributeValue};
+use crate::Error;
+use aws_sdk_dynamodb::{
+    primitives::Blob,
+    types::{AttributeValue, ScalarAttributeType},
+};
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 
+/// [`AttributeValue`]に変換できる型
+///
+/// ```
+/// # use dynamodb_utils::{sdk::types::AttributeValue, IntoValue};
+/// # use std::collections::BTreeSet;
+/// assert_eq!(None::<i64>.into_value(), AttributeValue::Null(true));
+/// assert_eq!(
+///     vec![1, 2].into_value(),
+///     AttributeValue::L(vec![AttributeValue::N("1".into()), AttributeValue::N("2".into())])
+/// );
+/// assert_eq!(
+///     BTreeSet::from(["a", "b"]).into_value(),
+///     AttributeValue::Ss(vec!["a".into(), "b".into()])
+/// );
+/// ```
 pub trait IntoValue {
     fn into_value(self) -> AttributeValue;
 }
@@ -39,3 +60,246 @@ impl IntoValue for Vec<u8> {
         AttributeValue::B(Blob::new(self))
     }
 }
+
+impl IntoValue for AttributeValue {
+    fn into_value(self) -> AttributeValue {
+        self
+    }
+}
+
+impl IntoValue for bool {
+    fn into_value(self) -> AttributeValue {
+        AttributeValue::Bool(self)
+    }
+}
+
+/// `None`はNULLになります
+impl<T: IntoValue> IntoValue for Option<T> {
+    fn into_value(self) -> AttributeValue {
+        match self {
+            Some(value) => value.into_value(),
+            None => AttributeValue::Null(true),
+        }
+    }
+}
+
+/// `Vec<T>`でL型にできる要素の型
+///
+/// `Vec<u8>`はB型になるため、`u8`には実装していません。
+pub trait ListElement: IntoValue {}
+
+impl<T: ListElement> IntoValue for Vec<T> {
+    fn into_value(self) -> AttributeValue {
+        AttributeValue::L(self.into_iter().map(IntoValue::into_value).collect())
+    }
+}
+
+macro_rules! list_element {
+($($t: ty),*) => {
+    $(
+        impl ListElement for $t {}
+    )*
+};
+}
+
+list_element!(
+    String,
+    &str,
+    bool,
+    AttributeValue,
+    serde_json::Value,
+    Vec<u8>,
+    i8,
+    i16,
+    i32,
+    i64,
+    i128,
+    u16,
+    u32,
+    u64,
+    u128,
+    f32,
+    f64
+);
+
+impl<T: IntoValue> ListElement for Option<T> {}
+impl<T: ListElement> ListElement for Vec<T> {}
+impl<T: SetElement, S> ListElement for HashSet<T, S> {}
+impl<T: SetElement> ListElement for BTreeSet<T> {}
+impl<T: IntoValue, S> ListElement for HashMap<String, T, S> {}
+impl<T: IntoValue> ListElement for BTreeMap<String, T> {}
+impl ListElement for Serialized {}
+
+/// setにできる要素の型
+///
+/// 文字列はSS型、数値はNS型、`Vec<u8>`はBS型になります。
+/// DynamoDBには空のsetは保存できません。
+pub trait SetElement: Sized {
+    fn into_set(values: impl Iterator<Item = Self>) -> AttributeValue;
+}
+
+impl SetElement for String {
+    fn into_set(values: impl Iterator<Item = Self>) -> AttributeValue {
+        AttributeValue::Ss(values.collect())
+    }
+}
+
+impl SetElement for &str {
+    fn into_set(values: impl Iterator<Item = Self>) -> AttributeValue {
+        AttributeValue::Ss(values.map(Into::into).collect())
+    }
+}
+
+impl<T: Number + ToString> SetElement for T {
+    fn into_set(values: impl Iterator<Item = Self>) -> AttributeValue {
+        AttributeValue::Ns(values.map(|value| value.to_string()).collect())
+    }
+}
+
+impl SetElement for Vec<u8> {
+    fn into_set(values: impl Iterator<Item = Self>) -> AttributeValue {
+        AttributeValue::Bs(values.map(Blob::new).collect())
+    }
+}
+
+impl<T: SetElement, S> IntoValue for HashSet<T, S> {
+    fn into_value(self) -> AttributeValue {
+        T::into_set(self.into_iter())
+    }
+}
+
+impl<T: SetElement> IntoValue for BTreeSet<T> {
+    fn into_value(self) -> AttributeValue {
+        T::into_set(self.into_iter())
+    }
+}
+
+impl<T: IntoValue, S> IntoValue for HashMap<String, T, S> {
+    fn into_value(self) -> AttributeValue {
+        AttributeValue::M(
+            self.into_iter()
+                .map(|(key, value)| (key, value.into_value()))
+                .collect(),
+        )
+    }
+}
+
+impl<T: IntoValue> IntoValue for BTreeMap<String, T> {
+    fn into_value(self) -> AttributeValue {
+        AttributeValue::M(
+            self.into_iter()
+                .map(|(key, value)| (key, value.into_value()))
+                .collect(),
+        )
+    }
+}
+
+impl IntoValue for serde_json::Value {
+    fn into_value(self) -> AttributeValue {
+        match self {
+            serde_json::Value::Null => AttributeValue::Null(true),
+            serde_json::Value::Bool(value) => AttributeValue::Bool(value),
+            serde_json::Value::Number(value) => AttributeValue::N(value.to_string()),
+            serde_json::Value::String(value) => AttributeValue::S(value),
+            serde_json::Value::Array(values) => values.into_value(),
+            serde_json::Value::Object(values) => AttributeValue::M(
+                values
+                    .into_iter()
+                    .map(|(key, value)| (key, value.into_value()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// [`Serialize`]できる値を、serde_dynamoで変換して使います
+///
+/// 変換は[`new`](`Self::new`)の時点で行うので、シリアライズできない値はそこでエラーになります。
+/// 読み込むときは[`Deserialized`](`crate::Deserialized`)を使ってください。
+/// ```
+/// # use dynamodb_utils::*;
+/// #[derive(serde::Serialize)]
+/// struct Profile {
+///     name: String,
+///     tags: Vec<String>,
+/// }
+///
+/// let update = Update::new().set(
+///     "profile",
+///     Serialized::new(Profile { name: "alice".into(), tags: vec![] })?,
+/// );
+/// # Ok::<(), Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Serialized(AttributeValue);
+
+impl Serialized {
+    pub fn new<T: Serialize>(value: T) -> Result<Self, Error> {
+        Ok(Self(crate::serde_dynamo::to_attribute_value(value)?))
+    }
+}
+
+impl IntoValue for Serialized {
+    fn into_value(self) -> AttributeValue {
+        self.0
+    }
+}
+
+/// partition key、sort keyに使える型
+pub trait KeyAttribute: IntoValue {
+    /// テーブル作成時の項目の型
+    fn attribute_type() -> ScalarAttributeType;
+}
+
+impl KeyAttribute for String {
+    fn attribute_type() -> ScalarAttributeType {
+        ScalarAttributeType::S
+    }
+}
+
+impl KeyAttribute for &str {
+    fn attribute_type() -> ScalarAttributeType {
+        ScalarAttributeType::S
+    }
+}
+
+impl<T: Number> KeyAttribute for T {
+    fn attribute_type() -> ScalarAttributeType {
+        ScalarAttributeType::N
+    }
+}
+
+impl KeyAttribute for Vec<u8> {
+    fn attribute_type() -> ScalarAttributeType {
+        ScalarAttributeType::B
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Deserialized, FromValue};
+
+    struct Failing;
+
+    impl Serialize for Failing {
+        fn serialize<S: serde::Serializer>(&self, _: S) -> Result<S::Ok, S::Error> {
+            Err(serde::ser::Error::custom("cannot serialize"))
+        }
+    }
+
+    #[test]
+    fn serialized_reports_serialize_error() {
+        assert!(matches!(Serialized::new(Failing), Err(Error::Serde(_))));
+    }
+
+    #[test]
+    fn serialized_round_trip() {
+        let value = Serialized::new(BTreeMap::from([("a", vec![1, 2])]))
+            .unwrap()
+            .into_value();
+        let Deserialized(map) =
+            Deserialized::<BTreeMap<String, Vec<i64>>>::from_value(&value).unwrap();
+        assert_eq!(map, BTreeMap::from([("a".to_owned(), vec![1, 2])]));
+    }
+}
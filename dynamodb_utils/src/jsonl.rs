@@ -0,0 +1,578 @@
+use crate::{
+    batch::BATCH_WRITE_LIMIT, sdk::types::AttributeValue, Autoscale, Backoff, BatchWrite, Client,
+    Condition, Error, FromValue, IntoValue, Scan, WriteOperation,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use futures_util::TryStreamExt;
+use serde_json::{Map, Value};
+use std::{collections::HashMap, fmt, sync::Arc};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt};
+
+type Item = HashMap<String, AttributeValue>;
+type ProgressCallback<T> = Arc<dyn Fn(T) + Send + Sync>;
+
+/// JSON Linesの1行の形式
+///
+/// 項目は名前順に並びます。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum JsonFormat {
+    /// `{"id":{"S":"a"},"count":{"N":"1"}}`の形式。すべての型をそのまま保存できます。
+    #[default]
+    DynamoDb,
+    /// `{"id":"a","count":1}`の形式。setはarrayになり、B型を含むitemは書き出せません。
+    Plain,
+}
+
+impl JsonFormat {
+    pub(crate) fn encode(self, item: &Item) -> Option<Value> {
+        match self {
+            JsonFormat::DynamoDb => Some(Value::Object(
+                item.iter()
+                    .map(|(name, value)| Some((name.clone(), to_dynamodb_json(value)?)))
+                    .collect::<Option<Map<_, _>>>()?,
+            )),
+            JsonFormat::Plain => {
+                Value::from_value(&AttributeValue::M(item.clone())).filter(Value::is_object)
+            }
+        }
+    }
+
+    pub(crate) fn decode(self, value: Value) -> Option<Item> {
+        let item = match self {
+            JsonFormat::DynamoDb => value
+                .as_object()?
+                .iter()
+                .map(|(name, value)| Some((name.clone(), from_dynamodb_json(value)?)))
+                .collect::<Option<Item>>()?,
+            JsonFormat::Plain => match value {
+                Value::Object(_) => value.into_value().as_m().ok()?.clone(),
+                _ => return None,
+            },
+        };
+        Some(item)
+    }
+}
+
+/// DynamoDB JSONの1つの値
+fn to_dynamodb_json(value: &AttributeValue) -> Option<Value> {
+    let (type_name, value) = match value {
+        AttributeValue::S(value) => ("S", Value::from(value.as_str())),
+        AttributeValue::N(value) => ("N", Value::from(value.as_str())),
+        AttributeValue::B(value) => ("B", Value::from(STANDARD.encode(value.as_ref()))),
+        AttributeValue::Bool(value) => ("BOOL", Value::from(*value)),
+        AttributeValue::Null(_) => ("NULL", Value::from(true)),
+        AttributeValue::Ss(values) => ("SS", Value::from(values.clone())),
+        AttributeValue::Ns(values) => ("NS", Value::from(values.clone())),
+        AttributeValue::Bs(values) => (
+            "BS",
+            values
+                .iter()
+                .map(|value| Value::from(STANDARD.encode(value.as_ref())))
+                .collect(),
+        ),
+        AttributeValue::L(values) => (
+            "L",
+            values
+                .iter()
+                .map(to_dynamodb_json)
+                .collect::<Option<Value>>()?,
+        ),
+        AttributeValue::M(values) => (
+            "M",
+            Value::Object(
+                values
+                    .iter()
+                    .map(|(name, value)| Some((name.clone(), to_dynamodb_json(value)?)))
+                    .collect::<Option<_>>()?,
+            ),
+        ),
+        _ => return None,
+    };
+    Some(Value::Object(Map::from_iter([(
+        type_name.to_owned(),
+        value,
+    )])))
+}
+
+fn from_dynamodb_json(value: &Value) -> Option<AttributeValue> {
+    let object = value.as_object().filter(|object| object.len() == 1)?;
+    let (type_name, value) = object.iter().next()?;
+    let strings = |value: &Value| {
+        value
+            .as_array()?
+            .iter()
+            .map(|value| value.as_str().map(str::to_owned))
+            .collect::<Option<Vec<_>>>()
+    };
+    let blob = |value: &Value| {
+        STANDARD
+            .decode(value.as_str()?)
+            .ok()
+            .map(crate::sdk::primitives::Blob::new)
+    };
+    Some(match type_name.as_str() {
+        "S" => AttributeValue::S(value.as_str()?.to_owned()),
+        "N" => AttributeValue::N(value.as_str()?.to_owned()),
+        "B" => AttributeValue::B(blob(value)?),
+        "BOOL" => AttributeValue::Bool(value.as_bool()?),
+        "NULL" => AttributeValue::Null(true),
+        "SS" => AttributeValue::Ss(strings(value)?),
+        "NS" => AttributeValue::Ns(strings(value)?),
+        "BS" => AttributeValue::Bs(value.as_array()?.iter().map(blob).collect::<Option<_>>()?),
+        "L" => AttributeValue::L(
+            value
+                .as_array()?
+                .iter()
+                .map(from_dynamodb_json)
+                .collect::<Option<_>>()?,
+        ),
+        "M" => AttributeValue::M(
+            value
+                .as_object()?
+                .iter()
+                .map(|(name, value)| Some((name.clone(), from_dynamodb_json(value)?)))
+                .collect::<Option<_>>()?,
+        ),
+        _ => return None,
+    })
+}
+
+/// [`Client::export_jsonl`]の設定
+#[derive(Clone, Default)]
+pub struct Export {
+    format: JsonFormat,
+    scan: Scan,
+    progress: Option<ProgressCallback<usize>>,
+}
+
+impl fmt::Debug for Export {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Export")
+            .field("format", &self.format)
+            .field("scan", &self.scan)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Export {
+    pub fn new(format: JsonFormat) -> Self {
+        Self {
+            format,
+            ..Self::default()
+        }
+    }
+
+    /// 書き出すitemの条件を指定します
+    ///
+    /// すでに条件がある場合は`AND`で結合されます。
+    pub fn filter(mut self, condition: Condition) -> Self {
+        self.scan = self.scan.filter(condition);
+        self
+    }
+
+    /// scanの分割数を指定します
+    pub fn segments(mut self, segments: i32) -> Self {
+        self.scan = self.scan.segments(segments);
+        self
+    }
+
+    /// 行をsegmentの順番通りに書き出すかどうかを指定します。デフォルトは`false`です。
+    ///
+    /// [`Scan::ordered`]と同じく、後ろのsegmentの結果は順番が来るまでメモリ上に保持します。
+    pub fn ordered(mut self, ordered: bool) -> Self {
+        self.scan = self.scan.ordered(ordered);
+        self
+    }
+
+    /// 1行書き出すごとに、それまでに書き出した件数で呼ばれます
+    pub fn on_progress(mut self, progress: impl Fn(usize) + Send + Sync + 'static) -> Self {
+        self.progress = Some(Arc::new(progress));
+        self
+    }
+}
+
+/// [`Client::import_jsonl`]の設定
+#[derive(Clone)]
+pub struct Import {
+    format: JsonFormat,
+    concurrency: usize,
+    backoff: Backoff,
+    resume_from: usize,
+    progress: Option<ProgressCallback<ImportProgress>>,
+}
+
+impl fmt::Debug for Import {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Import")
+            .field("format", &self.format)
+            .field("concurrency", &self.concurrency)
+            .field("backoff", &self.backoff)
+            .field("resume_from", &self.resume_from)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for Import {
+    fn default() -> Self {
+        Self {
+            format: JsonFormat::default(),
+            concurrency: 4,
+            backoff: Backoff::default(),
+            resume_from: 0,
+            progress: None,
+        }
+    }
+}
+
+impl Import {
+    pub fn new(format: JsonFormat) -> Self {
+        Self {
+            format,
+            ..Self::default()
+        }
+    }
+
+    /// 同時に送るBatchWriteItemの最大数を指定します。デフォルトは4です。
+    ///
+    /// スロットリングされると半分に減らし、書き込めるたびに1ずつ戻します。
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// スロットリングされたitemを再送するときの待ち時間を指定します
+    ///
+    /// `UnprocessedItems`の再送もここで行うので、BatchWriteItem自体は再送しません。
+    pub fn backoff(mut self, backoff: Backoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// 先頭の`lines`行を読み飛ばします
+    ///
+    /// 中断したときは[`ImportProgress::lines`]か[`Error::Import`]の`resume_from`を指定してください。
+    pub fn resume_from(mut self, lines: usize) -> Self {
+        self.resume_from = lines;
+        self
+    }
+
+    /// まとめて書き込むたびに呼ばれます
+    pub fn on_progress(
+        mut self,
+        progress: impl Fn(ImportProgress) + Send + Sync + 'static,
+    ) -> Self {
+        self.progress = Some(Arc::new(progress));
+        self
+    }
+}
+
+/// 取り込みの進み具合
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ImportProgress {
+    /// 書き込みが終わった行数。読み飛ばした行を含みます。
+    pub lines: usize,
+    /// 今回書き込んだitemの件数
+    pub written: usize,
+}
+
+impl<A: Autoscale> Client<A> {
+    /// テーブルのitemをJSON Linesで書き出します。書き出した件数を返します。
+    /// ```
+    /// # use dynamodb_utils::*;
+    /// # tokio_test::block_on(async {
+    /// let client = Client::mock();
+    /// for table_name in ["users", "users_copy"] {
+    ///     client
+    ///         .create_table(table_name, "id", None::<String>, TableType::OnDemand)
+    ///         .await?;
+    /// }
+    /// client.set_value("users", ("id", "u1"), "name", "alice").await?;
+    ///
+    /// let mut lines = vec![];
+    /// client
+    ///     .export_jsonl("users", &mut lines, Export::new(JsonFormat::DynamoDb))
+    ///     .await?;
+    /// assert_eq!(
+    ///     String::from_utf8(lines.clone()).unwrap(),
+    ///     "{\"id\":{\"S\":\"u1\"},\"name\":{\"S\":\"alice\"}}\n"
+    /// );
+    ///
+    /// let progress = client
+    ///     .import_jsonl("users_copy", &lines[..], Import::new(JsonFormat::DynamoDb))
+    ///     .await?;
+    /// assert_eq!(progress.written, 1);
+    /// # Ok::<(), Error>(())
+    /// # });
+    /// ```
+    pub async fn export_jsonl(
+        &self,
+        table_name: impl Into<String>,
+        writer: impl AsyncWrite + Unpin,
+        export: Export,
+    ) -> Result<usize, Error> {
+        let mut writer = writer;
+        let items = self.scan_item_raw_with(table_name, export.scan);
+        futures_util::pin_mut!(items);
+        let mut count = 0;
+        while let Some(item) = items.try_next().await? {
+            let mut line = export
+                .format
+                .encode(&item)
+                .ok_or(Error::Unencodable {
+                    line: count + 1,
+                    format: export.format,
+                })?
+                .to_string();
+            line.push('\n');
+            writer.write_all(line.as_bytes()).await?;
+            count += 1;
+            if let Some(progress) = &export.progress {
+                progress(count);
+            }
+        }
+        writer.flush().await?;
+        Ok(count)
+    }
+
+    /// JSON Linesのitemをテーブルに書き込みます
+    ///
+    /// `25 * concurrency`行ずつBatchWriteItemで書き込み、空行は読み飛ばします。
+    /// 書き込めなかった場合は[`Error::Import`]になり、`resume_from`から再開できます。
+    pub async fn import_jsonl(
+        &self,
+        table_name: impl Into<String>,
+        reader: impl AsyncBufRead + Unpin,
+        import: Import,
+    ) -> Result<ImportProgress, Error> {
+        let table_name = table_name.into();
+        let mut lines = reader.lines();
+        let mut progress = ImportProgress {
+            lines: import.resume_from,
+            written: 0,
+        };
+        let mut concurrency = import.concurrency;
+        let mut line_number = 0;
+        loop {
+            let mut operations = vec![];
+            while operations.len() < BATCH_WRITE_LIMIT * concurrency {
+                let Some(line) = lines.next_line().await? else {
+                    break;
+                };
+                line_number += 1;
+                if line_number <= import.resume_from || line.trim().is_empty() {
+                    continue;
+                }
+                let invalid = |message: String| Error::InvalidJson {
+                    line: line_number,
+                    message,
+                };
+                let value = serde_json::from_str(&line).map_err(|e| invalid(e.to_string()))?;
+                let item = import
+                    .format
+                    .decode(value)
+                    .ok_or_else(|| invalid("not an item".to_owned()))?;
+                operations.push(WriteOperation::put_raw(&table_name, item));
+            }
+            if operations.is_empty() {
+                progress.lines = line_number.max(progress.lines);
+                return Ok(progress);
+            }
+
+            let mut attempt = 0;
+            while !operations.is_empty() {
+                let output = self
+                    .batch_write(
+                        operations
+                            .into_iter()
+                            .fold(BatchWrite::new(), BatchWrite::push)
+                            .concurrency(concurrency)
+                            .backoff(Backoff {
+                                max_retries: 0,
+                                ..import.backoff.clone()
+                            }),
+                    )
+                    .await;
+                progress.written += output.written;
+                // 再送しなかったUnprocessedItemsはRetryLimitExceededで返ってきます
+                let (throttled, failed) =
+                    output
+                        .failures
+                        .into_iter()
+                        .partition::<Vec<_>, _>(|failure| {
+                            failure.error.is_throttling()
+                                || matches!(failure.error, Error::RetryLimitExceeded)
+                        });
+                if let Some(failure) = failed.into_iter().next() {
+                    return Err(Error::Import {
+                        resume_from: progress.lines,
+                        source: Box::new(failure.error),
+                    });
+                }
+                operations = throttled
+                    .into_iter()
+                    .flat_map(|failure| failure.operations)
+                    .collect();
+                if operations.is_empty() {
+                    concurrency = (concurrency + 1).min(import.concurrency);
+                } else {
+                    concurrency = (concurrency / 2).max(1);
+                    attempt += 1;
+                    if !import.backoff.wait(attempt).await {
+                        return Err(Error::Import {
+                            resume_from: progress.lines,
+                            source: Box::new(Error::RetryLimitExceeded),
+                        });
+                    }
+                }
+            }
+            progress.lines = line_number;
+            if let Some(callback) = &import.progress {
+                callback(progress);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Key, TableType};
+
+    #[test]
+    fn export_reports_unencodable_item() {
+        tokio_test::block_on(async {
+            let client = Client::mock();
+            client
+                .create_table("files", "id", None::<String>, TableType::OnDemand)
+                .await
+                .unwrap();
+            client
+                .set_value("files", ("id", "f1"), "body", vec![1u8, 2])
+                .await
+                .unwrap();
+
+            let mut lines = vec![];
+            let error = client
+                .export_jsonl("files", &mut lines, Export::new(JsonFormat::Plain))
+                .await
+                .unwrap_err();
+            assert!(matches!(
+                error,
+                Error::Unencodable {
+                    line: 1,
+                    format: JsonFormat::Plain
+                }
+            ));
+
+            let count = client
+                .export_jsonl("files", &mut lines, Export::new(JsonFormat::DynamoDb))
+                .await
+                .unwrap();
+            assert_eq!(count, 1);
+        });
+    }
+
+    async fn create_users(client: &Client) {
+        client
+            .create_table("users", "id", None::<String>, TableType::OnDemand)
+            .await
+            .unwrap();
+    }
+
+    #[test]
+    fn import_resumes_and_reports_progress() {
+        tokio_test::block_on(async {
+            let client = Client::mock();
+            create_users(&client).await;
+            let lines = ["{\"id\":\"a\"}", "", "{\"id\":\"b\"}", "{\"id\":\"c\"}"].join("\n");
+            let reported = Arc::new(std::sync::Mutex::new(vec![]));
+            let import = Import::new(JsonFormat::Plain).resume_from(2).on_progress({
+                let reported = reported.clone();
+                move |progress| reported.lock().unwrap().push(progress)
+            });
+            let progress = client
+                .import_jsonl("users", lines.as_bytes(), import)
+                .await
+                .unwrap();
+            assert_eq!(
+                progress,
+                ImportProgress {
+                    lines: 4,
+                    written: 2
+                }
+            );
+            assert_eq!(*reported.lock().unwrap(), [progress]);
+            let ids = client
+                .scan_item_raw("users")
+                .map_ok(|item| item["id"].as_s().unwrap().clone())
+                .try_collect::<std::collections::BTreeSet<_>>()
+                .await
+                .unwrap();
+            assert_eq!(ids, ["b".to_owned(), "c".to_owned()].into());
+        });
+    }
+
+    #[test]
+    fn import_reports_invalid_line() {
+        tokio_test::block_on(async {
+            let client = Client::mock();
+            create_users(&client).await;
+            let lines = "{\"id\":\"a\"}\n[1]\n";
+            let error = client
+                .import_jsonl("users", lines.as_bytes(), Import::new(JsonFormat::Plain))
+                .await
+                .unwrap_err();
+            assert!(
+                matches!(error, Error::InvalidJson { line: 2, .. }),
+                "{error:?}"
+            );
+        });
+    }
+
+    #[test]
+    fn plain_round_trip() {
+        tokio_test::block_on(async {
+            let client = Client::mock();
+            create_users(&client).await;
+            client
+                .create_table("users_copy", "id", None::<String>, TableType::OnDemand)
+                .await
+                .unwrap();
+            client
+                .put_item(
+                    "users",
+                    serde_json::json!({
+                        "id": "u1",
+                        "age": 20,
+                        "active": true,
+                        "tags": ["a", "b"],
+                        "profile": { "name": "alice", "nickname": null },
+                    }),
+                )
+                .await
+                .unwrap();
+
+            let mut lines = vec![];
+            client
+                .export_jsonl("users", &mut lines, Export::new(JsonFormat::Plain))
+                .await
+                .unwrap();
+            client
+                .import_jsonl("users_copy", &lines[..], Import::new(JsonFormat::Plain))
+                .await
+                .unwrap();
+            let original = client
+                .get_item_raw("users", Key::new("id", "u1"))
+                .await
+                .unwrap()
+                .item;
+            let copied = client
+                .get_item_raw("users_copy", Key::new("id", "u1"))
+                .await
+                .unwrap()
+                .item;
+            assert!(original.is_some());
+            assert_eq!(original, copied);
+        });
+    }
+}
@@ -0,0 +1,92 @@
+use crate::{sdk::types::AttributeValue, IntoValue};
+use std::collections::HashMap;
+
+/// itemを特定するためのkey
+///
+/// partition keyのみ、またはpartition keyとsort keyの組で構成されます。
+/// `(key_name, key_value)`や`(key_name, key_value, sort_key_name, sort_key_value)`の
+/// タプルから変換できます。
+/// ```
+/// # use dynamodb_utils::Key;
+/// let key: Key = ("id", "abc").into();
+/// let composite: Key = ("user_id", "abc", "created_at", 1716877593).into();
+/// let same = Key::new("user_id", "abc").with_sort("created_at", 1716877593);
+/// assert_eq!(composite, same);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Key {
+    partition: (String, AttributeValue),
+    sort: Option<(String, AttributeValue)>,
+}
+
+impl Key {
+    /// partition keyのみのkeyを作ります
+    pub fn new(name: impl Into<String>, value: impl IntoValue) -> Self {
+        Self {
+            partition: (name.into(), value.into_value()),
+            sort: None,
+        }
+    }
+
+    /// partition keyとsort keyの組のkeyを作ります
+    pub fn composite(
+        partition_name: impl Into<String>,
+        partition_value: impl IntoValue,
+        sort_name: impl Into<String>,
+        sort_value: impl IntoValue,
+    ) -> Self {
+        Self::new(partition_name, partition_value).with_sort(sort_name, sort_value)
+    }
+
+    /// sort keyを設定します
+    pub fn with_sort(mut self, name: impl Into<String>, value: impl IntoValue) -> Self {
+        self.sort = Some((name.into(), value.into_value()));
+        self
+    }
+
+    /// partition keyの項目名と値
+    pub fn partition(&self) -> (&str, &AttributeValue) {
+        (&self.partition.0, &self.partition.1)
+    }
+
+    /// sort keyの項目名と値
+    pub fn sort(&self) -> Option<(&str, &AttributeValue)> {
+        self.sort
+            .as_ref()
+            .map(|(name, value)| (name.as_str(), value))
+    }
+
+    /// SDKにそのまま渡せる形にします
+    pub fn into_item(self) -> HashMap<String, AttributeValue> {
+        let mut item = HashMap::with_capacity(2);
+        item.insert(self.partition.0, self.partition.1);
+        if let Some((name, value)) = self.sort {
+            item.insert(name, value);
+        }
+        item
+    }
+}
+
+impl<N: Into<String>, V: IntoValue> From<(N, V)> for Key {
+    fn from((name, value): (N, V)) -> Self {
+        Self::new(name, value)
+    }
+}
+
+impl<N1, V1, N2, V2> From<(N1, V1, N2, V2)> for Key
+where
+    N1: Into<String>,
+    V1: IntoValue,
+    N2: Into<String>,
+    V2: IntoValue,
+{
+    fn from((partition_name, partition_value, sort_name, sort_value): (N1, V1, N2, V2)) -> Self {
+        Self::composite(partition_name, partition_value, sort_name, sort_value)
+    }
+}
+
+impl From<Key> for HashMap<String, AttributeValue> {
+    fn from(value: Key) -> Self {
+        value.into_item()
+    }
+}
@@ -1,8 +1,61 @@
+pub use autoscale::{AdaptiveCapacity, AdaptiveCapacityConfig, Autoscale, ProvisionedScaling};
+pub use batch::{
+    BatchGet, BatchWrite, BatchWriteFailure, BatchWriteOutput, BatchWriter, WriteOperation,
+};
 pub use client::{Client, Error, TableType};
-pub use into_values::IntoValue;
+pub use condition::Condition;
+pub use counter::{Sequence, ShardedCounter};
+#[cfg(feature = "derive")]
+pub use dynamodb_utils_derive::DynamoEntity;
+pub use entity::DynamoEntity;
+pub use expression::Path;
+pub use from_values::{Deserialized, FromValue, ItemExt};
+pub use idempotency::IdempotencyStore;
+pub use into_values::{IntoValue, KeyAttribute, ListElement, Serialized, SetElement};
+pub use jsonl::{Export, Import, ImportProgress, JsonFormat};
+pub use key::Key;
+pub use lifecycle::{EnsureTableOutput, WaitConfig};
+pub use lock::{LockClient, LockGuard};
+pub use page::{Page, PageRequest};
+pub use query::Query;
+pub use retry::Backoff;
+pub use scan::Scan;
+#[cfg(feature = "streams")]
+pub use streams::{
+    ChangeEvent, CheckpointStore, MemoryCheckpoints, StreamConsumer, TableCheckpoints,
+};
+pub use table::{KeySchema, KeyValue, Table};
+pub use table_definition::{GlobalIndex, LocalIndex, Projection, TableDefinition, TableDrift};
+pub use transaction::{CancellationReason, FromTransactItems, TransactGet, TransactWrite};
+pub use ttl::Expiry;
+pub use update::Update;
 
+mod autoscale;
+mod batch;
 mod client;
+mod condition;
+mod counter;
+mod entity;
+mod expression;
+mod from_values;
+mod idempotency;
 mod into_values;
+mod jsonl;
+mod key;
+mod lifecycle;
+mod lock;
+mod mock;
+mod page;
+mod query;
+mod retry;
+mod scan;
+#[cfg(feature = "streams")]
+mod streams;
+mod table;
+mod table_definition;
+mod transaction;
+mod ttl;
+mod update;
 pub mod utils;
 
 pub mod sdk {
@@ -10,6 +63,11 @@ pub mod sdk {
     pub use aws_smithy_types_convert::stream::*;
 }
 
+#[cfg(feature = "streams")]
+pub mod sdk_streams {
+    pub use aws_sdk_dynamodbstreams::*;
+}
+
 pub mod sdk_config {
     pub use aws_config::*;
 }
@@ -0,0 +1,192 @@
+use crate::{
+    client::from_sdk_error,
+    sdk::types::{IndexStatus, TableDescription, TableStatus, TimeToLiveStatus},
+    table_definition::TableDrift,
+    Autoscale, Client, Error, TableDefinition,
+};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// テーブルの状態が変わるのを待つときの設定
+///
+/// 確認の間隔は`delay`から始まって毎回倍になり、`max_delay`で頭打ちになります。
+#[derive(Debug, Clone)]
+pub struct WaitConfig {
+    /// これを超えると[`Error::WaitTimeout`]になります
+    pub timeout: Duration,
+    pub delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for WaitConfig {
+    fn default() -> Self {
+        Self {
+            timeout: Duration::from_secs(300),
+            delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(20),
+        }
+    }
+}
+
+impl WaitConfig {
+    /// `condition`が`Some`を返すまで間隔を空けて繰り返します
+    async fn wait_for<T, F, Fut>(&self, table_name: &str, mut condition: F) -> Result<T, Error>
+    where
+        F: FnMut() -> Fut,
+        Fut: std::future::Future<Output = Result<Option<T>, Error>>,
+    {
+        let deadline = Instant::now() + self.timeout;
+        let mut delay = self.delay;
+        loop {
+            if let Some(value) = condition().await? {
+                return Ok(value);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(Error::WaitTimeout(table_name.to_owned()));
+            }
+            tokio::time::sleep(delay.min(deadline - now)).await;
+            delay = delay.saturating_mul(2).min(self.max_delay);
+        }
+    }
+}
+
+/// [`Client::ensure_table`]の結果
+#[derive(Debug, Clone)]
+pub struct EnsureTableOutput {
+    /// テーブルを新しく作ったかどうか
+    pub created: bool,
+    /// ACTIVEになったテーブルの情報
+    pub table: TableDescription,
+    /// 既存のテーブルと定義の違い。作った場合は常に空です。
+    pub drift: Vec<TableDrift>,
+}
+
+impl<A: Autoscale> Client<A> {
+    /// テーブルの情報を取得します
+    pub async fn describe_table(
+        &self,
+        table_name: impl Into<String>,
+    ) -> Result<TableDescription, Error> {
+        if let Some(mock) = &self.mock {
+            return mock.describe_table(&table_name.into());
+        }
+        self.dynamodb
+            .describe_table()
+            .table_name(table_name)
+            .send()
+            .await
+            .map_err(from_sdk_error)?
+            .table
+            .ok_or(Error::NotFound)
+    }
+
+    /// テーブルとすべてのGSIがACTIVEになるまで待ちます
+    ///
+    /// 作成直後でテーブルがまだ見つからない場合も待ち続けます。
+    pub async fn wait_until_active(
+        &self,
+        table_name: impl Into<String>,
+        config: WaitConfig,
+    ) -> Result<TableDescription, Error> {
+        let table_name = table_name.into();
+        config
+            .wait_for(&table_name, || async {
+                let table = match self.describe_table(&table_name).await {
+                    Ok(table) => table,
+                    Err(Error::ResourceNotFound(_)) => return Ok(None),
+                    Err(e) => return Err(e),
+                };
+                let active = table.table_status() == Some(&TableStatus::Active)
+                    && table
+                        .global_secondary_indexes()
+                        .iter()
+                        .all(|index| index.index_status() == Some(&IndexStatus::Active));
+                Ok(active.then_some(table))
+            })
+            .await
+    }
+
+    /// テーブルの削除が終わるまで待ちます
+    pub async fn wait_until_deleted(
+        &self,
+        table_name: impl Into<String>,
+        config: WaitConfig,
+    ) -> Result<(), Error> {
+        let table_name = table_name.into();
+        config
+            .wait_for(&table_name, || async {
+                match self.describe_table(&table_name).await {
+                    Ok(_) => Ok(None),
+                    Err(Error::ResourceNotFound(_)) => Ok(Some(())),
+                    Err(e) => Err(e),
+                }
+            })
+            .await
+    }
+
+    /// テーブルが無ければ作り、ACTIVEになるまで待ちます。作った場合は定義にあるTTLも有効にします。
+    ///
+    /// 既にある場合は作り直さず、keyとindexとstreamとTTLの定義との違いを[`EnsureTableOutput::drift`]で返します。
+    /// ```
+    /// # use dynamodb_utils::*;
+    /// # use dynamodb_utils::sdk::types::ScalarAttributeType;
+    /// # tokio_test::block_on(async {
+    /// let client = Client::mock();
+    /// let definition = TableDefinition::new("users", "id", ScalarAttributeType::S);
+    /// let output = client.ensure_table(definition.clone(), WaitConfig::default()).await?;
+    /// assert!(output.created);
+    ///
+    /// let changed = definition.sort_key("created_at", ScalarAttributeType::N);
+    /// let output = client.ensure_table(changed, WaitConfig::default()).await?;
+    /// assert!(!output.created);
+    /// assert!(matches!(output.drift[..], [TableDrift::KeySchema { .. }]));
+    /// # Ok::<(), Error>(())
+    /// # });
+    /// ```
+    pub async fn ensure_table(
+        &self,
+        definition: TableDefinition,
+        config: WaitConfig,
+    ) -> Result<EnsureTableOutput, Error> {
+        let table_name = definition.get_table_name().to_owned();
+        let created = match self.describe_table(&table_name).await {
+            Ok(_) => false,
+            Err(Error::ResourceNotFound(_)) => {
+                match self.create_table_only(&definition).await {
+                    Ok(_) => true,
+                    // 同時に作られた場合
+                    Err(e) if e.is_resource_in_use() => false,
+                    Err(e) => return Err(e),
+                }
+            }
+            Err(e) => return Err(e),
+        };
+        let table = self.wait_until_active(&table_name, config.clone()).await?;
+        let drift = if created {
+            self.apply_ttl(&definition, config).await?;
+            vec![]
+        } else {
+            let mut drift = definition.drift(&table);
+            let ttl = self.describe_ttl(&table_name).await?;
+            let actual_ttl = match ttl.time_to_live_status() {
+                Some(TimeToLiveStatus::Enabled | TimeToLiveStatus::Enabling) => {
+                    ttl.attribute_name().map(str::to_owned)
+                }
+                _ => None,
+            };
+            if definition.get_ttl() != actual_ttl.as_deref() {
+                drift.push(TableDrift::Ttl {
+                    expected: definition.get_ttl().map(str::to_owned),
+                    actual: actual_ttl,
+                });
+            }
+            drift
+        };
+        Ok(EnsureTableOutput {
+            created,
+            table,
+            drift,
+        })
+    }
+}
@@ -0,0 +1,393 @@
+use crate::{
+    sdk::types::ScalarAttributeType, Autoscale, Client, Condition, Error, IntoValue, Key,
+    TableDefinition, Update,
+};
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tokio::{task::JoinHandle, time::Instant};
+
+const LOCK_NAME: &str = "lock_name";
+const OWNER: &str = "owner";
+const LEASE_ID: &str = "lease_id";
+/// UNIX時間のミリ秒
+const LEASE_UNTIL: &str = "lease_until";
+
+/// 他と重ならないID
+pub(crate) fn random_id() -> String {
+    uuid::Uuid::new_v4().simple().to_string()
+}
+
+pub(crate) fn now_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_millis() as i64)
+}
+
+/// DynamoDBのテーブルを使った、ホストをまたぐ排他制御
+///
+/// lockは`lease_duration`の間だけ有効で、取得している間は`heartbeat_interval`ごとに延長します。
+/// 期限が過ぎたlockは他のownerが奪えます。期限の判定は各ホストの時計を使います。
+/// 延長はtokioのtaskで行うので、tokioのruntimeの上で使ってください。
+/// ```
+/// # use dynamodb_utils::*;
+/// # use std::time::Duration;
+/// # tokio_test::block_on(async {
+/// let client = Client::mock();
+/// client.create_table_with(LockClient::table_definition("locks")).await?;
+/// let locks = client.lock_client("locks");
+///
+/// let guard = locks.acquire("daily-report", Duration::from_secs(10)).await?;
+/// assert!(locks.try_acquire("daily-report").await?.is_none());
+/// guard.release().await?;
+/// assert!(locks.try_acquire("daily-report").await?.is_some());
+/// # Ok::<(), Error>(())
+/// # });
+/// ```
+#[derive(Debug, Clone)]
+pub struct LockClient<A = ()> {
+    client: Client<A>,
+    table_name: String,
+    owner: String,
+    lease_duration: Duration,
+    heartbeat_interval: Duration,
+    retry_interval: Duration,
+}
+
+impl LockClient {
+    /// lockを保存するテーブルの定義。partition keyは`lock_name`です。
+    pub fn table_definition(table_name: impl Into<String>) -> TableDefinition {
+        TableDefinition::new(table_name, LOCK_NAME, ScalarAttributeType::S)
+    }
+}
+
+impl<A: Autoscale> Client<A> {
+    /// `table_name`にlockを保存する[`LockClient`]を作ります。prefixは付きません。
+    pub fn lock_client(&self, table_name: impl Into<String>) -> LockClient<A> {
+        LockClient {
+            client: self.clone(),
+            table_name: table_name.into(),
+            owner: random_id(),
+            lease_duration: Duration::from_secs(20),
+            heartbeat_interval: Duration::from_secs(5),
+            retry_interval: Duration::from_secs(1),
+        }
+    }
+}
+
+impl<A: Autoscale> LockClient<A> {
+    /// ownerのIDを指定します。デフォルトはランダムなIDです。
+    pub fn owner(mut self, owner: impl Into<String>) -> Self {
+        self.owner = owner.into();
+        self
+    }
+
+    /// lockの有効期間を指定します。デフォルトは20秒です。
+    pub fn lease_duration(mut self, lease_duration: Duration) -> Self {
+        self.lease_duration = lease_duration;
+        self
+    }
+
+    /// lockを延長する間隔を指定します。デフォルトは5秒です。
+    ///
+    /// `lease_duration`より十分短くしてください。
+    pub fn heartbeat_interval(mut self, heartbeat_interval: Duration) -> Self {
+        self.heartbeat_interval = heartbeat_interval;
+        self
+    }
+
+    /// [`acquire`](`Self::acquire`)でlockを取り直す間隔を指定します。デフォルトは1秒です。
+    pub fn retry_interval(mut self, retry_interval: Duration) -> Self {
+        self.retry_interval = retry_interval;
+        self
+    }
+
+    pub fn get_owner(&self) -> &str {
+        &self.owner
+    }
+
+    /// lockを取得します。他のownerが持っている場合は`None`になります。
+    ///
+    /// 期限が過ぎたlockは奪います。
+    pub async fn try_acquire(
+        &self,
+        lock_name: impl Into<String>,
+    ) -> Result<Option<LockGuard<A>>, Error> {
+        let lock_name = lock_name.into();
+        let lease_id = random_id();
+        let sent = Instant::now();
+        let now = now_millis();
+        let item = HashMap::from([
+            (LOCK_NAME.to_owned(), lock_name.as_str().into_value()),
+            (OWNER.to_owned(), self.owner.as_str().into_value()),
+            (LEASE_ID.to_owned(), lease_id.as_str().into_value()),
+            (
+                LEASE_UNTIL.to_owned(),
+                (now + self.lease_duration.as_millis() as i64).into_value(),
+            ),
+        ]);
+        let condition =
+            Condition::attribute_not_exists(LOCK_NAME).or(Condition::lt(LEASE_UNTIL, now));
+        match self
+            .client
+            .put_item_raw_if(&self.table_name, item, condition)
+            .await
+        {
+            Ok(_) => {}
+            Err(Error::ConditionalCheckFailed { .. }) => return Ok(None),
+            Err(e) => return Err(e),
+        }
+        let lost = Arc::new(AtomicBool::new(false));
+        let heartbeat = tokio::spawn(heartbeat(
+            self.clone(),
+            lock_name.clone(),
+            lease_id.clone(),
+            sent + self.lease_duration,
+            lost.clone(),
+        ));
+        Ok(Some(LockGuard {
+            client: self.client.clone(),
+            table_name: self.table_name.clone(),
+            lock_name,
+            lease_id,
+            lost,
+            heartbeat: Some(heartbeat),
+            released: false,
+        }))
+    }
+
+    /// lockを取得できるまで、`timeout`の間待ちます
+    ///
+    /// 取得できなかった場合は[`Error::LockTimeout`]になります。
+    pub async fn acquire(
+        &self,
+        lock_name: impl Into<String>,
+        timeout: Duration,
+    ) -> Result<LockGuard<A>, Error> {
+        let lock_name = lock_name.into();
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(guard) = self.try_acquire(lock_name.as_str()).await? {
+                return Ok(guard);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(Error::LockTimeout(lock_name));
+            }
+            tokio::time::sleep(self.retry_interval.min(deadline - now)).await;
+        }
+    }
+
+    /// 自分が持っているlockの期限を延長します
+    async fn renew(&self, lock_name: &str, lease_id: &str) -> Result<(), Error> {
+        let lease_until = now_millis() + self.lease_duration.as_millis() as i64;
+        self.client
+            .update_item(
+                &self.table_name,
+                Key::new(LOCK_NAME, lock_name),
+                Update::new()
+                    .set(LEASE_UNTIL, lease_until)
+                    .condition(Condition::eq(LEASE_ID, lease_id)),
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+/// lockを奪われるか、延長できないまま期限が過ぎるまで延長し続けます
+///
+/// `lease_until`は最後に延長できたlockの期限です。
+async fn heartbeat<A: Autoscale>(
+    locks: LockClient<A>,
+    lock_name: String,
+    lease_id: String,
+    mut lease_until: Instant,
+    lost: Arc<AtomicBool>,
+) {
+    loop {
+        tokio::time::sleep(locks.heartbeat_interval).await;
+        let sent = Instant::now();
+        match locks.renew(&lock_name, &lease_id).await {
+            Ok(()) => lease_until = sent + locks.lease_duration,
+            Err(Error::ConditionalCheckFailed { .. }) => {
+                lost.store(true, Ordering::Relaxed);
+                return;
+            }
+            // 期限までは、次の延長で取り戻せます
+            Err(_) if Instant::now() < lease_until => {}
+            Err(_) => {
+                lost.store(true, Ordering::Relaxed);
+                return;
+            }
+        }
+    }
+}
+
+/// 取得したlock
+///
+/// dropすると延長をやめ、tokioのruntimeの上であればバックグラウンドで解放します。
+/// 解放の結果を知りたい場合は[`release`](`Self::release`)を使ってください。
+#[derive(Debug)]
+pub struct LockGuard<A: Autoscale = ()> {
+    client: Client<A>,
+    table_name: String,
+    lock_name: String,
+    lease_id: String,
+    lost: Arc<AtomicBool>,
+    heartbeat: Option<JoinHandle<()>>,
+    released: bool,
+}
+
+impl<A: Autoscale> LockGuard<A> {
+    pub fn get_lock_name(&self) -> &str {
+        &self.lock_name
+    }
+
+    /// 他のownerに奪われたか、延長できないまま期限が過ぎたかどうか
+    ///
+    /// 判定は延長を試みたときに行うので、最大で`heartbeat_interval`遅れます。
+    pub fn is_lost(&self) -> bool {
+        self.lost.load(Ordering::Relaxed)
+    }
+
+    /// lockを解放します
+    ///
+    /// すでに奪われていた場合は[`Error::ConditionalCheckFailed`]になります。
+    pub async fn release(mut self) -> Result<(), Error> {
+        self.released = true;
+        if let Some(heartbeat) = self.heartbeat.take() {
+            heartbeat.abort();
+        }
+        release(
+            &self.client,
+            &self.table_name,
+            &self.lock_name,
+            &self.lease_id,
+        )
+        .await
+    }
+}
+
+impl<A: Autoscale> Drop for LockGuard<A> {
+    fn drop(&mut self) {
+        if let Some(heartbeat) = self.heartbeat.take() {
+            heartbeat.abort();
+        }
+        if self.released {
+            return;
+        }
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let client = self.client.clone();
+        let table_name = std::mem::take(&mut self.table_name);
+        let lock_name = std::mem::take(&mut self.lock_name);
+        let lease_id = std::mem::take(&mut self.lease_id);
+        runtime.spawn(async move {
+            let _ = release(&client, &table_name, &lock_name, &lease_id).await;
+        });
+    }
+}
+
+async fn release<A: Autoscale>(
+    client: &Client<A>,
+    table_name: &str,
+    lock_name: &str,
+    lease_id: &str,
+) -> Result<(), Error> {
+    client
+        .delete_item_if(
+            table_name,
+            Key::new(LOCK_NAME, lock_name),
+            Condition::eq(LEASE_ID, lease_id),
+        )
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn lock_client(lease_duration: u64, heartbeat_interval: u64) -> LockClient {
+        let client = Client::mock();
+        client
+            .create_table_with(LockClient::table_definition("locks"))
+            .await
+            .unwrap();
+        client
+            .lock_client("locks")
+            .lease_duration(Duration::from_millis(lease_duration))
+            .heartbeat_interval(Duration::from_millis(heartbeat_interval))
+    }
+
+    #[test]
+    fn heartbeat_keeps_lock() {
+        tokio_test::block_on(async {
+            let locks = lock_client(1000, 100).await;
+            let guard = locks.try_acquire("job").await.unwrap().unwrap();
+            tokio::time::sleep(Duration::from_millis(2000)).await;
+            let other = locks.clone().owner("other");
+            assert!(other.try_acquire("job").await.unwrap().is_none());
+            assert!(!guard.is_lost());
+            guard.release().await.unwrap();
+        });
+    }
+
+    #[test]
+    fn expired_lock_is_stolen() {
+        tokio_test::block_on(async {
+            let locks = lock_client(500, 60_000).await;
+            let guard = locks.try_acquire("job").await.unwrap().unwrap();
+            let other = locks.clone().owner("other");
+            assert!(other.try_acquire("job").await.unwrap().is_none());
+            tokio::time::sleep(Duration::from_millis(1500)).await;
+            let stolen = other.try_acquire("job").await.unwrap().unwrap();
+            // 奪われたlockは解放できません
+            assert!(matches!(
+                guard.release().await,
+                Err(Error::ConditionalCheckFailed { .. })
+            ));
+            stolen.release().await.unwrap();
+        });
+    }
+
+    #[test]
+    fn stolen_lock_is_lost_on_next_heartbeat() {
+        tokio_test::block_on(async {
+            let locks = lock_client(200, 1500).await;
+            let guard = locks.try_acquire("job").await.unwrap().unwrap();
+            // 期限が切れてから、次のheartbeatまでの間に奪います
+            tokio::time::sleep(Duration::from_millis(700)).await;
+            let _stolen = locks
+                .clone()
+                .owner("other")
+                .try_acquire("job")
+                .await
+                .unwrap()
+                .unwrap();
+            assert!(!guard.is_lost());
+            tokio::time::sleep(Duration::from_millis(1800)).await;
+            assert!(guard.is_lost());
+        });
+    }
+
+    #[test]
+    fn lock_is_lost_when_renewal_keeps_failing() {
+        tokio_test::block_on(async {
+            let locks = lock_client(2000, 100).await;
+            let guard = locks.try_acquire("job").await.unwrap().unwrap();
+            locks.client.delete_table("locks").await.unwrap();
+            // 期限までは延長に失敗しても持っているとみなします
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            assert!(!guard.is_lost());
+            tokio::time::sleep(Duration::from_millis(2500)).await;
+            assert!(guard.is_lost());
+        });
+    }
+}
@@ -0,0 +1,1586 @@
+use crate::{
+    client::from_aws_sdk_dynamodb_error,
+    condition::{Comparator, ConditionExpr},
+    expression::PathElement,
+    query::SortKeyCondition,
+    sdk::{
+        config::{
+            interceptors::BeforeSerializationInterceptorContextRef, retry::RetryConfig,
+            BehaviorVersion, ConfigBag, Credentials, Intercept, Region,
+        },
+        error::{BoxError, ErrorMetadata},
+        operation::{
+            create_table::CreateTableOutput,
+            delete_item::DeleteItemOutput,
+            delete_table::DeleteTableOutput,
+            get_item::GetItemOutput,
+            put_item::PutItemOutput,
+            update_item::{UpdateItemError, UpdateItemOutput},
+            update_table::UpdateTableOutput,
+            update_time_to_live::UpdateTimeToLiveOutput,
+        },
+        types::{
+            error::{
+                ConditionalCheckFailedException, ResourceInUseException, ResourceNotFoundException,
+            },
+            AttributeValue, ReturnValue, ScalarAttributeType, TableDescription, TableStatus,
+            TimeToLiveDescription, TimeToLiveSpecification, TimeToLiveStatus,
+        },
+    },
+    update::{SetValue, UpdateAction},
+    Client, Condition, Error, Path, Query, Scan, TableDefinition, Update,
+};
+use futures_util::{stream, Stream};
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+type Item = HashMap<String, AttributeValue>;
+
+impl Client {
+    /// Mock用のClientを作ります。
+    ///
+    /// テーブルとitemをメモリ上に持ち、DynamoDBには接続しません。
+    /// cloneしたClientとは中身を共有します。
+    /// 使う前に[`create_table`](`Client::create_table`)などでテーブルを作ってください。
+    ///
+    /// 対応しているのはget, put, delete, update(`set_value`, `add_value`を含む), scan, query,
+    /// batch get, batch write, テーブルの作成と削除、TTLの設定です。
+    /// transactionは[`Error::UnsupportedByMock`]になります。
+    /// [`raw_client`](`Client::raw_client`)で直接送ったリクエストも、送信前にエラーになります。
+    /// ```
+    /// # use dynamodb_utils::*;
+    /// # tokio_test::block_on(async {
+    /// let client = Client::mock();
+    /// client
+    ///     .create_table("users", "id", None::<String>, TableType::OnDemand)
+    ///     .await?;
+    /// client.set_value("users", ("id", "u1"), "name", "alice").await?;
+    /// client.add_value("users", ("id", "u1"), "count", 1).await?;
+    /// let item = client.get_item_raw("users", ("id", "u1")).await?.item.unwrap();
+    /// assert_eq!(item["count"], 1.into_value());
+    /// # Ok::<(), Error>(())
+    /// # });
+    /// ```
+    pub fn mock() -> Self {
+        let config = aws_sdk_dynamodb::Config::builder()
+            .behavior_version(BehaviorVersion::latest())
+            .region(Region::from_static("mock"))
+            .credentials_provider(Credentials::new("mock", "mock", None, None, "mock"))
+            .endpoint_url("http://127.0.0.1:0")
+            .retry_config(RetryConfig::disabled())
+            .interceptor(RejectRequests)
+            .build();
+        let mut client = Self::from_conf(config);
+        client.mock = Some(Arc::default());
+        client
+    }
+}
+
+impl<A> Client<A> {
+    /// mockかどうか
+    pub fn is_mock(&self) -> bool {
+        self.mock.is_some()
+    }
+
+    /// mockで対応していない操作を[`Error::UnsupportedByMock`]にします
+    pub(crate) fn reject_mock(&self, operation: &'static str) -> Result<(), Error> {
+        match self.mock {
+            Some(_) => Err(Error::UnsupportedByMock(operation)),
+            None => Ok(()),
+        }
+    }
+}
+
+/// mockのclientから送られようとしたリクエストを止めます
+#[derive(Debug)]
+struct RejectRequests;
+
+impl Intercept for RejectRequests {
+    fn name(&self) -> &'static str {
+        "RejectRequests"
+    }
+
+    fn read_before_execution(
+        &self,
+        _context: &BeforeSerializationInterceptorContextRef<'_>,
+        _cfg: &mut ConfigBag,
+    ) -> Result<(), BoxError> {
+        Err("this operation is not supported by the mock client".into())
+    }
+}
+
+/// mockのテーブルを保持する
+#[derive(Debug, Default)]
+pub(crate) struct MockDatabase {
+    tables: Mutex<HashMap<String, MockTable>>,
+}
+
+#[derive(Debug)]
+struct MockTable {
+    definition: TableDefinition,
+    /// TTLが有効な場合の項目名
+    ttl_attribute: Option<String>,
+    items: Vec<Item>,
+}
+
+impl MockTable {
+    fn key_names(&self) -> impl Iterator<Item = &(String, ScalarAttributeType)> {
+        std::iter::once(&self.definition.partition_key).chain(&self.definition.sort_key)
+    }
+
+    /// keyの項目名と型がテーブルと一致するか確認します
+    fn check_key(&self, item: Item) -> Result<Item, Error> {
+        let matches = item.len() == self.key_names().count()
+            && self.key_names().all(|(name, attribute_type)| {
+                item.get(name)
+                    .is_some_and(|value| scalar_type(value).as_ref() == Some(attribute_type))
+            });
+        if !matches {
+            return Err(validation(
+                "The provided key element does not match the schema",
+            ));
+        }
+        Ok(item)
+    }
+
+    /// itemにkeyが含まれているか確認します
+    fn check_item_key(&self, item: &Item) -> Result<(), Error> {
+        for (name, attribute_type) in self.key_names() {
+            match item.get(name) {
+                Some(value) if scalar_type(value).as_ref() == Some(attribute_type) => {}
+                Some(_) => {
+                    return Err(validation(format!(
+                        "One or more parameter values were invalid: Type mismatch for key {name}"
+                    )))
+                }
+                None => {
+                    return Err(validation(format!(
+                    "One or more parameter values were invalid: Missing the key {name} in the item"
+                )))
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn position(&self, key: &Item) -> Option<usize> {
+        self.items.iter().position(|item| {
+            key.iter()
+                .all(|(name, value)| item.get(name).is_some_and(|current| equals(current, value)))
+        })
+    }
+}
+
+impl MockDatabase {
+    fn with_table<T>(
+        &self,
+        table_name: &str,
+        f: impl FnOnce(&mut MockTable) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let mut tables = self.tables.lock().expect("poisoned lock");
+        let table = tables.get_mut(table_name).ok_or_else(resource_not_found)?;
+        f(table)
+    }
+
+    pub(crate) fn get_item(&self, table_name: &str, key: Item) -> Result<GetItemOutput, Error> {
+        self.with_table(table_name, |table| {
+            let key = table.check_key(key)?;
+            let item = table.position(&key).map(|i| table.items[i].clone());
+            Ok(GetItemOutput::builder().set_item(item).build())
+        })
+    }
+
+    pub(crate) fn put_item(
+        &self,
+        table_name: &str,
+        item: Item,
+        condition: Option<&Condition>,
+        return_values: Option<&ReturnValue>,
+    ) -> Result<PutItemOutput, Error> {
+        self.with_table(table_name, |table| {
+            table.check_item_key(&item)?;
+            let key = table
+                .key_names()
+                .map(|(name, _)| (name.clone(), item[name].clone()))
+                .collect();
+            let position = table.position(&key);
+            check_condition(condition, position.map(|i| &table.items[i]))?;
+            let old = match position {
+                Some(i) => Some(std::mem::replace(&mut table.items[i], item)),
+                None => {
+                    table.items.push(item);
+                    None
+                }
+            };
+            Ok(PutItemOutput::builder()
+                .set_attributes(old.filter(|_| return_values == Some(&ReturnValue::AllOld)))
+                .build())
+        })
+    }
+
+    pub(crate) fn delete_item(
+        &self,
+        table_name: &str,
+        key: Item,
+        condition: Option<&Condition>,
+        return_values: Option<&ReturnValue>,
+    ) -> Result<DeleteItemOutput, Error> {
+        self.with_table(table_name, |table| {
+            let key = table.check_key(key)?;
+            let position = table.position(&key);
+            check_condition(condition, position.map(|i| &table.items[i]))?;
+            let old = position.map(|i| table.items.remove(i));
+            Ok(DeleteItemOutput::builder()
+                .set_attributes(old.filter(|_| return_values == Some(&ReturnValue::AllOld)))
+                .build())
+        })
+    }
+
+    pub(crate) fn update_item(
+        &self,
+        table_name: &str,
+        key: Item,
+        update: &Update,
+    ) -> Result<UpdateItemOutput, Error> {
+        self.with_table(table_name, |table| {
+            let key = table.check_key(key)?;
+            let position = table.position(&key);
+            check_condition(update.condition.as_ref(), position.map(|i| &table.items[i]))?;
+            let mut item = match position {
+                Some(i) => table.items[i].clone(),
+                None => key.clone(),
+            };
+            for action in &update.actions {
+                if let Some(PathElement::Name(name)) = action_path(action).0.first() {
+                    if key.contains_key(name) {
+                        return Err(validation(format!(
+                            "Cannot update attribute {name}. This attribute is part of the key"
+                        )));
+                    }
+                }
+                apply_action(&mut item, action)?;
+            }
+            let old = position.map(|i| std::mem::replace(&mut table.items[i], item.clone()));
+            if position.is_none() {
+                table.items.push(item.clone());
+            }
+            let updated = |item: &Item| {
+                let paths = update
+                    .actions
+                    .iter()
+                    .map(action_path)
+                    .cloned()
+                    .collect::<Vec<_>>();
+                project(item, &paths)
+            };
+            let attributes = match update.return_values {
+                Some(ReturnValue::AllOld) => old,
+                Some(ReturnValue::AllNew) => Some(item),
+                Some(ReturnValue::UpdatedOld) => old.as_ref().map(updated),
+                Some(ReturnValue::UpdatedNew) => Some(updated(&item)),
+                _ => None,
+            };
+            Ok(UpdateItemOutput::builder()
+                .set_attributes(attributes.filter(|attributes| !attributes.is_empty()))
+                .build())
+        })
+    }
+
+    /// segmentの指定は無視します
+    pub(crate) fn scan(&self, table_name: &str, scan: &Scan) -> Result<Vec<Item>, Error> {
+        self.with_table(table_name, |table| {
+            Ok(table
+                .items
+                .iter()
+                .filter(|item| {
+                    scan.filter
+                        .as_ref()
+                        .is_none_or(|filter| evaluate(&filter.0, item))
+                })
+                .map(|item| project(item, &scan.projection))
+                .collect())
+        })
+    }
+
+    /// keyの順で`start`の次のitemから`limit`件を読み、filterに合うものを返します
+    pub(crate) fn scan_page(
+        &self,
+        table_name: &str,
+        scan: &Scan,
+        start: Option<Item>,
+        limit: usize,
+    ) -> Result<(Vec<Item>, Option<Item>), Error> {
+        self.with_table(table_name, |table| {
+            let order = table
+                .key_names()
+                .map(|(name, _)| name.as_str())
+                .collect::<Vec<_>>();
+            let mut items = table.items.clone();
+            items.sort_by(|a, b| compare_keys(&order, a, b));
+            let (items, last) = paginate(&order, items, start, limit, false);
+            Ok((
+                items
+                    .iter()
+                    .filter(|item| {
+                        scan.filter
+                            .as_ref()
+                            .is_none_or(|filter| evaluate(&filter.0, item))
+                    })
+                    .map(|item| project(item, &scan.projection))
+                    .collect(),
+                last,
+            ))
+        })
+    }
+
+    /// `start`の次のitemから`limit`件を読み、filterに合うものを返します
+    pub(crate) fn query_page(
+        &self,
+        table_name: &str,
+        query: &Query,
+        start: Option<Item>,
+        limit: usize,
+    ) -> Result<(Vec<Item>, Option<Item>), Error> {
+        self.with_table(table_name, |table| {
+            let (items, order) = query_items(table, query);
+            let descending = query.scan_index_forward == Some(false);
+            let (items, last) = paginate(&order, items, start, limit, descending);
+            Ok((filter_items(items, query.filter.as_ref()), last))
+        })
+    }
+
+    pub(crate) fn query(&self, table_name: &str, query: &Query) -> Result<Vec<Item>, Error> {
+        self.with_table(table_name, |table| {
+            let (items, _) = query_items(table, query);
+            Ok(filter_items(items, query.filter.as_ref()))
+        })
+    }
+
+    pub(crate) fn create_table(
+        &self,
+        definition: &TableDefinition,
+    ) -> Result<CreateTableOutput, Error> {
+        let table_name = definition.get_table_name();
+        let mut tables = self.tables.lock().expect("poisoned lock");
+        if tables.contains_key(table_name) {
+            return Err(from_aws_sdk_dynamodb_error(
+                aws_sdk_dynamodb::Error::ResourceInUseException(
+                    ResourceInUseException::builder()
+                        .message(format!("Table already exists: {table_name}"))
+                        .build(),
+                ),
+            ));
+        }
+        let description = definition.description(TableStatus::Active)?;
+        tables.insert(
+            table_name.to_owned(),
+            MockTable {
+                definition: definition.clone(),
+                ttl_attribute: None,
+                items: vec![],
+            },
+        );
+        Ok(CreateTableOutput::builder()
+            .table_description(description)
+            .build())
+    }
+
+    /// テーブルは作成直後からACTIVEです
+    pub(crate) fn describe_table(&self, table_name: &str) -> Result<TableDescription, Error> {
+        self.with_table(table_name, |table| {
+            table.definition.description(TableStatus::Active)
+        })
+    }
+
+    pub(crate) fn delete_table(&self, table_name: &str) -> Result<DeleteTableOutput, Error> {
+        let mut tables = self.tables.lock().expect("poisoned lock");
+        let table = tables.remove(table_name).ok_or_else(resource_not_found)?;
+        Ok(DeleteTableOutput::builder()
+            .table_description(table.definition.description(TableStatus::Deleting)?)
+            .build())
+    }
+
+    /// 期限切れのitemは削除しません
+    pub(crate) fn update_time_to_live(
+        &self,
+        table_name: &str,
+        specification: TimeToLiveSpecification,
+    ) -> Result<UpdateTimeToLiveOutput, Error> {
+        self.with_table(table_name, |table| {
+            table.ttl_attribute = specification
+                .enabled
+                .then(|| specification.attribute_name.clone());
+            Ok(UpdateTimeToLiveOutput::builder()
+                .time_to_live_specification(specification)
+                .build())
+        })
+    }
+
+    pub(crate) fn describe_time_to_live(
+        &self,
+        table_name: &str,
+    ) -> Result<TimeToLiveDescription, Error> {
+        self.with_table(table_name, |table| {
+            Ok(match &table.ttl_attribute {
+                Some(attribute) => TimeToLiveDescription::builder()
+                    .time_to_live_status(TimeToLiveStatus::Enabled)
+                    .attribute_name(attribute),
+                None => {
+                    TimeToLiveDescription::builder().time_to_live_status(TimeToLiveStatus::Disabled)
+                }
+            }
+            .build())
+        })
+    }
+
+    /// キャパシティは持たないので、テーブルがあるかだけ確認します
+    pub(crate) fn update_table(&self, table_name: &str) -> Result<UpdateTableOutput, Error> {
+        self.with_table(table_name, |table| {
+            Ok(UpdateTableOutput::builder()
+                .table_description(table.definition.description(TableStatus::Active)?)
+                .build())
+        })
+    }
+}
+
+/// keyの条件に合うitemを、読む順番に並べて返します。並べるのに使ったkeyの項目名も返します。
+///
+/// indexを指定した場合も、テーブルのitemからpartition keyとsort keyの項目名で絞り込みます。
+/// sort keyが同じitemは、テーブルのkeyの順に並べます。
+fn query_items<'a>(table: &'a MockTable, query: &'a Query) -> (Vec<Item>, Vec<&'a str>) {
+    let (partition_name, partition_value) = &query.partition;
+    let sort_name = match &query.sort {
+        Some(sort) => Some(sort.name()),
+        None if query.index_name.is_none() => table
+            .definition
+            .sort_key
+            .as_ref()
+            .map(|(name, _)| name.as_str()),
+        None => None,
+    };
+    let order = [partition_name.as_str()]
+        .into_iter()
+        .chain(sort_name)
+        .chain(table.key_names().map(|(name, _)| name.as_str()))
+        .collect::<Vec<_>>();
+    let mut items = table
+        .items
+        .iter()
+        .filter(|item| {
+            item.get(partition_name)
+                .is_some_and(|value| equals(value, partition_value))
+        })
+        .filter(|item| {
+            query
+                .sort
+                .as_ref()
+                .is_none_or(|sort| evaluate_sort_key(sort, item))
+        })
+        .cloned()
+        .collect::<Vec<_>>();
+    items.sort_by(|a, b| compare_keys(&order, a, b));
+    if query.scan_index_forward == Some(false) {
+        items.reverse();
+    }
+    (items, order)
+}
+
+fn filter_items(items: Vec<Item>, filter: Option<&Condition>) -> Vec<Item> {
+    items
+        .into_iter()
+        .filter(|item| filter.is_none_or(|filter| evaluate(&filter.0, item)))
+        .collect()
+}
+
+/// `order`の項目を順に比べます。無い項目は等しいとみなします。
+fn compare_keys(order: &[&str], a: &Item, b: &Item) -> Ordering {
+    order
+        .iter()
+        .map(|name| match (a.get(*name), b.get(*name)) {
+            (Some(a), Some(b)) => compare(a, b).unwrap_or(Ordering::Equal),
+            _ => Ordering::Equal,
+        })
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or(Ordering::Equal)
+}
+
+/// `order`の順に並んだ`items`を、`start`より後ろのitemから`limit`件に絞ります。
+/// `start`のitemが消えていても、その次に来るはずのitemから返します。
+/// 続きがある場合は、最後のitemのkeyの項目も返します。
+fn paginate(
+    order: &[&str],
+    items: Vec<Item>,
+    start: Option<Item>,
+    limit: usize,
+    descending: bool,
+) -> (Vec<Item>, Option<Item>) {
+    let mut key_names = order.to_vec();
+    key_names.sort();
+    key_names.dedup();
+    let key_of = |item: &Item| {
+        key_names
+            .iter()
+            .filter_map(|name| Some((name.to_string(), item.get(*name)?.clone())))
+            .collect::<Item>()
+    };
+    let skip = match &start {
+        Some(start) => items
+            .iter()
+            .position(|item| {
+                let ordering = compare_keys(order, item, start);
+                if descending {
+                    ordering.is_lt()
+                } else {
+                    ordering.is_gt()
+                }
+            })
+            .unwrap_or(items.len()),
+        None => 0,
+    };
+    let remaining = items.len().saturating_sub(skip);
+    let items = items.into_iter().skip(skip).take(limit).collect::<Vec<_>>();
+    let last = (remaining > limit)
+        .then(|| items.last().map(key_of))
+        .flatten();
+    (items, last)
+}
+
+/// mockの結果をstreamにします
+pub(crate) fn into_stream(
+    result: Result<Vec<Item>, Error>,
+) -> impl Stream<Item = Result<Item, Error>> {
+    stream::iter(match result {
+        Ok(items) => items.into_iter().map(Ok).collect(),
+        Err(error) => vec![Err(error)],
+    })
+}
+
+fn resource_not_found() -> Error {
+    from_aws_sdk_dynamodb_error(aws_sdk_dynamodb::Error::ResourceNotFoundException(
+        ResourceNotFoundException::builder()
+            .message("Requested resource not found")
+            .build(),
+    ))
+}
+
+fn validation(message: impl Into<String>) -> Error {
+    from_aws_sdk_dynamodb_error(UpdateItemError::generic(
+        ErrorMetadata::builder()
+            .code("ValidationException")
+            .message(message)
+            .build(),
+    ))
+}
+
+fn check_condition(condition: Option<&Condition>, item: Option<&Item>) -> Result<(), Error> {
+    let empty = Item::new();
+    match condition {
+        Some(condition) if !evaluate(&condition.0, item.unwrap_or(&empty)) => Err(
+            from_aws_sdk_dynamodb_error(aws_sdk_dynamodb::Error::ConditionalCheckFailedException(
+                ConditionalCheckFailedException::builder()
+                    .message("The conditional request failed")
+                    .set_item(item.cloned())
+                    .build(),
+            )),
+        ),
+        _ => Ok(()),
+    }
+}
+
+fn scalar_type(value: &AttributeValue) -> Option<ScalarAttributeType> {
+    match value {
+        AttributeValue::S(_) => Some(ScalarAttributeType::S),
+        AttributeValue::N(_) => Some(ScalarAttributeType::N),
+        AttributeValue::B(_) => Some(ScalarAttributeType::B),
+        _ => None,
+    }
+}
+
+fn evaluate(condition: &ConditionExpr, item: &Item) -> bool {
+    match condition {
+        ConditionExpr::AttributeExists(path) => path.resolve(item).is_some(),
+        ConditionExpr::AttributeNotExists(path) => path.resolve(item).is_none(),
+        ConditionExpr::Compare(path, comparator, value) => {
+            path.resolve(item).is_some_and(|current| match comparator {
+                Comparator::Eq => equals(current, value),
+                Comparator::Ne => !equals(current, value),
+                Comparator::Lt => compare(current, value) == Some(Ordering::Less),
+                Comparator::Le => compare(current, value).is_some_and(Ordering::is_le),
+                Comparator::Gt => compare(current, value) == Some(Ordering::Greater),
+                Comparator::Ge => compare(current, value).is_some_and(Ordering::is_ge),
+            })
+        }
+        ConditionExpr::Between(path, low, high) => path
+            .resolve(item)
+            .is_some_and(|current| between(current, low, high)),
+        ConditionExpr::BeginsWith(path, prefix) => path
+            .resolve(item)
+            .is_some_and(|current| begins_with(current, prefix)),
+        ConditionExpr::Contains(path, value) => path
+            .resolve(item)
+            .is_some_and(|current| contains(current, value)),
+        ConditionExpr::And(a, b) => evaluate(a, item) && evaluate(b, item),
+        ConditionExpr::Or(a, b) => evaluate(a, item) || evaluate(b, item),
+        ConditionExpr::Not(a) => !evaluate(a, item),
+    }
+}
+
+fn evaluate_sort_key(condition: &SortKeyCondition, item: &Item) -> bool {
+    let Some(current) = item.get(condition.name()) else {
+        return false;
+    };
+    match condition {
+        SortKeyCondition::Eq(_, value) => equals(current, value),
+        SortKeyCondition::Lt(_, value) => compare(current, value) == Some(Ordering::Less),
+        SortKeyCondition::Le(_, value) => compare(current, value).is_some_and(Ordering::is_le),
+        SortKeyCondition::Gt(_, value) => compare(current, value) == Some(Ordering::Greater),
+        SortKeyCondition::Ge(_, value) => compare(current, value).is_some_and(Ordering::is_ge),
+        SortKeyCondition::Between(_, low, high) => between(current, low, high),
+        SortKeyCondition::BeginsWith(_, prefix) => begins_with(current, prefix),
+    }
+}
+
+/// 同じ型のS、N、Bだけ比較できます
+fn compare(a: &AttributeValue, b: &AttributeValue) -> Option<Ordering> {
+    match (a, b) {
+        (AttributeValue::S(a), AttributeValue::S(b)) => Some(a.cmp(b)),
+        (AttributeValue::B(a), AttributeValue::B(b)) => Some(a.as_ref().cmp(b.as_ref())),
+        (AttributeValue::N(a), AttributeValue::N(b)) => {
+            Some(Decimal::parse(a)?.cmp(&Decimal::parse(b)?))
+        }
+        _ => None,
+    }
+}
+
+/// 値が等しいかどうか
+///
+/// 数値は表記によらず値で比べ(`10`と`1e1`は等しい)、setは順番を無視して比べます。
+fn equals(a: &AttributeValue, b: &AttributeValue) -> bool {
+    match (a, b) {
+        (AttributeValue::N(_), AttributeValue::N(_)) => compare(a, b) == Some(Ordering::Equal),
+        (AttributeValue::Ns(a), AttributeValue::Ns(b)) => {
+            let numbers = |set: &[String]| {
+                let mut numbers = set.iter().map(|n| Decimal::parse(n)).collect::<Vec<_>>();
+                numbers.sort();
+                numbers
+            };
+            numbers(a) == numbers(b)
+        }
+        (AttributeValue::Ss(a), AttributeValue::Ss(b)) => same_set(a, b),
+        (AttributeValue::Bs(a), AttributeValue::Bs(b)) => same_set(a, b),
+        (AttributeValue::L(a), AttributeValue::L(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| equals(a, b))
+        }
+        (AttributeValue::M(a), AttributeValue::M(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .all(|(name, a)| b.get(name).is_some_and(|b| equals(a, b)))
+        }
+        _ => a == b,
+    }
+}
+
+fn same_set<T: PartialEq>(a: &[T], b: &[T]) -> bool {
+    a.len() == b.len() && a.iter().all(|value| b.contains(value))
+}
+
+/// DynamoDBの数値
+///
+/// `0.digits × 10^exponent`の形で持ち、`digits`の先頭と末尾に0はありません。0は`digits`が空です。
+#[derive(Debug, PartialEq, Eq)]
+struct Decimal {
+    negative: bool,
+    digits: Vec<u8>,
+    exponent: i64,
+}
+
+impl Decimal {
+    fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        let (negative, value) = match value.strip_prefix('-') {
+            Some(value) => (true, value),
+            None => (false, value.strip_prefix('+').unwrap_or(value)),
+        };
+        let (mantissa, exponent) = match value.split_once(['e', 'E']) {
+            Some((mantissa, exponent)) => (mantissa, exponent.parse::<i64>().ok()?),
+            None => (value, 0),
+        };
+        let (integer, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+        if integer.is_empty() && fraction.is_empty()
+            || !integer
+                .bytes()
+                .chain(fraction.bytes())
+                .all(|b| b.is_ascii_digit())
+        {
+            return None;
+        }
+        let all = integer
+            .bytes()
+            .chain(fraction.bytes())
+            .map(|b| b - b'0')
+            .collect::<Vec<_>>();
+        let leading = all.iter().take_while(|&&digit| digit == 0).count();
+        let mut digits = all[leading..].to_vec();
+        while digits.last() == Some(&0) {
+            digits.pop();
+        }
+        if digits.is_empty() {
+            return Some(Self {
+                negative: false,
+                digits,
+                exponent: 0,
+            });
+        }
+        Some(Self {
+            negative,
+            digits,
+            exponent: exponent + integer.len() as i64 - leading as i64,
+        })
+    }
+
+    /// 一番下の桁の位。`digits × 10^scale`になります
+    fn scale(&self) -> i64 {
+        self.exponent - self.digits.len() as i64
+    }
+
+    /// 桁を揃えて足します。揃えるのに必要な桁数が`max_width`を超える場合は`None`です。
+    fn checked_add(&self, other: &Self, max_width: usize) -> Option<Self> {
+        if self.digits.is_empty() || other.digits.is_empty() {
+            let value = if self.digits.is_empty() { other } else { self };
+            return Some(Self {
+                negative: value.negative,
+                digits: value.digits.clone(),
+                exponent: value.exponent,
+            });
+        }
+        let scale = self.scale().min(other.scale());
+        let width = usize::try_from(self.exponent.max(other.exponent) - scale).ok()?;
+        if width > max_width {
+            return None;
+        }
+        // 下の桁から並べます
+        let aligned = |value: &Self| {
+            let mut digits = vec![0; (value.scale() - scale) as usize];
+            digits.extend(value.digits.iter().rev());
+            digits.resize(width + 1, 0);
+            digits
+        };
+        let (a, b) = (aligned(self), aligned(other));
+        let (negative, digits) = if self.negative == other.negative {
+            let mut carry = 0;
+            let digits = a
+                .iter()
+                .zip(&b)
+                .map(|(a, b)| {
+                    let sum = a + b + carry;
+                    carry = sum / 10;
+                    sum % 10
+                })
+                .collect::<Vec<_>>();
+            (self.negative, digits)
+        } else {
+            // 絶対値の大きい方から小さい方を引きます
+            let (large, small, negative) = match self.cmp_magnitude(other) {
+                Ordering::Less => (&b, &a, other.negative),
+                _ => (&a, &b, self.negative),
+            };
+            let mut borrow = 0;
+            let digits = large
+                .iter()
+                .zip(small)
+                .map(|(large, small)| {
+                    let (diff, next) = match large.checked_sub(small + borrow) {
+                        Some(diff) => (diff, 0),
+                        None => (large + 10 - small - borrow, 1),
+                    };
+                    borrow = next;
+                    diff
+                })
+                .collect::<Vec<_>>();
+            (negative, digits)
+        };
+        let mut digits = digits.into_iter().rev().collect::<Vec<_>>();
+        let leading = digits.iter().take_while(|&&digit| digit == 0).count();
+        let exponent = scale + (digits.len() - leading) as i64;
+        digits.drain(..leading);
+        while digits.last() == Some(&0) {
+            digits.pop();
+        }
+        if digits.is_empty() {
+            return Some(Self {
+                negative: false,
+                digits,
+                exponent: 0,
+            });
+        }
+        Some(Self {
+            negative,
+            digits,
+            exponent,
+        })
+    }
+
+    fn cmp_magnitude(&self, other: &Self) -> Ordering {
+        match (self.digits.is_empty(), other.digits.is_empty()) {
+            (true, true) => Ordering::Equal,
+            (true, false) => Ordering::Less,
+            (false, true) => Ordering::Greater,
+            (false, false) => self
+                .exponent
+                .cmp(&other.exponent)
+                .then_with(|| self.digits.cmp(&other.digits)),
+        }
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.digits.is_empty() {
+            return write!(f, "0");
+        }
+        if self.negative {
+            write!(f, "-")?;
+        }
+        let digits = self
+            .digits
+            .iter()
+            .map(|digit| char::from(b'0' + digit))
+            .collect::<String>();
+        let len = digits.len() as i64;
+        if self.exponent <= 0 {
+            write!(f, "0.{}{digits}", "0".repeat(-self.exponent as usize))
+        } else if self.exponent >= len {
+            write!(f, "{digits}{}", "0".repeat((self.exponent - len) as usize))
+        } else {
+            let (integer, fraction) = digits.split_at(self.exponent as usize);
+            write!(f, "{integer}.{fraction}")
+        }
+    }
+}
+
+impl Ord for Decimal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, false) => self.cmp_magnitude(other),
+            (true, true) => other.cmp_magnitude(self),
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+        }
+    }
+}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+fn between(value: &AttributeValue, low: &AttributeValue, high: &AttributeValue) -> bool {
+    compare(value, low).is_some_and(Ordering::is_ge)
+        && compare(value, high).is_some_and(Ordering::is_le)
+}
+
+fn begins_with(value: &AttributeValue, prefix: &AttributeValue) -> bool {
+    match (value, prefix) {
+        (AttributeValue::S(value), AttributeValue::S(prefix)) => value.starts_with(prefix.as_str()),
+        (AttributeValue::B(value), AttributeValue::B(prefix)) => {
+            value.as_ref().starts_with(prefix.as_ref())
+        }
+        _ => false,
+    }
+}
+
+fn contains(value: &AttributeValue, operand: &AttributeValue) -> bool {
+    match (value, operand) {
+        (AttributeValue::S(value), AttributeValue::S(operand)) => value.contains(operand.as_str()),
+        (AttributeValue::Ss(set), AttributeValue::S(operand)) => set.contains(operand),
+        (AttributeValue::Ns(set), operand @ AttributeValue::N(_)) => set
+            .iter()
+            .any(|n| equals(&AttributeValue::N(n.clone()), operand)),
+        (AttributeValue::Bs(set), AttributeValue::B(operand)) => set.contains(operand),
+        (AttributeValue::L(list), operand) => list.iter().any(|value| equals(value, operand)),
+        _ => false,
+    }
+}
+
+fn resolve_mut<'a>(item: &'a mut Item, elements: &[PathElement]) -> Option<&'a mut AttributeValue> {
+    let (PathElement::Name(first), rest) = elements.split_first()? else {
+        return None;
+    };
+    rest.iter()
+        .try_fold(item.get_mut(first)?, |current, element| {
+            match (element, current) {
+                (PathElement::Name(name), AttributeValue::M(map)) => map.get_mut(name),
+                (PathElement::Index(i), AttributeValue::L(list)) => list.get_mut(*i),
+                _ => None,
+            }
+        })
+}
+
+fn invalid_path() -> Error {
+    validation("The document path provided in the update expression is invalid for update")
+}
+
+fn set_path(item: &mut Item, path: &Path, value: AttributeValue) -> Result<(), Error> {
+    let Some((last, parents)) = path.0.split_last() else {
+        return Err(invalid_path());
+    };
+    if parents.is_empty() {
+        let PathElement::Name(name) = last else {
+            return Err(invalid_path());
+        };
+        item.insert(name.clone(), value);
+        return Ok(());
+    }
+    match (resolve_mut(item, parents), last) {
+        (Some(AttributeValue::M(map)), PathElement::Name(name)) => {
+            map.insert(name.clone(), value);
+        }
+        (Some(AttributeValue::L(list)), PathElement::Index(i)) => match list.get_mut(*i) {
+            Some(current) => *current = value,
+            None => list.push(value),
+        },
+        _ => return Err(invalid_path()),
+    }
+    Ok(())
+}
+
+fn remove_path(item: &mut Item, path: &Path) {
+    let Some((last, parents)) = path.0.split_last() else {
+        return;
+    };
+    if parents.is_empty() {
+        if let PathElement::Name(name) = last {
+            item.remove(name);
+        }
+        return;
+    }
+    match (resolve_mut(item, parents), last) {
+        (Some(AttributeValue::M(map)), PathElement::Name(name)) => {
+            map.remove(name);
+        }
+        (Some(AttributeValue::L(list)), PathElement::Index(i)) if *i < list.len() => {
+            list.remove(*i);
+        }
+        _ => {}
+    }
+}
+
+fn action_path(action: &UpdateAction) -> &Path {
+    match action {
+        UpdateAction::Set(path, _)
+        | UpdateAction::Remove(path)
+        | UpdateAction::Add(path, _)
+        | UpdateAction::Delete(path, _) => path,
+    }
+}
+
+fn incorrect_type() -> Error {
+    validation("An operand in the update expression has an incorrect data type")
+}
+
+fn apply_action(item: &mut Item, action: &UpdateAction) -> Result<(), Error> {
+    match action {
+        UpdateAction::Set(path, set_value) => {
+            let current = path.resolve(item);
+            let value = match set_value {
+                SetValue::Value(value) => value.clone(),
+                SetValue::IfNotExists(value) => current.unwrap_or(value).clone(),
+                SetValue::ListAppend(values) | SetValue::ListPrepend(values) => {
+                    let current = current.cloned().unwrap_or(AttributeValue::L(vec![]));
+                    let (AttributeValue::L(current), AttributeValue::L(values)) = (current, values)
+                    else {
+                        return Err(incorrect_type());
+                    };
+                    AttributeValue::L(if matches!(set_value, SetValue::ListAppend(_)) {
+                        current.into_iter().chain(values.iter().cloned()).collect()
+                    } else {
+                        values.iter().cloned().chain(current).collect()
+                    })
+                }
+            };
+            set_path(item, path, value)
+        }
+        UpdateAction::Remove(path) => {
+            remove_path(item, path);
+            Ok(())
+        }
+        UpdateAction::Add(path, value) => {
+            let next = match (path.resolve(item), value) {
+                (None, AttributeValue::N(_) | AttributeValue::Ss(_))
+                | (None, AttributeValue::Ns(_) | AttributeValue::Bs(_)) => value.clone(),
+                (Some(AttributeValue::N(a)), AttributeValue::N(b)) => {
+                    AttributeValue::N(add_numbers(a, b)?)
+                }
+                (Some(AttributeValue::Ss(a)), AttributeValue::Ss(b)) => {
+                    AttributeValue::Ss(union(a, b))
+                }
+                (Some(AttributeValue::Ns(a)), AttributeValue::Ns(b)) => {
+                    AttributeValue::Ns(union_by(a, b, |a, b| same_number(a, b)))
+                }
+                (Some(AttributeValue::Bs(a)), AttributeValue::Bs(b)) => {
+                    AttributeValue::Bs(union(a, b))
+                }
+                _ => return Err(incorrect_type()),
+            };
+            set_path(item, path, next)
+        }
+        UpdateAction::Delete(path, value) => {
+            let next = match (path.resolve(item), value) {
+                (None, AttributeValue::Ss(_) | AttributeValue::Ns(_) | AttributeValue::Bs(_)) => {
+                    return Ok(())
+                }
+                (Some(AttributeValue::Ss(a)), AttributeValue::Ss(b)) => {
+                    AttributeValue::Ss(difference(a, b))
+                }
+                (Some(AttributeValue::Ns(a)), AttributeValue::Ns(b)) => {
+                    AttributeValue::Ns(difference_by(a, b, |a, b| same_number(a, b)))
+                }
+                (Some(AttributeValue::Bs(a)), AttributeValue::Bs(b)) => {
+                    AttributeValue::Bs(difference(a, b))
+                }
+                _ => return Err(incorrect_type()),
+            };
+            let is_empty = match &next {
+                AttributeValue::Ss(set) | AttributeValue::Ns(set) => set.is_empty(),
+                AttributeValue::Bs(set) => set.is_empty(),
+                _ => false,
+            };
+            if is_empty {
+                remove_path(item, path);
+                Ok(())
+            } else {
+                set_path(item, path, next)
+            }
+        }
+    }
+}
+
+/// DynamoDBの数値の有効桁数
+const NUMBER_PRECISION: usize = 38;
+
+/// DynamoDBの数値の範囲。`0.digits × 10^exponent`の`exponent`で表します
+const NUMBER_EXPONENTS: std::ops::RangeInclusive<i64> = -129..=126;
+
+/// 数値を桁を揃えて足します。有効桁数や範囲を超える場合はValidationExceptionと同じエラーです。
+fn add_numbers(a: &str, b: &str) -> Result<String, Error> {
+    let (Some(a), Some(b)) = (Decimal::parse(a), Decimal::parse(b)) else {
+        return Err(incorrect_type());
+    };
+    let width = (NUMBER_EXPONENTS.end() - NUMBER_EXPONENTS.start()) as usize + NUMBER_PRECISION;
+    let sum = a
+        .checked_add(&b, width)
+        .filter(|sum| sum.digits.is_empty() || NUMBER_EXPONENTS.contains(&sum.exponent))
+        .ok_or_else(|| {
+            validation(
+                "Number overflow. Attempting to store a number with magnitude larger than supported range",
+            )
+        })?;
+    if sum.digits.len() > NUMBER_PRECISION {
+        return Err(validation(
+            "Attempting to store a number with more than 38 significant digits",
+        ));
+    }
+    Ok(sum.to_string())
+}
+
+fn union<T: Clone + PartialEq>(a: &[T], b: &[T]) -> Vec<T> {
+    union_by(a, b, T::eq)
+}
+
+fn union_by<T: Clone>(a: &[T], b: &[T], eq: impl Fn(&T, &T) -> bool) -> Vec<T> {
+    let mut set = a.to_vec();
+    set.extend(
+        b.iter()
+            .filter(|value| !a.iter().any(|current| eq(current, value)))
+            .cloned(),
+    );
+    set
+}
+
+fn difference<T: Clone + PartialEq>(a: &[T], b: &[T]) -> Vec<T> {
+    difference_by(a, b, T::eq)
+}
+
+fn difference_by<T: Clone>(a: &[T], b: &[T], eq: impl Fn(&T, &T) -> bool) -> Vec<T> {
+    a.iter()
+        .filter(|value| !b.iter().any(|removed| eq(value, removed)))
+        .cloned()
+        .collect()
+}
+
+fn same_number(a: &str, b: &str) -> bool {
+    Decimal::parse(a).is_some_and(|a| Decimal::parse(b) == Some(a))
+}
+
+/// ProjectionExpressionで指定された項目だけを残します
+fn project(item: &Item, projection: &[Path]) -> Item {
+    if projection.is_empty() {
+        return item.clone();
+    }
+    let mut projected = Item::new();
+    for path in projection {
+        let Some(value) = path.resolve(item) else {
+            continue;
+        };
+        let Some((PathElement::Name(first), rest)) = path.0.split_first() else {
+            continue;
+        };
+        let slot = projected
+            .entry(first.clone())
+            .or_insert_with(|| empty_container(rest.first()));
+        insert_projected(slot, rest, value.clone());
+    }
+    projected
+}
+
+fn empty_container(next: Option<&PathElement>) -> AttributeValue {
+    match next {
+        Some(PathElement::Index(_)) => AttributeValue::L(vec![]),
+        _ => AttributeValue::M(HashMap::new()),
+    }
+}
+
+fn insert_projected(slot: &mut AttributeValue, path: &[PathElement], value: AttributeValue) {
+    let Some((element, rest)) = path.split_first() else {
+        *slot = value;
+        return;
+    };
+    match (element, slot) {
+        (PathElement::Name(name), AttributeValue::M(map)) => {
+            let next = map
+                .entry(name.clone())
+                .or_insert_with(|| empty_container(rest.first()));
+            insert_projected(next, rest, value);
+        }
+        (PathElement::Index(_), AttributeValue::L(list)) => {
+            let mut next = empty_container(rest.first());
+            insert_projected(&mut next, rest, value);
+            list.push(next);
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{IntoValue, ItemExt, PageRequest, TableType};
+    use futures_util::TryStreamExt;
+
+    fn n(value: &str) -> AttributeValue {
+        AttributeValue::N(value.to_owned())
+    }
+
+    fn item() -> Item {
+        HashMap::from([
+            ("id".to_owned(), "u1".into_value()),
+            ("age".to_owned(), n("30")),
+            ("name".to_owned(), "alice".into_value()),
+            (
+                "profile".to_owned(),
+                AttributeValue::M(HashMap::from([(
+                    "tags".to_owned(),
+                    AttributeValue::L(vec!["a".into_value(), "b".into_value()]),
+                )])),
+            ),
+            (
+                "colors".to_owned(),
+                AttributeValue::Ss(vec!["red".into(), "blue".into()]),
+            ),
+        ])
+    }
+
+    fn check(condition: Condition) -> bool {
+        evaluate(&condition.0, &item())
+    }
+
+    fn apply(item: &mut Item, update: Update) -> Result<(), Error> {
+        update
+            .actions
+            .iter()
+            .try_for_each(|action| apply_action(item, action))
+    }
+
+    #[test]
+    fn evaluate_comparisons() {
+        assert!(check(Condition::eq("age", 30)));
+        assert!(check(Condition::eq("age", AttributeValue::N("3e1".into()))));
+        assert!(check(Condition::ne("age", 31)));
+        assert!(check(Condition::lt("age", 31)));
+        assert!(check(Condition::le("age", 30)));
+        assert!(check(Condition::gt("age", 29)));
+        assert!(check(Condition::ge("age", 30)));
+        assert!(!check(Condition::gt("age", 30)));
+        assert!(check(Condition::between("age", 20, 30)));
+        assert!(!check(Condition::between("age", 31, 40)));
+        // 型が違う値とは比較できません
+        assert!(!check(Condition::lt("age", "31")));
+        // 無い項目との比較は常に偽です
+        assert!(!check(Condition::ne("missing", 1)));
+    }
+
+    #[test]
+    fn evaluate_functions_and_logic() {
+        assert!(check(Condition::attribute_exists("profile.tags[1]")));
+        assert!(check(Condition::attribute_not_exists("profile.tags[2]")));
+        assert!(check(Condition::begins_with("name", "al")));
+        assert!(!check(Condition::begins_with("name", "bo")));
+        assert!(check(Condition::contains("name", "lic")));
+        assert!(check(Condition::contains("colors", "red")));
+        assert!(check(Condition::contains("profile.tags", "b")));
+        assert!(!check(Condition::contains("profile.tags", "c")));
+        assert!(check(Condition::eq("profile.tags[0]", "a")));
+        assert!(check(
+            Condition::eq("name", "bob").or(Condition::eq("age", 30))
+        ));
+        assert!(!check(
+            Condition::eq("name", "bob").and(Condition::eq("age", 30))
+        ));
+        assert!(check(!Condition::eq("name", "bob")));
+    }
+
+    #[test]
+    fn apply_nested_paths() {
+        let mut item = item();
+        apply(
+            &mut item,
+            Update::new()
+                .set("profile.bio", "hello")
+                .set("profile.tags[0]", "z")
+                .list_append("profile.tags", vec!["c"])
+                .remove("name"),
+        )
+        .unwrap();
+        assert_eq!(
+            Path::from("profile.bio").resolve(&item),
+            Some(&"hello".into_value())
+        );
+        assert_eq!(
+            Path::from("profile.tags").resolve(&item),
+            Some(&vec!["z", "b", "c"].into_value())
+        );
+        assert!(!item.contains_key("name"));
+
+        apply(&mut item, Update::new().remove("profile.tags[1]")).unwrap();
+        assert_eq!(
+            Path::from("profile.tags").resolve(&item),
+            Some(&vec!["z", "c"].into_value())
+        );
+        // 親が無いpathには書き込めません
+        assert!(matches!(
+            apply(&mut item, Update::new().set("missing.child", 1)),
+            Err(Error::Validation(_))
+        ));
+    }
+
+    #[test]
+    fn apply_set_if_not_exists() {
+        let mut item = item();
+        apply(
+            &mut item,
+            Update::new()
+                .set_if_not_exists("age", 1)
+                .set_if_not_exists("score", 1),
+        )
+        .unwrap();
+        assert_eq!(item["age"], n("30"));
+        assert_eq!(item["score"], n("1"));
+    }
+
+    #[test]
+    fn apply_add_and_delete_on_sets() {
+        let mut item = item();
+        apply(
+            &mut item,
+            Update::new()
+                .add(
+                    "colors",
+                    AttributeValue::Ss(vec!["green".into(), "red".into()]),
+                )
+                .add("numbers", AttributeValue::Ns(vec!["1".into()]))
+                .add("age", 5),
+        )
+        .unwrap();
+        assert!(equals(
+            &item["colors"],
+            &AttributeValue::Ss(vec!["red".into(), "blue".into(), "green".into()])
+        ));
+        assert_eq!(item["numbers"], AttributeValue::Ns(vec!["1".into()]));
+        assert_eq!(item["age"], n("35"));
+
+        apply(
+            &mut item,
+            Update::new()
+                .add(
+                    "numbers",
+                    AttributeValue::Ns(vec!["1.0".into(), "2".into()]),
+                )
+                .delete("colors", AttributeValue::Ss(vec!["red".into()])),
+        )
+        .unwrap();
+        assert_eq!(
+            item["numbers"],
+            AttributeValue::Ns(vec!["1".into(), "2".into()])
+        );
+        assert!(equals(
+            &item["colors"],
+            &AttributeValue::Ss(vec!["blue".into(), "green".into()])
+        ));
+
+        // 空になったsetは項目ごと消えます
+        apply(
+            &mut item,
+            Update::new().delete(
+                "colors",
+                AttributeValue::Ss(vec!["blue".into(), "green".into()]),
+            ),
+        )
+        .unwrap();
+        assert!(!item.contains_key("colors"));
+    }
+
+    #[test]
+    fn apply_rejects_incorrect_types() {
+        let mut item = item();
+        assert!(matches!(
+            apply(&mut item, Update::new().add("name", 1)),
+            Err(Error::Validation(_))
+        ));
+        assert!(matches!(
+            apply(
+                &mut item,
+                Update::new().delete("age", AttributeValue::Ss(vec!["a".into()]))
+            ),
+            Err(Error::Validation(_))
+        ));
+        assert!(matches!(
+            apply(&mut item, Update::new().list_append("name", vec!["a"])),
+            Err(Error::Validation(_))
+        ));
+    }
+
+    #[test]
+    fn unsupported_operations_fail_explicitly() {
+        tokio_test::block_on(async {
+            let client = Client::mock();
+            let error = client
+                .transact_get_raw(crate::TransactGet::new().get("users", ("id", "u1")))
+                .await
+                .unwrap_err();
+            assert!(matches!(
+                error,
+                Error::UnsupportedByMock("TransactGetItems")
+            ));
+
+            let error = client.raw_client().list_tables().send().await.unwrap_err();
+            assert!(format!("{:?}", error).contains("not supported by the mock client"));
+        });
+    }
+
+    fn ids(page: &crate::Page<Item>) -> Vec<String> {
+        page.items
+            .iter()
+            .map(|item| item.get_as::<String>("id").unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn page_resumes_after_deleted_cursor_item() {
+        tokio_test::block_on(async {
+            let client = Client::mock();
+            client
+                .create_table("messages", "room", Some("id"), TableType::OnDemand)
+                .await
+                .unwrap();
+            for id in ["d", "b", "a", "c"] {
+                client
+                    .set_value("messages", ("room", "r1", "id", id), "body", id)
+                    .await
+                    .unwrap();
+            }
+            let request = PageRequest::new(2);
+            for (query, first_ids, rest) in [
+                (Query::new("room", "r1"), ["a", "b"], ["c", "d"]),
+                (
+                    Query::new("room", "r1").scan_index_forward(false),
+                    ["d", "c"],
+                    ["b", "a"],
+                ),
+            ] {
+                let first = client
+                    .query_page_raw("messages", query.clone(), request.clone())
+                    .await
+                    .unwrap();
+                assert_eq!(ids(&first), first_ids);
+                // 2件目を消しても、続きから読めます
+                client
+                    .delete_item("messages", ("room", "r1", "id", first_ids[1]))
+                    .await
+                    .unwrap();
+                let second = client
+                    .query_page_raw("messages", query, request.clone().cursor(first.next_cursor))
+                    .await
+                    .unwrap();
+                assert_eq!(ids(&second), rest);
+                client
+                    .set_value("messages", ("room", "r1", "id", first_ids[1]), "body", "")
+                    .await
+                    .unwrap();
+            }
+
+            let first = client
+                .scan_page_raw("messages", Scan::new(), request.clone())
+                .await
+                .unwrap();
+            assert_eq!(ids(&first), ["a", "b"]);
+            client
+                .delete_item("messages", ("room", "r1", "id", "b"))
+                .await
+                .unwrap();
+            let second = client
+                .scan_page_raw("messages", Scan::new(), request.cursor(first.next_cursor))
+                .await
+                .unwrap();
+            assert_eq!(ids(&second), ["c", "d"]);
+        });
+    }
+
+    #[test]
+    fn query_matches_numeric_partition_by_value() {
+        tokio_test::block_on(async {
+            let client = Client::mock();
+            client
+                .create_table_with(crate::TableDefinition::new(
+                    "scores",
+                    "player",
+                    ScalarAttributeType::N,
+                ))
+                .await
+                .unwrap();
+            client
+                .set_value("scores", ("player", 1), "score", 10)
+                .await
+                .unwrap();
+            let items = client
+                .query_item_raw("scores", Query::new("player", n("1.0")))
+                .try_collect::<Vec<_>>()
+                .await
+                .unwrap();
+            assert_eq!(items.len(), 1);
+        });
+    }
+
+    #[test]
+    fn page_limit_applies_before_filter() {
+        tokio_test::block_on(async {
+            let client = Client::mock();
+            client
+                .create_table("messages", "room", Some("id"), TableType::OnDemand)
+                .await
+                .unwrap();
+            for (id, read) in [("a", true), ("b", false), ("c", true), ("d", false)] {
+                client
+                    .set_value("messages", ("room", "r1", "id", id), "read", read)
+                    .await
+                    .unwrap();
+            }
+            let query = Query::new("room", "r1").filter(Condition::eq("read", false));
+            let first = client
+                .query_page_raw("messages", query.clone(), PageRequest::new(1))
+                .await
+                .unwrap();
+            // 読んだ1件がfilterで除かれても、続きのcursorは返ります
+            assert!(first.items.is_empty());
+            assert!(first.next_cursor.is_some());
+            let second = client
+                .query_page_raw(
+                    "messages",
+                    query,
+                    PageRequest::new(2).cursor(first.next_cursor),
+                )
+                .await
+                .unwrap();
+            assert_eq!(ids(&second), ["b"]);
+            assert!(second.next_cursor.is_some());
+
+            let page = client
+                .scan_page_raw(
+                    "messages",
+                    Scan::new().filter(Condition::eq("read", true)),
+                    PageRequest::new(4),
+                )
+                .await
+                .unwrap();
+            assert_eq!(ids(&page), ["a", "c"]);
+            assert_eq!(page.next_cursor, None);
+        });
+    }
+
+    #[test]
+    fn decimal_compares_by_value() {
+        assert_eq!(compare(&n("10"), &n("1e1")), Some(Ordering::Equal));
+        assert_eq!(compare(&n("0.05"), &n("5E-2")), Some(Ordering::Equal));
+        assert_eq!(compare(&n("-0"), &n("0.000")), Some(Ordering::Equal));
+        assert_eq!(compare(&n("12.5"), &n("12.05")), Some(Ordering::Greater));
+        assert_eq!(compare(&n("-3"), &n("-20")), Some(Ordering::Greater));
+        assert_eq!(compare(&n("-1"), &n("0")), Some(Ordering::Less));
+        assert_eq!(
+            compare(&n("99999999999999999999999999999999999999"), &n("1e38")),
+            Some(Ordering::Less)
+        );
+        assert_eq!(compare(&n("abc"), &n("1")), None);
+    }
+
+    #[test]
+    fn equals_numbers_and_sets() {
+        assert!(equals(&n("10"), &n("1e1")));
+        assert!(!equals(&n("10"), &n("11")));
+        assert!(equals(
+            &AttributeValue::Ns(vec!["1".into(), "2.0".into()]),
+            &AttributeValue::Ns(vec!["2".into(), "1".into()])
+        ));
+        assert!(equals(
+            &AttributeValue::Ss(vec!["a".into(), "b".into()]),
+            &AttributeValue::Ss(vec!["b".into(), "a".into()])
+        ));
+        assert!(equals(
+            &AttributeValue::M(HashMap::from([("a".into(), n("1.0"))])),
+            &AttributeValue::M(HashMap::from([("a".into(), n("1"))]))
+        ));
+    }
+
+    #[test]
+    fn add_numbers_rejects_overflow() {
+        assert_eq!(add_numbers("1", "2").unwrap(), "3");
+        assert_eq!(add_numbers("1.5", "1").unwrap(), "2.5");
+        assert_eq!(add_numbers("0.1", "0.2").unwrap(), "0.3");
+        assert_eq!(add_numbers("1e2", "-0.5").unwrap(), "99.5");
+        assert_eq!(add_numbers("-3", "1.25").unwrap(), "-1.75");
+        assert_eq!(add_numbers("2.5", "-2.5").unwrap(), "0");
+        assert_eq!(add_numbers("0.001", "0").unwrap(), "0.001");
+        let max = "9".repeat(NUMBER_PRECISION);
+        assert_eq!(
+            add_numbers(&max, "1").unwrap(),
+            format!("1{}", "0".repeat(38))
+        );
+        assert!(matches!(
+            add_numbers(&max, "0.1"),
+            Err(Error::Validation(_))
+        ));
+        assert!(matches!(
+            add_numbers("9.9e125", "1e125"),
+            Err(Error::Validation(_))
+        ));
+        assert!(matches!(
+            add_numbers("1e125", "1e-130"),
+            Err(Error::Validation(_))
+        ));
+        let huge = "170141183460469231731687303715884105727";
+        assert!(matches!(add_numbers(huge, huge), Err(Error::Validation(_))));
+    }
+}
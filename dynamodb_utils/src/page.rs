@@ -0,0 +1,382 @@
+use crate::{
+    client::from_sdk_error, sdk::types::AttributeValue, Autoscale, Client, Error, JsonFormat,
+    Query, Scan,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+use std::{collections::HashMap, sync::Arc};
+
+type Item = HashMap<String, AttributeValue>;
+
+/// 1ページ分の結果
+#[derive(Debug, Clone, PartialEq)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// 続きを取得するためのcursor。最後のページでは`None`です。
+    pub next_cursor: Option<String>,
+}
+
+/// ページ単位で取得するときの件数とcursor
+///
+/// cursorは`LastEvaluatedKey`をURLにそのまま使える文字列にしたものです。
+/// 署名用の鍵を指定すると、HMAC-SHA256の署名を付け、受け取ったcursorの署名を確認します。
+/// 署名はテーブル名とindex名、queryのpartition keyにも紐付くので、
+/// 別のテーブルやpartitionで発行されたcursorは受け付けません。
+/// ```
+/// # use dynamodb_utils::PageRequest;
+/// # let cursor: Option<String> = None;
+/// let request = PageRequest::new(20)
+///     .cursor(cursor)
+///     .signing_key(b"secret");
+/// ```
+#[derive(Clone)]
+pub struct PageRequest {
+    limit: i32,
+    cursor: Option<String>,
+    signing_key: Option<Arc<[u8]>>,
+}
+
+impl std::fmt::Debug for PageRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PageRequest")
+            .field("limit", &self.limit)
+            .field("cursor", &self.cursor)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PageRequest {
+    /// 1ページの最大件数を指定します
+    ///
+    /// DynamoDBと同じく、filterで除外されたitemも件数に含まれます。
+    pub fn new(limit: i32) -> Self {
+        Self {
+            limit: limit.max(1),
+            cursor: None,
+            signing_key: None,
+        }
+    }
+
+    /// 前のページの[`Page::next_cursor`]を指定します。`None`の場合は最初のページです。
+    pub fn cursor(mut self, cursor: Option<impl Into<String>>) -> Self {
+        self.cursor = cursor.map(Into::into);
+        self
+    }
+
+    /// cursorの署名に使う鍵を指定します
+    pub fn signing_key(mut self, key: impl AsRef<[u8]>) -> Self {
+        self.signing_key = Some(key.as_ref().into());
+        self
+    }
+
+    /// `scope`はcursorを発行したテーブルと条件を表す文字列です
+    fn mac(&self, scope: &str, payload: &str) -> Option<Hmac<Sha256>> {
+        let key = self.signing_key.as_ref()?;
+        let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+        mac.update(&(scope.len() as u64).to_be_bytes());
+        mac.update(scope.as_bytes());
+        mac.update(payload.as_bytes());
+        Some(mac)
+    }
+
+    /// `LastEvaluatedKey`をcursorにします
+    fn encode(&self, scope: &str, key: &Item) -> Result<String, Error> {
+        let json = JsonFormat::DynamoDb
+            .encode(key)
+            .ok_or(Error::InvalidCursor)?
+            .to_string();
+        let payload = URL_SAFE_NO_PAD.encode(json);
+        Ok(match self.mac(scope, &payload) {
+            Some(mac) => format!(
+                "{payload}.{}",
+                URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes())
+            ),
+            None => payload,
+        })
+    }
+
+    /// cursorを`ExclusiveStartKey`に戻します
+    fn decode(&self, scope: &str) -> Result<Option<Item>, Error> {
+        let Some(cursor) = &self.cursor else {
+            return Ok(None);
+        };
+        let payload = match (cursor.split_once('.'), self.signing_key.is_some()) {
+            (Some((payload, signature)), true) => {
+                let signature = URL_SAFE_NO_PAD
+                    .decode(signature)
+                    .map_err(|_| Error::InvalidCursor)?;
+                self.mac(scope, payload)
+                    .expect("signing key is set")
+                    .verify_slice(&signature)
+                    .map_err(|_| Error::InvalidCursor)?;
+                payload
+            }
+            (None, false) => cursor.as_str(),
+            _ => return Err(Error::InvalidCursor),
+        };
+        let json = URL_SAFE_NO_PAD
+            .decode(payload)
+            .map_err(|_| Error::InvalidCursor)?;
+        let value = serde_json::from_slice(&json).map_err(|_| Error::InvalidCursor)?;
+        JsonFormat::DynamoDb
+            .decode(value)
+            .map(Some)
+            .ok_or(Error::InvalidCursor)
+    }
+
+    fn page(&self, scope: &str, items: Vec<Item>, last: Option<Item>) -> Result<Page<Item>, Error> {
+        Ok(Page {
+            items,
+            next_cursor: last.map(|key| self.encode(scope, &key)).transpose()?,
+        })
+    }
+}
+
+/// queryのcursorを紐付ける範囲
+fn query_scope(table_name: &str, query: &Query) -> String {
+    let (partition_name, partition_value) = &query.partition;
+    let partition = JsonFormat::DynamoDb
+        .encode(&Item::from([(
+            partition_name.clone(),
+            partition_value.clone(),
+        )]))
+        .map(|value| value.to_string())
+        .unwrap_or_default();
+    serde_json::json!(["query", table_name, query.index_name.as_deref(), partition]).to_string()
+}
+
+/// scanのcursorを紐付ける範囲
+fn scan_scope(table_name: &str) -> String {
+    serde_json::json!(["scan", table_name]).to_string()
+}
+
+impl<A: Autoscale> Client<A> {
+    /// queryの結果を1ページ分取得します
+    /// 具体的な型で受けたいなら[`query_page`](`Self::query_page`)があります。
+    ///
+    /// [`Query::limit`]の代わりに[`PageRequest`]の件数を使います。
+    /// 件数はfilterの前に数えるので、filterがあると少なくなることがあります。
+    pub async fn query_page_raw(
+        &self,
+        table_name: impl Into<String>,
+        query: Query,
+        request: PageRequest,
+    ) -> Result<Page<Item>, Error> {
+        let table_name = table_name.into();
+        let scope = query_scope(&table_name, &query);
+        let start = request.decode(&scope)?;
+        if let Some(mock) = &self.mock {
+            let (items, last) =
+                mock.query_page(&table_name, &query, start, request.limit as usize)?;
+            return request.page(&scope, items, last);
+        }
+        let output = query
+            .apply(self.dynamodb.query().table_name(table_name))
+            .limit(request.limit)
+            .set_exclusive_start_key(start)
+            .send()
+            .await
+            .map_err(from_sdk_error)?;
+        request.page(
+            &scope,
+            output.items.unwrap_or_default(),
+            output.last_evaluated_key,
+        )
+    }
+
+    /// queryの結果を1ページ分取得します
+    /// ```
+    /// # use dynamodb_utils::*;
+    /// # tokio_test::block_on(async {
+    /// let client = Client::mock();
+    /// client
+    ///     .create_table("messages", "room", Some("id"), TableType::OnDemand)
+    ///     .await?;
+    /// for id in ["a", "b", "c"] {
+    ///     client.set_value("messages", ("room", "r1", "id", id), "body", id).await?;
+    /// }
+    ///
+    /// let request = PageRequest::new(2).signing_key(b"secret");
+    /// let first = client
+    ///     .query_page_raw("messages", Query::new("room", "r1"), request.clone())
+    ///     .await?;
+    /// assert_eq!(first.items.len(), 2);
+    /// let second = client
+    ///     .query_page_raw("messages", Query::new("room", "r1"), request.cursor(first.next_cursor))
+    ///     .await?;
+    /// assert_eq!(second.items.len(), 1);
+    /// assert_eq!(second.next_cursor, None);
+    /// # Ok::<(), Error>(())
+    /// # });
+    /// ```
+    pub async fn query_page<T>(
+        &self,
+        table_name: impl Into<String>,
+        query: Query,
+        request: PageRequest,
+    ) -> Result<Page<T>, Error>
+    where
+        for<'de> T: Deserialize<'de>,
+    {
+        deserialize_page(self.query_page_raw(table_name, query, request).await?)
+    }
+
+    /// scanの結果を1ページ分取得します
+    /// 具体的な型で受けたいなら[`scan_page`](`Self::scan_page`)があります。
+    ///
+    /// [`Scan::segments`]の指定は無視します。
+    /// 件数はfilterの前に数えるので、filterがあると少なくなることがあります。
+    pub async fn scan_page_raw(
+        &self,
+        table_name: impl Into<String>,
+        scan: Scan,
+        request: PageRequest,
+    ) -> Result<Page<Item>, Error> {
+        let table_name = table_name.into();
+        let scope = scan_scope(&table_name);
+        let start = request.decode(&scope)?;
+        if let Some(mock) = &self.mock {
+            let (items, last) =
+                mock.scan_page(&table_name, &scan, start, request.limit as usize)?;
+            return request.page(&scope, items, last);
+        }
+        let output = self
+            .scan_request(&table_name, &scan)
+            .limit(request.limit)
+            .set_exclusive_start_key(start)
+            .send()
+            .await
+            .map_err(from_sdk_error)?;
+        request.page(
+            &scope,
+            output.items.unwrap_or_default(),
+            output.last_evaluated_key,
+        )
+    }
+
+    /// scanの結果を1ページ分取得します
+    pub async fn scan_page<T>(
+        &self,
+        table_name: impl Into<String>,
+        scan: Scan,
+        request: PageRequest,
+    ) -> Result<Page<T>, Error>
+    where
+        for<'de> T: Deserialize<'de>,
+    {
+        deserialize_page(self.scan_page_raw(table_name, scan, request).await?)
+    }
+}
+
+fn deserialize_page<T>(page: Page<Item>) -> Result<Page<T>, Error>
+where
+    for<'de> T: Deserialize<'de>,
+{
+    Ok(Page {
+        items: crate::serde_dynamo::aws_sdk_dynamodb_1::from_items(page.items)?,
+        next_cursor: page.next_cursor,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::IntoValue;
+
+    fn key() -> Item {
+        Item::from([
+            ("room".to_owned(), "r1".into_value()),
+            ("id".to_owned(), "a".into_value()),
+        ])
+    }
+
+    fn scope() -> String {
+        query_scope("messages", &Query::new("room", "r1"))
+    }
+
+    fn signed() -> PageRequest {
+        PageRequest::new(10).signing_key(b"secret")
+    }
+
+    fn decode(request: PageRequest, cursor: String) -> Result<Option<Item>, Error> {
+        request.cursor(Some(cursor)).decode(&scope())
+    }
+
+    #[test]
+    fn signed_cursor_round_trip() {
+        let cursor = signed().encode(&scope(), &key()).unwrap();
+        assert_eq!(decode(signed(), cursor).unwrap(), Some(key()));
+        let cursor = PageRequest::new(10).encode(&scope(), &key()).unwrap();
+        assert_eq!(decode(PageRequest::new(10), cursor).unwrap(), Some(key()));
+    }
+
+    #[test]
+    fn tampered_payload_is_rejected() {
+        let cursor = signed().encode(&scope(), &key()).unwrap();
+        let (_, signature) = cursor.split_once('.').unwrap();
+        let mut other = key();
+        other.insert("id".to_owned(), "b".into_value());
+        let payload = PageRequest::new(10).encode(&scope(), &other).unwrap();
+        assert!(matches!(
+            decode(signed(), format!("{payload}.{signature}")),
+            Err(Error::InvalidCursor)
+        ));
+    }
+
+    #[test]
+    fn tampered_signature_is_rejected() {
+        let cursor = signed().encode(&scope(), &key()).unwrap();
+        let (payload, _) = cursor.split_once('.').unwrap();
+        let forged = PageRequest::new(10)
+            .signing_key(b"other")
+            .encode(&scope(), &key())
+            .unwrap();
+        let (_, signature) = forged.split_once('.').unwrap();
+        for cursor in [
+            format!("{payload}.{signature}"),
+            format!("{payload}.not-base64!"),
+            format!("{payload}."),
+        ] {
+            assert!(matches!(
+                decode(signed(), cursor),
+                Err(Error::InvalidCursor)
+            ));
+        }
+    }
+
+    #[test]
+    fn unsigned_cursor_is_rejected_when_signing() {
+        let cursor = PageRequest::new(10).encode(&scope(), &key()).unwrap();
+        assert!(matches!(
+            decode(signed(), cursor),
+            Err(Error::InvalidCursor)
+        ));
+    }
+
+    #[test]
+    fn signed_cursor_is_rejected_without_key() {
+        let cursor = signed().encode(&scope(), &key()).unwrap();
+        assert!(matches!(
+            decode(PageRequest::new(10), cursor),
+            Err(Error::InvalidCursor)
+        ));
+    }
+
+    #[test]
+    fn cursor_is_bound_to_table_and_query() {
+        let cursor = signed().encode(&scope(), &key()).unwrap();
+        for other in [
+            query_scope("messages", &Query::new("room", "r2")),
+            query_scope("messages", &Query::new("room", "r1").index("by_user")),
+            query_scope("archived", &Query::new("room", "r1")),
+            scan_scope("messages"),
+        ] {
+            assert!(matches!(
+                signed().cursor(Some(cursor.clone())).decode(&other),
+                Err(Error::InvalidCursor)
+            ));
+        }
+    }
+}
@@ -0,0 +1,364 @@
+use crate::{
+    expression::ExpressionAttributes, sdk::operation::query::builders::QueryFluentBuilder,
+    sdk::types::AttributeValue, Condition, IntoValue,
+};
+
+/// Queryの条件
+///
+/// partition keyの一致は必須で、sort keyの条件は任意です。
+/// ```
+/// # use dynamodb_utils::Query;
+/// let query = Query::new("user_id", "abc")
+///     .sort_between("created_at", 1716000000, 1717000000)
+///     .scan_index_forward(false)
+///     .limit(10);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Query {
+    pub(crate) partition: (String, AttributeValue),
+    pub(crate) sort: Option<SortKeyCondition>,
+    pub(crate) filter: Option<Condition>,
+    pub(crate) index_name: Option<String>,
+    pub(crate) scan_index_forward: Option<bool>,
+    pub(crate) limit: Option<i32>,
+}
+
+/// sort keyの条件
+#[derive(Debug, Clone)]
+pub(crate) enum SortKeyCondition {
+    Eq(String, AttributeValue),
+    Lt(String, AttributeValue),
+    Le(String, AttributeValue),
+    Gt(String, AttributeValue),
+    Ge(String, AttributeValue),
+    Between(String, AttributeValue, AttributeValue),
+    BeginsWith(String, AttributeValue),
+}
+
+impl SortKeyCondition {
+    /// sort keyの項目名
+    pub(crate) fn name(&self) -> &str {
+        match self {
+            SortKeyCondition::Eq(name, _)
+            | SortKeyCondition::Lt(name, _)
+            | SortKeyCondition::Le(name, _)
+            | SortKeyCondition::Gt(name, _)
+            | SortKeyCondition::Ge(name, _)
+            | SortKeyCondition::Between(name, _, _)
+            | SortKeyCondition::BeginsWith(name, _) => name,
+        }
+    }
+}
+
+impl Query {
+    /// partition keyが`value`と一致するitemを対象にします
+    pub fn new(partition_name: impl Into<String>, value: impl IntoValue) -> Self {
+        Self {
+            partition: (partition_name.into(), value.into_value()),
+            sort: None,
+            filter: None,
+            index_name: None,
+            scan_index_forward: None,
+            limit: None,
+        }
+    }
+
+    /// sort keyが`value`と一致するもの
+    pub fn sort_eq(self, name: impl Into<String>, value: impl IntoValue) -> Self {
+        self.sort(SortKeyCondition::Eq(name.into(), value.into_value()))
+    }
+
+    /// sort keyが`value`未満のもの
+    pub fn sort_lt(self, name: impl Into<String>, value: impl IntoValue) -> Self {
+        self.sort(SortKeyCondition::Lt(name.into(), value.into_value()))
+    }
+
+    /// sort keyが`value`以下のもの
+    pub fn sort_le(self, name: impl Into<String>, value: impl IntoValue) -> Self {
+        self.sort(SortKeyCondition::Le(name.into(), value.into_value()))
+    }
+
+    /// sort keyが`value`より大きいもの
+    pub fn sort_gt(self, name: impl Into<String>, value: impl IntoValue) -> Self {
+        self.sort(SortKeyCondition::Gt(name.into(), value.into_value()))
+    }
+
+    /// sort keyが`value`以上のもの
+    pub fn sort_ge(self, name: impl Into<String>, value: impl IntoValue) -> Self {
+        self.sort(SortKeyCondition::Ge(name.into(), value.into_value()))
+    }
+
+    /// sort keyが`low`以上`high`以下のもの
+    pub fn sort_between(
+        self,
+        name: impl Into<String>,
+        low: impl IntoValue,
+        high: impl IntoValue,
+    ) -> Self {
+        self.sort(SortKeyCondition::Between(
+            name.into(),
+            low.into_value(),
+            high.into_value(),
+        ))
+    }
+
+    /// sort keyが`prefix`から始まるもの
+    pub fn sort_begins_with(self, name: impl Into<String>, prefix: impl IntoValue) -> Self {
+        self.sort(SortKeyCondition::BeginsWith(
+            name.into(),
+            prefix.into_value(),
+        ))
+    }
+
+    /// FilterExpressionを指定します
+    ///
+    /// すでに条件がある場合は`AND`で結合されます。
+    pub fn filter(mut self, condition: Condition) -> Self {
+        self.filter = Some(match self.filter {
+            Some(prev) => prev.and(condition),
+            None => condition,
+        });
+        self
+    }
+
+    /// 検索に使うindexを指定します
+    pub fn index(mut self, index_name: impl Into<String>) -> Self {
+        self.index_name = Some(index_name.into());
+        self
+    }
+
+    /// sort keyの昇順(`true`)、降順(`false`)を指定します。デフォルトは昇順です。
+    pub fn scan_index_forward(mut self, forward: bool) -> Self {
+        self.scan_index_forward = Some(forward);
+        self
+    }
+
+    /// 取得する最大件数を指定します
+    pub fn limit(mut self, limit: i32) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    fn sort(mut self, condition: SortKeyCondition) -> Self {
+        self.sort = Some(condition);
+        self
+    }
+
+    /// SDKのbuilderへ条件を設定します
+    pub(crate) fn apply(self, builder: QueryFluentBuilder) -> QueryFluentBuilder {
+        let mut attributes = ExpressionAttributes::default();
+        let key_condition = self.key_condition_expression(&mut attributes);
+        let filter = self
+            .filter
+            .as_ref()
+            .map(|condition| condition.expression(&mut attributes));
+        let (names, values) = attributes.into_parts();
+        builder
+            .key_condition_expression(key_condition)
+            .set_filter_expression(filter)
+            .set_expression_attribute_names(names)
+            .set_expression_attribute_values(values)
+            .set_index_name(self.index_name)
+            .set_scan_index_forward(self.scan_index_forward)
+            .set_limit(self.limit)
+    }
+
+    fn key_condition_expression(&self, attributes: &mut ExpressionAttributes) -> String {
+        let (name, value) = &self.partition;
+        let partition = format!(
+            "{} = {}",
+            attributes.name(name),
+            attributes.value(value.clone())
+        );
+        let Some(sort) = &self.sort else {
+            return partition;
+        };
+        let sort = match sort {
+            SortKeyCondition::Eq(name, value) => compare(attributes, name, "=", value),
+            SortKeyCondition::Lt(name, value) => compare(attributes, name, "<", value),
+            SortKeyCondition::Le(name, value) => compare(attributes, name, "<=", value),
+            SortKeyCondition::Gt(name, value) => compare(attributes, name, ">", value),
+            SortKeyCondition::Ge(name, value) => compare(attributes, name, ">=", value),
+            SortKeyCondition::Between(name, low, high) => format!(
+                "{} BETWEEN {} AND {}",
+                attributes.name(name),
+                attributes.value(low.clone()),
+                attributes.value(high.clone())
+            ),
+            SortKeyCondition::BeginsWith(name, prefix) => format!(
+                "begins_with({}, {})",
+                attributes.name(name),
+                attributes.value(prefix.clone())
+            ),
+        };
+        format!("{partition} AND {sort}")
+    }
+}
+
+fn compare(
+    attributes: &mut ExpressionAttributes,
+    name: &str,
+    operator: &str,
+    value: &AttributeValue,
+) -> String {
+    format!(
+        "{} {operator} {}",
+        attributes.name(name),
+        attributes.value(value.clone())
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Client;
+    use std::collections::HashMap;
+
+    fn apply(query: Query) -> QueryFluentBuilder {
+        query.apply(Client::mock().dynamodb.query())
+    }
+
+    fn names(pairs: &[(&str, &str)]) -> Option<HashMap<String, String>> {
+        Some(
+            pairs
+                .iter()
+                .map(|(placeholder, name)| (placeholder.to_string(), name.to_string()))
+                .collect(),
+        )
+    }
+
+    fn values(pairs: Vec<(&str, AttributeValue)>) -> Option<HashMap<String, AttributeValue>> {
+        Some(
+            pairs
+                .into_iter()
+                .map(|(placeholder, value)| (placeholder.to_string(), value))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn partition_only() {
+        let builder = apply(Query::new("id", "a"));
+        assert_eq!(
+            builder.get_key_condition_expression().as_deref(),
+            Some("#n0 = :v0")
+        );
+        assert_eq!(builder.get_filter_expression(), &None);
+        assert_eq!(
+            builder.get_expression_attribute_names(),
+            &names(&[("#n0", "id")])
+        );
+        assert_eq!(
+            builder.get_expression_attribute_values(),
+            &values(vec![(":v0", "a".into_value())])
+        );
+        assert_eq!(builder.get_index_name(), &None);
+        assert_eq!(builder.get_scan_index_forward(), &None);
+        assert_eq!(builder.get_limit(), &None);
+    }
+
+    #[test]
+    fn sort_comparisons() {
+        for (query, operator) in [
+            (Query::new("id", "a").sort_eq("n", 1), "="),
+            (Query::new("id", "a").sort_lt("n", 1), "<"),
+            (Query::new("id", "a").sort_le("n", 1), "<="),
+            (Query::new("id", "a").sort_gt("n", 1), ">"),
+            (Query::new("id", "a").sort_ge("n", 1), ">="),
+        ] {
+            let builder = apply(query);
+            assert_eq!(
+                builder.get_key_condition_expression().as_deref(),
+                Some(format!("#n0 = :v0 AND #n1 {operator} :v1").as_str())
+            );
+            assert_eq!(
+                builder.get_expression_attribute_names(),
+                &names(&[("#n0", "id"), ("#n1", "n")])
+            );
+            assert_eq!(
+                builder.get_expression_attribute_values(),
+                &values(vec![(":v0", "a".into_value()), (":v1", 1.into_value())])
+            );
+        }
+    }
+
+    #[test]
+    fn sort_between() {
+        let builder = apply(Query::new("id", "a").sort_between("n", 1, 5));
+        assert_eq!(
+            builder.get_key_condition_expression().as_deref(),
+            Some("#n0 = :v0 AND #n1 BETWEEN :v1 AND :v2")
+        );
+        assert_eq!(
+            builder.get_expression_attribute_values(),
+            &values(vec![
+                (":v0", "a".into_value()),
+                (":v1", 1.into_value()),
+                (":v2", 5.into_value()),
+            ])
+        );
+    }
+
+    #[test]
+    fn sort_begins_with() {
+        let builder = apply(Query::new("id", "a").sort_begins_with("sk", "2024-"));
+        assert_eq!(
+            builder.get_key_condition_expression().as_deref(),
+            Some("#n0 = :v0 AND begins_with(#n1, :v1)")
+        );
+        assert_eq!(
+            builder.get_expression_attribute_names(),
+            &names(&[("#n0", "id"), ("#n1", "sk")])
+        );
+        assert_eq!(
+            builder.get_expression_attribute_values(),
+            &values(vec![
+                (":v0", "a".into_value()),
+                (":v1", "2024-".into_value())
+            ])
+        );
+    }
+
+    #[test]
+    fn filter_shares_placeholders_with_key_condition() {
+        let builder = apply(
+            Query::new("id", "a")
+                .sort_gt("n", 1)
+                .filter(Condition::eq("status", "ok").and(Condition::lt("n", 10))),
+        );
+        assert_eq!(
+            builder.get_key_condition_expression().as_deref(),
+            Some("#n0 = :v0 AND #n1 > :v1")
+        );
+        assert_eq!(
+            builder.get_filter_expression().as_deref(),
+            Some("(#n2 = :v2) AND (#n1 < :v3)")
+        );
+        assert_eq!(
+            builder.get_expression_attribute_names(),
+            &names(&[("#n0", "id"), ("#n1", "n"), ("#n2", "status")])
+        );
+        assert_eq!(
+            builder.get_expression_attribute_values(),
+            &values(vec![
+                (":v0", "a".into_value()),
+                (":v1", 1.into_value()),
+                (":v2", "ok".into_value()),
+                (":v3", 10.into_value()),
+            ])
+        );
+    }
+
+    #[test]
+    fn index_and_paging_options() {
+        let builder = apply(
+            Query::new("id", "a")
+                .index("by_id")
+                .scan_index_forward(false)
+                .limit(10),
+        );
+        assert_eq!(builder.get_index_name().as_deref(), Some("by_id"));
+        assert_eq!(builder.get_scan_index_forward(), &Some(false));
+        assert_eq!(builder.get_limit(), &Some(10));
+    }
+}
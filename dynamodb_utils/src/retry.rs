@@ -0,0 +1,38 @@
+use std::time::Duration;
+
+/// 未処理の要求を再送するときの待ち時間を決めます
+///
+/// 待ち時間は再送のたびに倍になり、`max_delay`で頭打ちになります。
+#[derive(Debug, Clone)]
+pub struct Backoff {
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+    /// 再送の最大回数
+    pub max_retries: u32,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            base_delay: Duration::from_millis(50),
+            max_delay: Duration::from_secs(5),
+            max_retries: 10,
+        }
+    }
+}
+
+impl Backoff {
+    /// `attempt`回目の再送の前に待ちます。
+    /// 再送回数の上限を超えていたら`false`を返します。
+    pub(crate) async fn wait(&self, attempt: u32) -> bool {
+        if attempt > self.max_retries {
+            return false;
+        }
+        let delay = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)))
+            .min(self.max_delay);
+        tokio::time::sleep(delay).await;
+        true
+    }
+}
@@ -0,0 +1,262 @@
+use crate::{
+    client::from_sdk_error,
+    expression::{ExpressionAttributes, Path},
+    mock,
+    sdk::{
+        operation::scan::builders::ScanFluentBuilder, types::AttributeValue, PaginationStreamExt,
+    },
+    utils::deserialize_stream,
+    Client, Condition, Error,
+};
+use futures_util::{stream, Stream, StreamExt, TryStream, TryStreamExt};
+use serde::Deserialize;
+use std::{
+    collections::{HashMap, VecDeque},
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// Scanの条件
+///
+/// `segments`を2以上にすると、テーブルを分割して並列にscanします。
+/// ```
+/// # use dynamodb_utils::{Condition, Scan};
+/// let scan = Scan::new()
+///     .filter(Condition::eq("status", "active"))
+///     .projection(["id", "profile.name"])
+///     .segments(8);
+/// ```
+#[derive(Debug, Clone)]
+pub struct Scan {
+    pub(crate) filter: Option<Condition>,
+    pub(crate) projection: Vec<Path>,
+    pub(crate) segments: i32,
+    pub(crate) ordered: bool,
+}
+
+impl Default for Scan {
+    fn default() -> Self {
+        Self {
+            filter: None,
+            projection: vec![],
+            segments: 1,
+            ordered: false,
+        }
+    }
+}
+
+impl Scan {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// FilterExpressionを指定します
+    ///
+    /// すでに条件がある場合は`AND`で結合されます。
+    pub fn filter(mut self, condition: Condition) -> Self {
+        self.filter = Some(match self.filter {
+            Some(prev) => prev.and(condition),
+            None => condition,
+        });
+        self
+    }
+
+    /// 取得する項目を指定します
+    pub fn projection<P: Into<Path>>(mut self, paths: impl IntoIterator<Item = P>) -> Self {
+        self.projection.extend(paths.into_iter().map(Into::into));
+        self
+    }
+
+    /// 分割数(`TotalSegments`)を指定します。デフォルトは1で、分割しません。
+    pub fn segments(mut self, segments: i32) -> Self {
+        self.segments = segments.max(1);
+        self
+    }
+
+    /// segmentの順番通りにitemを返すかどうかを指定します。デフォルトは`false`です。
+    ///
+    /// `false`の場合は、各segmentで取得できたものから順に返します。
+    /// `true`の場合も並列に取得します。先頭のsegmentは取得したものから順に返し、
+    /// 後ろのsegmentの結果は順番が来るまでメモリ上に保持します。
+    pub fn ordered(mut self, ordered: bool) -> Self {
+        self.ordered = ordered;
+        self
+    }
+}
+
+impl<A> Client<A> {
+    /// 条件を指定してscanを掛けます
+    /// 具体的な型で受けたいなら[`scan_item_with`](`Self::scan_item_with`)があります。
+    pub fn scan_item_raw_with(
+        &self,
+        table_name: impl Into<String>,
+        scan: Scan,
+    ) -> impl TryStream<Ok = HashMap<String, AttributeValue>, Error = Error> {
+        let table_name = table_name.into();
+        if let Some(mock) = &self.mock {
+            return mock::into_stream(mock.scan(&table_name, &scan)).left_stream();
+        }
+        let segments = (0..scan.segments)
+            .map(|segment| self.scan_segment(&table_name, &scan, segment))
+            .collect::<Vec<_>>();
+        if scan.ordered {
+            OrderedSegments::new(segments).left_stream().right_stream()
+        } else {
+            stream::select_all(segments).right_stream().right_stream()
+        }
+    }
+
+    /// 条件を指定してscanを掛けます
+    pub fn scan_item_with<T>(
+        &self,
+        table_name: impl Into<String>,
+        scan: Scan,
+    ) -> impl TryStream<Ok = T, Error = Error>
+    where
+        for<'de> T: Deserialize<'de>,
+    {
+        deserialize_stream(self.scan_item_raw_with(table_name, scan))
+    }
+
+    fn scan_segment(
+        &self,
+        table_name: &str,
+        scan: &Scan,
+        segment: i32,
+    ) -> impl futures_util::Stream<Item = Result<HashMap<String, AttributeValue>, Error>> + Unpin
+    {
+        let (segment, total_segments) = if scan.segments > 1 {
+            (Some(segment), Some(scan.segments))
+        } else {
+            (None, None)
+        };
+        self.scan_request(table_name, scan)
+            .set_segment(segment)
+            .set_total_segments(total_segments)
+            .into_paginator()
+            .items()
+            .send()
+            .into_stream_03x()
+            .map_err(from_sdk_error)
+    }
+
+    /// 条件と取得する項目を設定したリクエスト
+    pub(crate) fn scan_request(&self, table_name: &str, scan: &Scan) -> ScanFluentBuilder {
+        let mut attributes = ExpressionAttributes::default();
+        let filter = scan
+            .filter
+            .as_ref()
+            .map(|condition| condition.expression(&mut attributes));
+        let projection = Some(
+            scan.projection
+                .iter()
+                .map(|path| attributes.path(path))
+                .collect::<Vec<_>>()
+                .join(", "),
+        )
+        .filter(|projection| !projection.is_empty());
+        let (names, values) = attributes.into_parts();
+        self.raw_client()
+            .scan()
+            .table_name(table_name)
+            .set_filter_expression(filter)
+            .set_projection_expression(projection)
+            .set_expression_attribute_names(names)
+            .set_expression_attribute_values(values)
+    }
+}
+
+/// segmentを順番通りに返すstream
+///
+/// 先頭のsegmentはそのまま返し、後ろのsegmentは並行して取得しながら順番が来るまでためておきます。
+struct OrderedSegments<S: Stream> {
+    segments: VecDeque<Segment<S>>,
+}
+
+struct Segment<S: Stream> {
+    /// 取得し終わったら`None`
+    stream: Option<S>,
+    buffer: VecDeque<S::Item>,
+}
+
+impl<S: Stream + Unpin> OrderedSegments<S> {
+    fn new(segments: Vec<S>) -> Self {
+        Self {
+            segments: segments
+                .into_iter()
+                .map(|stream| Segment {
+                    stream: Some(stream),
+                    buffer: VecDeque::new(),
+                })
+                .collect(),
+        }
+    }
+}
+
+// 中身をpinしないので、itemの型によらずUnpinです
+impl<S: Stream> Unpin for OrderedSegments<S> {}
+
+impl<S: Stream + Unpin> Stream for OrderedSegments<S> {
+    type Item = S::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        for segment in self.segments.iter_mut().skip(1) {
+            while let Some(stream) = &mut segment.stream {
+                match stream.poll_next_unpin(cx) {
+                    Poll::Ready(Some(item)) => segment.buffer.push_back(item),
+                    Poll::Ready(None) => segment.stream = None,
+                    Poll::Pending => break,
+                }
+            }
+        }
+        while let Some(head) = self.segments.front_mut() {
+            if let Some(item) = head.buffer.pop_front() {
+                return Poll::Ready(Some(item));
+            }
+            if let Some(stream) = &mut head.stream {
+                match stream.poll_next_unpin(cx) {
+                    Poll::Ready(Some(item)) => return Poll::Ready(Some(item)),
+                    Poll::Ready(None) => {}
+                    Poll::Pending => return Poll::Pending,
+                }
+            }
+            self.segments.pop_front();
+        }
+        Poll::Ready(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::mpsc;
+
+    fn channel() -> (mpsc::UnboundedSender<i32>, stream::BoxStream<'static, i32>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let receiver = stream::unfold(receiver, |mut receiver| async move {
+            Some((receiver.recv().await?, receiver))
+        });
+        (sender, receiver.boxed())
+    }
+
+    #[test]
+    fn ordered_segments_stream_head_before_later_segments_finish() {
+        tokio_test::block_on(async {
+            let (head, head_rx) = channel();
+            let (tail, tail_rx) = channel();
+            let mut ordered = OrderedSegments::new(vec![head_rx, tail_rx]);
+
+            tail.send(3).unwrap();
+            head.send(1).unwrap();
+            assert_eq!(ordered.next().await, Some(1));
+            head.send(2).unwrap();
+            assert_eq!(ordered.next().await, Some(2));
+            drop(head);
+            assert_eq!(ordered.next().await, Some(3));
+            tail.send(4).unwrap();
+            drop(tail);
+            assert_eq!(ordered.next().await, Some(4));
+            assert_eq!(ordered.next().await, None);
+        });
+    }
+}
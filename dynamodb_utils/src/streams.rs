@@ -0,0 +1,582 @@
+use crate::{Autoscale, Client, Error, IntoValue, Key, TableDefinition};
+use aws_sdk_dynamodb::types::{AttributeValue, ScalarAttributeType};
+use aws_sdk_dynamodbstreams::types::{OperationType, Record, Shard, ShardIteratorType};
+use futures_util::{stream, Stream, TryStreamExt};
+use serde::de::DeserializeOwned;
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    future::Future,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+type Item = HashMap<String, AttributeValue>;
+
+/// streamから読み込んだitemの変更
+#[derive(Debug, Clone)]
+pub enum ChangeEvent<T> {
+    /// itemが追加された
+    Insert { keys: Item, new_image: Option<T> },
+    /// itemが更新された
+    Modify {
+        keys: Item,
+        old_image: Option<T>,
+        new_image: Option<T>,
+    },
+    /// itemが削除された
+    Remove { keys: Item, old_image: Option<T> },
+}
+
+impl<T> ChangeEvent<T> {
+    /// 変更されたitemのkey
+    pub fn keys(&self) -> &Item {
+        match self {
+            ChangeEvent::Insert { keys, .. }
+            | ChangeEvent::Modify { keys, .. }
+            | ChangeEvent::Remove { keys, .. } => keys,
+        }
+    }
+}
+
+impl<T: DeserializeOwned> ChangeEvent<T> {
+    /// imageは`StreamViewType`によっては含まれません
+    fn from_record(record: Record) -> Result<Option<Self>, Error> {
+        let Some(change) = record.dynamodb else {
+            return Ok(None);
+        };
+        let keys = change.keys.map(convert_item).unwrap_or_default();
+        let old_image = change.old_image.map(deserialize_image).transpose()?;
+        let new_image = change.new_image.map(deserialize_image).transpose()?;
+        Ok(match record.event_name {
+            Some(OperationType::Insert) => Some(ChangeEvent::Insert { keys, new_image }),
+            Some(OperationType::Modify) => Some(ChangeEvent::Modify {
+                keys,
+                old_image,
+                new_image,
+            }),
+            Some(OperationType::Remove) => Some(ChangeEvent::Remove { keys, old_image }),
+            _ => None,
+        })
+    }
+}
+
+/// streamsのSDKのitemを、dynamodbのSDKのitemにします
+fn convert_item(item: HashMap<String, aws_sdk_dynamodbstreams::types::AttributeValue>) -> Item {
+    crate::serde_dynamo::Item::from(item).into()
+}
+
+fn deserialize_image<T: DeserializeOwned>(
+    image: HashMap<String, aws_sdk_dynamodbstreams::types::AttributeValue>,
+) -> Result<T, Error> {
+    crate::serde_dynamo::aws_sdk_dynamodbstreams_1::from_item(image).map_err(Into::into)
+}
+
+/// shardごとに処理済みの位置を保存する先
+pub trait CheckpointStore: Send + Sync {
+    /// `shard_id`で最後に処理したsequence number
+    fn load(
+        &self,
+        stream_arn: &str,
+        shard_id: &str,
+    ) -> impl Future<Output = Result<Option<String>, Error>> + Send;
+
+    /// `shard_id`の`sequence_number`までを処理済みにします
+    fn save(
+        &self,
+        stream_arn: &str,
+        shard_id: &str,
+        sequence_number: &str,
+    ) -> impl Future<Output = Result<(), Error>> + Send;
+}
+
+/// メモリ上に保存します。cloneしたものとは中身を共有します。
+#[derive(Debug, Clone, Default)]
+pub struct MemoryCheckpoints {
+    checkpoints: Arc<Mutex<HashMap<(String, String), String>>>,
+}
+
+impl CheckpointStore for MemoryCheckpoints {
+    async fn load(&self, stream_arn: &str, shard_id: &str) -> Result<Option<String>, Error> {
+        let checkpoints = self.checkpoints.lock().expect("poisoned lock");
+        Ok(checkpoints
+            .get(&(stream_arn.to_owned(), shard_id.to_owned()))
+            .cloned())
+    }
+
+    async fn save(
+        &self,
+        stream_arn: &str,
+        shard_id: &str,
+        sequence_number: &str,
+    ) -> Result<(), Error> {
+        let mut checkpoints = self.checkpoints.lock().expect("poisoned lock");
+        checkpoints.insert(
+            (stream_arn.to_owned(), shard_id.to_owned()),
+            sequence_number.to_owned(),
+        );
+        Ok(())
+    }
+}
+
+/// DynamoDBのテーブルに保存します
+///
+/// テーブルはpartition keyが`stream_arn`、sort keyが`shard_id`で、
+/// [`table_definition`](`Self::table_definition`)で作れます。
+#[derive(Debug, Clone)]
+pub struct TableCheckpoints<A = ()> {
+    client: Client<A>,
+    table_name: String,
+}
+
+impl TableCheckpoints {
+    /// checkpointを保存するテーブルの定義
+    pub fn table_definition(table_name: impl Into<String>) -> TableDefinition {
+        TableDefinition::new(table_name, "stream_arn", ScalarAttributeType::S)
+            .sort_key("shard_id", ScalarAttributeType::S)
+    }
+}
+
+impl<A: Autoscale> TableCheckpoints<A> {
+    /// prefixは付きません
+    pub fn new(client: Client<A>, table_name: impl Into<String>) -> Self {
+        Self {
+            client,
+            table_name: table_name.into(),
+        }
+    }
+}
+
+impl<A: Autoscale> CheckpointStore for TableCheckpoints<A> {
+    async fn load(&self, stream_arn: &str, shard_id: &str) -> Result<Option<String>, Error> {
+        let item = self
+            .client
+            .get_item_raw(
+                &self.table_name,
+                Key::composite("stream_arn", stream_arn, "shard_id", shard_id),
+            )
+            .await?
+            .item;
+        Ok(item
+            .as_ref()
+            .and_then(|item| item.get("sequence_number"))
+            .and_then(|value| value.as_s().ok())
+            .cloned())
+    }
+
+    async fn save(
+        &self,
+        stream_arn: &str,
+        shard_id: &str,
+        sequence_number: &str,
+    ) -> Result<(), Error> {
+        let item = HashMap::from([
+            ("stream_arn".to_owned(), stream_arn.into_value()),
+            ("shard_id".to_owned(), shard_id.into_value()),
+            ("sequence_number".to_owned(), sequence_number.into_value()),
+        ]);
+        self.client.put_item_raw(&self.table_name, item).await?;
+        Ok(())
+    }
+}
+
+/// DynamoDB Streamsを読み込み、[`ChangeEvent`]を返します
+///
+/// shardを見つけて親から順に読み、閉じていないshardは`poll_interval`ごとに読み直します。
+/// 処理済みの位置は、次のrecordを要求した時点で[`CheckpointStore`]に保存するので、
+/// 途中で止まった場合は最後のrecordsをもう一度受け取ることがあります。
+/// ```no_run
+/// # use dynamodb_utils::*;
+/// # use futures_util::TryStreamExt;
+/// # #[derive(serde::Deserialize)]
+/// # struct User { id: String }
+/// # tokio_test::block_on(async {
+/// let config = sdk_config::load_from_env().await;
+/// let client = Client::from_conf(&config);
+/// let stream_arn = client.latest_stream_arn("users").await?;
+/// let checkpoints = TableCheckpoints::new(client.clone(), "stream_checkpoints");
+/// let consumer = StreamConsumer::new(sdk_streams::Client::new(&config), stream_arn)
+///     .checkpoints(checkpoints);
+/// let mut events = std::pin::pin!(consumer.events::<User>());
+/// while let Some(event) = events.try_next().await? {
+///     if let ChangeEvent::Insert { new_image: Some(user), .. } = event {
+///         println!("{}", user.id);
+///     }
+/// }
+/// # Ok::<(), Error>(())
+/// # });
+/// ```
+#[derive(Debug, Clone)]
+pub struct StreamConsumer<C = MemoryCheckpoints> {
+    streams: aws_sdk_dynamodbstreams::Client,
+    stream_arn: String,
+    checkpoints: C,
+    iterator_type: ShardIteratorType,
+    poll_interval: Duration,
+    limit: Option<i32>,
+}
+
+impl StreamConsumer {
+    /// コンフィグから作ります
+    ///
+    /// dynamodb-localなどを使う場合は、endpointを変えたコンフィグを渡してください。
+    pub fn from_conf<C: Into<aws_sdk_dynamodbstreams::Config>>(
+        conf: C,
+        stream_arn: impl Into<String>,
+    ) -> Self {
+        Self::new(
+            aws_sdk_dynamodbstreams::Client::from_conf(conf.into()),
+            stream_arn,
+        )
+    }
+
+    pub fn new(streams: aws_sdk_dynamodbstreams::Client, stream_arn: impl Into<String>) -> Self {
+        Self {
+            streams,
+            stream_arn: stream_arn.into(),
+            checkpoints: MemoryCheckpoints::default(),
+            iterator_type: ShardIteratorType::TrimHorizon,
+            poll_interval: Duration::from_secs(1),
+            limit: None,
+        }
+    }
+}
+
+impl<C: CheckpointStore> StreamConsumer<C> {
+    /// checkpointの保存先を指定します。デフォルトはメモリ上です。
+    pub fn checkpoints<D: CheckpointStore>(self, checkpoints: D) -> StreamConsumer<D> {
+        StreamConsumer {
+            streams: self.streams,
+            stream_arn: self.stream_arn,
+            checkpoints,
+            iterator_type: self.iterator_type,
+            poll_interval: self.poll_interval,
+            limit: self.limit,
+        }
+    }
+
+    /// checkpointが無いshardを最新の位置から読みます。デフォルトは残っている最初のrecordからです。
+    pub fn start_from_latest(mut self) -> Self {
+        self.iterator_type = ShardIteratorType::Latest;
+        self
+    }
+
+    /// 新しいrecordが無かったときに待つ時間を指定します。デフォルトは1秒です。
+    pub fn poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// 1回のGetRecordsで取得する最大件数を指定します
+    pub fn limit(mut self, limit: i32) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn get_stream_arn(&self) -> &str {
+        &self.stream_arn
+    }
+
+    /// 変更を読み続けます。streamが無効になった場合もエラーになるまで終わりません。
+    pub fn events<T: DeserializeOwned>(
+        &self,
+    ) -> impl Stream<Item = Result<ChangeEvent<T>, Error>> + '_ {
+        stream::try_unfold(ConsumerState::default(), move |mut state| async move {
+            let events = self.next_events(&mut state).await?;
+            Ok::<_, Error>(Some((
+                stream::iter(events.into_iter().map(Ok::<_, Error>)),
+                state,
+            )))
+        })
+        .try_flatten()
+    }
+
+    /// recordsが見つかるまでshardを順に読みます
+    async fn next_events<T: DeserializeOwned>(
+        &self,
+        state: &mut ConsumerState,
+    ) -> Result<Vec<ChangeEvent<T>>, Error> {
+        for (shard_id, sequence_number) in std::mem::take(&mut state.pending) {
+            self.checkpoints
+                .save(&self.stream_arn, &shard_id, &sequence_number)
+                .await?;
+        }
+        loop {
+            let Some(shard_id) = state.queue.pop_front() else {
+                if state.started && !state.found_records {
+                    tokio::time::sleep(self.poll_interval).await;
+                }
+                state.started = true;
+                state.found_records = false;
+                state.queue = readable_shards(&self.shards().await?, &state.finished);
+                continue;
+            };
+            let iterator = match state.iterators.remove(&shard_id) {
+                Some(iterator) => Some(iterator),
+                None => self.shard_iterator(&shard_id).await?,
+            };
+            let Some(iterator) = iterator else {
+                state.finished.insert(shard_id);
+                continue;
+            };
+            let output = match self
+                .streams
+                .get_records()
+                .shard_iterator(iterator)
+                .set_limit(self.limit)
+                .send()
+                .await
+            {
+                Ok(output) => output,
+                // 次の周回で取り直します
+                Err(e)
+                    if e.as_service_error()
+                        .is_some_and(|e| e.is_expired_iterator_exception()) =>
+                {
+                    continue
+                }
+                Err(e) => return Err(Error::from_streams_error(e)),
+            };
+            match output.next_shard_iterator {
+                Some(next) => {
+                    state.iterators.insert(shard_id.clone(), next);
+                }
+                None => {
+                    state.finished.insert(shard_id.clone());
+                }
+            }
+            let events = state.accept(&shard_id, output.records.unwrap_or_default())?;
+            if events.is_empty() {
+                continue;
+            }
+            return Ok(events);
+        }
+    }
+
+    /// streamのすべてのshard
+    async fn shards(&self) -> Result<Vec<Shard>, Error> {
+        let mut shards = vec![];
+        let mut exclusive_start_shard_id = None;
+        loop {
+            let description = self
+                .streams
+                .describe_stream()
+                .stream_arn(&self.stream_arn)
+                .set_exclusive_start_shard_id(exclusive_start_shard_id)
+                .send()
+                .await
+                .map_err(Error::from_streams_error)?
+                .stream_description;
+            let Some(description) = description else {
+                return Ok(shards);
+            };
+            shards.extend(description.shards.unwrap_or_default());
+            exclusive_start_shard_id = description.last_evaluated_shard_id;
+            if exclusive_start_shard_id.is_none() {
+                return Ok(shards);
+            }
+        }
+    }
+
+    /// checkpointがあればその続きから読むiterator
+    async fn shard_iterator(&self, shard_id: &str) -> Result<Option<String>, Error> {
+        let checkpoint = self.checkpoints.load(&self.stream_arn, shard_id).await?;
+        let iterator_type = match checkpoint {
+            Some(_) => ShardIteratorType::AfterSequenceNumber,
+            None => self.iterator_type.clone(),
+        };
+        Ok(self
+            .streams
+            .get_shard_iterator()
+            .stream_arn(&self.stream_arn)
+            .shard_id(shard_id)
+            .shard_iterator_type(iterator_type)
+            .set_sequence_number(checkpoint)
+            .send()
+            .await
+            .map_err(Error::from_streams_error)?
+            .shard_iterator)
+    }
+}
+
+#[derive(Debug, Default)]
+struct ConsumerState {
+    /// この周回で読むshard
+    queue: VecDeque<String>,
+    iterators: HashMap<String, String>,
+    /// 閉じていて、最後まで読んだshard
+    finished: HashSet<String>,
+    /// shardごとの、返したrecordsのうちまだcheckpointに保存していない位置
+    pending: HashMap<String, String>,
+    started: bool,
+    found_records: bool,
+}
+
+impl ConsumerState {
+    /// recordsを[`ChangeEvent`]にします
+    ///
+    /// すべて変換できた場合だけ、checkpointに保存する位置を進めます。
+    fn accept<T: DeserializeOwned>(
+        &mut self,
+        shard_id: &str,
+        records: Vec<Record>,
+    ) -> Result<Vec<ChangeEvent<T>>, Error> {
+        let Some(last) = records
+            .last()
+            .and_then(|record| record.dynamodb.as_ref())
+            .and_then(|change| change.sequence_number.clone())
+        else {
+            return Ok(vec![]);
+        };
+        let events = records
+            .into_iter()
+            .filter_map(|record| ChangeEvent::from_record(record).transpose())
+            .collect::<Result<Vec<_>, _>>()?;
+        self.found_records = true;
+        self.pending.insert(shard_id.to_owned(), last);
+        Ok(events)
+    }
+}
+
+/// 読み始められるshard
+///
+/// 分割されたshardは、親を最後まで読んでから読みます。
+/// 親が一覧に無い場合は、期限切れで消えたものとして読み始めます。
+fn readable_shards(shards: &[Shard], finished: &HashSet<String>) -> VecDeque<String> {
+    let listed = shards
+        .iter()
+        .filter_map(|shard| shard.shard_id.as_deref())
+        .collect::<HashSet<_>>();
+    shards
+        .iter()
+        .filter(|shard| {
+            shard
+                .parent_shard_id
+                .as_deref()
+                .is_none_or(|parent| finished.contains(parent) || !listed.contains(parent))
+        })
+        .filter_map(|shard| shard.shard_id.clone())
+        .filter(|shard_id| !finished.contains(shard_id))
+        .collect()
+}
+
+impl<A: Autoscale> Client<A> {
+    /// テーブルの最新のstreamのARN
+    pub async fn latest_stream_arn(&self, table_name: impl Into<String>) -> Result<String, Error> {
+        self.describe_table(table_name)
+            .await?
+            .latest_stream_arn
+            .ok_or(Error::NotFound)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aws_sdk_dynamodbstreams::types::{AttributeValue, StreamRecord};
+
+    #[derive(Debug, serde::Deserialize)]
+    struct User {
+        #[allow(dead_code)]
+        name: String,
+    }
+
+    fn record(
+        operation: Option<OperationType>,
+        sequence_number: &str,
+        new_image: Option<(&str, AttributeValue)>,
+    ) -> Record {
+        let change = StreamRecord::builder()
+            .keys("id", AttributeValue::S("u1".into()))
+            .sequence_number(sequence_number)
+            .set_new_image(
+                new_image.map(|(name, value)| HashMap::from([(name.to_owned(), value)])),
+            );
+        Record::builder()
+            .set_event_name(operation)
+            .dynamodb(change.build())
+            .build()
+    }
+
+    fn shard(shard_id: &str, parent_shard_id: Option<&str>) -> Shard {
+        Shard::builder()
+            .shard_id(shard_id)
+            .set_parent_shard_id(parent_shard_id.map(Into::into))
+            .build()
+    }
+
+    #[test]
+    fn accept_keeps_checkpoint_when_deserialize_fails() {
+        let mut state = ConsumerState::default();
+        let records = vec![
+            record(
+                Some(OperationType::Insert),
+                "1",
+                Some(("name", AttributeValue::S("alice".into()))),
+            ),
+            record(
+                Some(OperationType::Insert),
+                "2",
+                Some(("name", AttributeValue::N("1".into()))),
+            ),
+        ];
+        assert!(state.accept::<User>("shard-1", records).is_err());
+        assert!(state.pending.is_empty());
+        assert!(!state.found_records);
+    }
+
+    #[test]
+    fn accept_keeps_checkpoint_per_shard() {
+        let mut state = ConsumerState::default();
+        // imageの無いeventも位置は進めます
+        let events = state
+            .accept::<User>("shard-1", vec![record(None, "10", None)])
+            .unwrap();
+        assert!(events.is_empty());
+        let events = state
+            .accept::<User>(
+                "shard-2",
+                vec![record(
+                    Some(OperationType::Insert),
+                    "20",
+                    Some(("name", AttributeValue::S("bob".into()))),
+                )],
+            )
+            .unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(
+            state.pending,
+            HashMap::from([
+                ("shard-1".to_owned(), "10".to_owned()),
+                ("shard-2".to_owned(), "20".to_owned()),
+            ])
+        );
+    }
+
+    #[test]
+    fn accept_ignores_empty_records() {
+        let mut state = ConsumerState::default();
+        let events = state.accept::<User>("shard-1", vec![]).unwrap();
+        assert!(events.is_empty());
+        assert!(state.pending.is_empty());
+    }
+
+    #[test]
+    fn readable_shards_waits_for_parent_after_split() {
+        let shards = [
+            shard("parent", None),
+            shard("child-1", Some("parent")),
+            shard("child-2", Some("parent")),
+        ];
+        let mut finished = HashSet::new();
+        assert_eq!(readable_shards(&shards, &finished), ["parent"]);
+
+        finished.insert("parent".to_owned());
+        assert_eq!(readable_shards(&shards, &finished), ["child-1", "child-2"]);
+    }
+
+    #[test]
+    fn readable_shards_starts_child_of_trimmed_parent() {
+        let shards = [shard("child", Some("trimmed"))];
+        assert_eq!(readable_shards(&shards, &HashSet::new()), ["child"]);
+    }
+}
@@ -0,0 +1,328 @@
+use crate::{
+    sdk::{
+        operation::{
+            delete_item::DeleteItemOutput, put_item::PutItemOutput, update_item::UpdateItemOutput,
+        },
+        types::AttributeValue,
+    },
+    Autoscale, Client, Error, IntoValue, Key, Query, Scan, Update,
+};
+use futures_util::TryStream;
+use serde::{de::DeserializeOwned, Serialize};
+use std::marker::PhantomData;
+
+/// テーブルのkeyの項目名
+///
+/// `"id"`や`("user_id", "created_at")`から変換できます。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeySchema {
+    pub partition_key: String,
+    pub sort_key: Option<String>,
+}
+
+impl KeySchema {
+    /// partition keyのみ
+    pub fn new(partition_key: impl Into<String>) -> Self {
+        Self {
+            partition_key: partition_key.into(),
+            sort_key: None,
+        }
+    }
+
+    /// sort keyを設定します
+    pub fn with_sort(mut self, sort_key: impl Into<String>) -> Self {
+        self.sort_key = Some(sort_key.into());
+        self
+    }
+
+    /// keyの値と組み合わせて[`Key`]にします
+    ///
+    /// sort keyの値の有無が定義と合わない場合は[`Error::InvalidKey`]になります。
+    /// ```
+    /// # use dynamodb_utils::*;
+    /// let schema = KeySchema::from(("room_id", "posted_at"));
+    /// assert!(schema.key(("room1", 1)).is_ok());
+    /// assert!(matches!(schema.key("room1"), Err(Error::InvalidKey(_))));
+    /// ```
+    pub fn key(&self, value: impl Into<KeyValue>) -> Result<Key, Error> {
+        let value = value.into();
+        let key = Key::new(&self.partition_key, value.partition);
+        match (&self.sort_key, value.sort) {
+            (Some(sort_key), Some(sort)) => Ok(key.with_sort(sort_key, sort)),
+            (None, None) => Ok(key),
+            (Some(sort_key), None) => Err(Error::InvalidKey(format!(
+                "missing a value for sort key {sort_key}"
+            ))),
+            (None, Some(_)) => Err(Error::InvalidKey(format!(
+                "a sort key value was given, but {} has no sort key",
+                self.partition_key
+            ))),
+        }
+    }
+}
+
+impl From<&str> for KeySchema {
+    fn from(value: &str) -> Self {
+        Self::new(value)
+    }
+}
+
+impl From<String> for KeySchema {
+    fn from(value: String) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<P: Into<String>, S: Into<String>> From<(P, S)> for KeySchema {
+    fn from((partition_key, sort_key): (P, S)) -> Self {
+        Self::new(partition_key).with_sort(sort_key)
+    }
+}
+
+/// 項目名を含まないkeyの値
+///
+/// partition keyの値、または`(partition keyの値, sort keyの値)`から変換できます。
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyValue {
+    partition: AttributeValue,
+    sort: Option<AttributeValue>,
+}
+
+impl<V: IntoValue> From<V> for KeyValue {
+    fn from(value: V) -> Self {
+        Self {
+            partition: value.into_value(),
+            sort: None,
+        }
+    }
+}
+
+impl<P: IntoValue, S: IntoValue> From<(P, S)> for KeyValue {
+    fn from((partition, sort): (P, S)) -> Self {
+        Self {
+            partition: partition.into_value(),
+            sort: Some(sort.into_value()),
+        }
+    }
+}
+
+impl<A> Client<A> {
+    /// 全てのテーブル名の前に付けるprefixを指定します
+    ///
+    /// prefixが付くのは次のものだけです。
+    ///
+    /// - [`table`](`Client::table`)、[`entity_table`](`Client::entity_table`)で作った[`Table`]
+    /// - [`get`](`Client::get`)、[`put`](`Client::put`)、[`delete`](`Client::delete`)、
+    ///   [`delete_entity`](`Client::delete_entity`)、[`create_entity_table`](`Client::create_entity_table`)
+    ///
+    /// `get_item`や`*_raw`など、テーブル名を引数で受け取るメソッドには付きません。
+    pub fn with_table_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.table_prefix = Some(prefix.into());
+        self
+    }
+
+    /// 環境変数`key`の値を、全てのテーブル名の前に付けるprefixにします
+    ///
+    /// 環境変数が無い場合はprefixを付けません。
+    /// ```no_run
+    /// # use dynamodb_utils::*;
+    /// # tokio_test::block_on(async {
+    /// // TABLE_PREFIX=dev- なら dev-users テーブルを使う
+    /// let client = Client::from_env().await.with_table_prefix_from_env("TABLE_PREFIX");
+    /// # #[derive(serde::Deserialize)]
+    /// # struct User {}
+    /// let users = client.table::<User>("users", "id");
+    /// # });
+    /// ```
+    pub fn with_table_prefix_from_env(self, key: &str) -> Self {
+        match std::env::var(key) {
+            Ok(prefix) => self.with_table_prefix(prefix),
+            Err(_) => self,
+        }
+    }
+
+    /// prefixを付けたテーブル名
+    pub(crate) fn prefixed_table_name(&self, table_name: &str) -> String {
+        format!(
+            "{}{}",
+            self.table_prefix.as_deref().unwrap_or_default(),
+            table_name
+        )
+    }
+}
+
+impl<A: Autoscale> Client<A> {
+    /// テーブル名、keyの項目名、itemの型を固定した[`Table`]を作ります
+    pub fn table<T>(
+        &self,
+        table_name: impl AsRef<str>,
+        key_schema: impl Into<KeySchema>,
+    ) -> Table<T, A> {
+        Table {
+            client: self.clone(),
+            table_name: self.prefixed_table_name(table_name.as_ref()),
+            key_schema: key_schema.into(),
+            _item: PhantomData,
+        }
+    }
+}
+
+/// テーブルを固定した状態で使う`Client`.
+/// ```no_run
+/// # use dynamodb_utils::*;
+/// # #[derive(serde::Serialize, serde::Deserialize)]
+/// # struct Message { room_id: String, posted_at: i64 }
+/// # tokio_test::block_on(async {
+/// let client = Client::from_env().await;
+/// let messages = client.table::<Message>("messages", ("room_id", "posted_at"));
+/// let message = messages.get(("room1", 1716877593)).await?;
+/// messages.put(&message).await?;
+/// messages.delete(("room1", 1716877593)).await?;
+/// # Ok::<(), Error>(())
+/// # });
+/// ```
+#[derive(Debug)]
+pub struct Table<T, A = ()> {
+    client: Client<A>,
+    table_name: String,
+    key_schema: KeySchema,
+    _item: PhantomData<fn() -> T>,
+}
+
+impl<T, A: Clone> Clone for Table<T, A> {
+    fn clone(&self) -> Self {
+        Self {
+            client: self.client.clone(),
+            table_name: self.table_name.clone(),
+            key_schema: self.key_schema.clone(),
+            _item: PhantomData,
+        }
+    }
+}
+
+impl<T, A> Table<T, A> {
+    /// テーブル無しのclientを取得します
+    pub fn no_table_client(&self) -> &Client<A> {
+        &self.client
+    }
+
+    /// prefixを含んだテーブル名
+    pub fn get_table_name(&self) -> &str {
+        &self.table_name
+    }
+
+    pub fn key_schema(&self) -> &KeySchema {
+        &self.key_schema
+    }
+
+    /// keyの値から[`Key`]を作ります
+    pub fn key(&self, value: impl Into<KeyValue>) -> Result<Key, Error> {
+        self.key_schema.key(value)
+    }
+
+    /// partition keyの値を指定した[`Query`]を作ります
+    pub fn partition(&self, value: impl IntoValue) -> Query {
+        Query::new(&self.key_schema.partition_key, value)
+    }
+}
+
+impl<T, A: Autoscale> Table<T, A> {
+    /// itemを取得します
+    pub async fn get(&self, key: impl Into<KeyValue>) -> Result<T, Error>
+    where
+        T: DeserializeOwned,
+    {
+        self.client.get_item(&self.table_name, self.key(key)?).await
+    }
+
+    /// itemを登録します
+    pub async fn put(&self, item: &T) -> Result<PutItemOutput, Error>
+    where
+        T: Serialize,
+    {
+        self.client.put_item(&self.table_name, item).await
+    }
+
+    /// itemを削除します
+    pub async fn delete(&self, key: impl Into<KeyValue>) -> Result<DeleteItemOutput, Error> {
+        self.client
+            .delete_item(&self.table_name, self.key(key)?)
+            .await
+    }
+
+    /// itemを更新します
+    pub async fn update(
+        &self,
+        key: impl Into<KeyValue>,
+        update: Update,
+    ) -> Result<UpdateItemOutput, Error> {
+        self.client
+            .update_item(&self.table_name, self.key(key)?, update)
+            .await
+    }
+
+    /// queryを掛けます
+    ///
+    /// [`partition`](`Self::partition`)で作った[`Query`]を使うと、keyの項目名を省略できます。
+    pub fn query(&self, query: Query) -> impl TryStream<Ok = T, Error = Error>
+    where
+        T: DeserializeOwned,
+    {
+        self.client.query_item(self.table_name.clone(), query)
+    }
+
+    /// scanを掛けます
+    pub fn scan(&self) -> impl TryStream<Ok = T, Error = Error>
+    where
+        T: DeserializeOwned,
+    {
+        self.client.scan_item(self.table_name.clone())
+    }
+
+    /// 条件を指定してscanを掛けます
+    pub fn scan_with(&self, scan: Scan) -> impl TryStream<Ok = T, Error = Error>
+    where
+        T: DeserializeOwned,
+    {
+        self.client.scan_item_with(self.table_name.clone(), scan)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AdaptiveCapacityConfig;
+
+    #[derive(Debug, serde::Deserialize)]
+    struct Message {}
+
+    #[test]
+    fn key_rejects_mismatched_sort_value() {
+        let schema = KeySchema::new("id");
+        assert_eq!(schema.key("a").unwrap(), Key::new("id", "a"));
+        assert!(matches!(schema.key(("a", 1)), Err(Error::InvalidKey(_))));
+
+        let schema = KeySchema::from(("room_id", "posted_at"));
+        assert_eq!(
+            schema.key(("room1", 1)).unwrap(),
+            Key::new("room_id", "room1").with_sort("posted_at", 1)
+        );
+        assert!(matches!(schema.key("room1"), Err(Error::InvalidKey(_))));
+    }
+
+    #[test]
+    fn table_prefix_chains_after_autoscale() {
+        tokio_test::block_on(async {
+            let client = Client::mock()
+                .with_adaptive_capacity(AdaptiveCapacityConfig::default())
+                .with_table_prefix("dev_");
+            let messages = client.table::<Message>("messages", ("room_id", "posted_at"));
+            assert_eq!(messages.get_table_name(), "dev_messages");
+            // keyが足りない場合は送る前にエラーになります
+            assert!(matches!(
+                messages.get("room1").await,
+                Err(Error::InvalidKey(_))
+            ));
+        });
+    }
+}
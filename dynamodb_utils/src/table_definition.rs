@@ -0,0 +1,708 @@
+use crate::{
+    sdk::{
+        operation::create_table::builders::CreateTableFluentBuilder,
+        types::{
+            AttributeDefinition, BillingMode, GlobalSecondaryIndex,
+            GlobalSecondaryIndexDescription, IndexStatus, KeySchemaElement, KeyType,
+            LocalSecondaryIndex, LocalSecondaryIndexDescription, ProjectionType,
+            ProvisionedThroughput, ScalarAttributeType, SseSpecification, SseType,
+            StreamSpecification, StreamViewType, TableDescription, TableStatus, Tag,
+        },
+    },
+    DynamoEntity, Error, TableType,
+};
+use std::collections::HashMap;
+
+/// テーブルの定義
+///
+/// [`Client::create_table_with`](`crate::Client::create_table_with`)で使います。
+/// ```
+/// # use dynamodb_utils::{sdk::types::{ScalarAttributeType, StreamViewType}, *};
+/// let definition = TableDefinition::new("orders", "user_id", ScalarAttributeType::S)
+///     .sort_key("created_at", ScalarAttributeType::N)
+///     .global_index(
+///         GlobalIndex::new("by_status", "status", ScalarAttributeType::S)
+///             .sort_key("created_at", ScalarAttributeType::N)
+///             .projection(Projection::Include(vec!["total".into()])),
+///     )
+///     .local_index(LocalIndex::new("by_total", "total", ScalarAttributeType::N))
+///     .stream(StreamViewType::NewAndOldImages)
+///     .ttl("expires_at")
+///     .sse_kms()
+///     .tag("env", "dev");
+/// ```
+#[derive(Debug, Clone)]
+pub struct TableDefinition {
+    pub(crate) table_name: String,
+    pub(crate) partition_key: (String, ScalarAttributeType),
+    pub(crate) sort_key: Option<(String, ScalarAttributeType)>,
+    pub(crate) table_type: TableType,
+    pub(crate) global_indexes: Vec<GlobalIndex>,
+    pub(crate) local_indexes: Vec<LocalIndex>,
+    pub(crate) stream: Option<StreamViewType>,
+    pub(crate) ttl: Option<String>,
+    pub(crate) sse: Option<Option<String>>,
+    pub(crate) tags: Vec<(String, String)>,
+}
+
+impl TableDefinition {
+    /// partition keyを指定して作ります。デフォルトはオンデマンドです。
+    pub fn new(
+        table_name: impl Into<String>,
+        partition_key: impl Into<String>,
+        key_type: ScalarAttributeType,
+    ) -> Self {
+        Self {
+            table_name: table_name.into(),
+            partition_key: (partition_key.into(), key_type),
+            sort_key: None,
+            table_type: TableType::OnDemand,
+            global_indexes: vec![],
+            local_indexes: vec![],
+            stream: None,
+            ttl: None,
+            sse: None,
+            tags: vec![],
+        }
+    }
+
+    /// `E`のテーブル名とkeyから作ります
+    ///
+    /// テーブル名にprefixは付きません。
+    pub fn from_entity<E: DynamoEntity>() -> Self {
+        let key_schema = E::key_schema();
+        let (key_type, sort_key_type) = E::key_attribute_types();
+        Self {
+            sort_key: key_schema.sort_key.zip(sort_key_type),
+            ..Self::new(E::TABLE_NAME, key_schema.partition_key, key_type)
+        }
+    }
+
+    /// sort keyを指定します
+    pub fn sort_key(mut self, name: impl Into<String>, key_type: ScalarAttributeType) -> Self {
+        self.sort_key = Some((name.into(), key_type));
+        self
+    }
+
+    /// キャパシティの設定を指定します
+    pub fn table_type(mut self, table_type: TableType) -> Self {
+        self.table_type = table_type;
+        self
+    }
+
+    /// GSIを追加します
+    pub fn global_index(mut self, index: GlobalIndex) -> Self {
+        self.global_indexes.push(index);
+        self
+    }
+
+    /// LSIを追加します
+    pub fn local_index(mut self, index: LocalIndex) -> Self {
+        self.local_indexes.push(index);
+        self
+    }
+
+    /// DynamoDB Streamsを有効にします
+    pub fn stream(mut self, view_type: StreamViewType) -> Self {
+        self.stream = Some(view_type);
+        self
+    }
+
+    /// TTLに使う項目を指定します
+    ///
+    /// TTLはテーブルがACTIVEになってから有効にします。
+    pub fn ttl(mut self, attribute: impl Into<String>) -> Self {
+        self.ttl = Some(attribute.into());
+        self
+    }
+
+    /// AWS管理のKMSキーで暗号化します
+    pub fn sse_kms(mut self) -> Self {
+        self.sse = Some(None);
+        self
+    }
+
+    /// 指定したKMSキーで暗号化します
+    pub fn sse_kms_key(mut self, key_id: impl Into<String>) -> Self {
+        self.sse = Some(Some(key_id.into()));
+        self
+    }
+
+    /// タグを追加します
+    pub fn tag(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.tags.push((key.into(), value.into()));
+        self
+    }
+
+    /// テーブル名
+    pub fn get_table_name(&self) -> &str {
+        &self.table_name
+    }
+
+    /// TTLに使う項目名
+    pub fn get_ttl(&self) -> Option<&str> {
+        self.ttl.as_deref()
+    }
+
+    /// テーブルとindexのkeyの項目の定義。同じ項目は1つにまとめます。
+    fn attribute_definitions(&self) -> Result<Vec<AttributeDefinition>, Error> {
+        let keys = std::iter::once(&self.partition_key)
+            .chain(&self.sort_key)
+            .chain(
+                self.global_indexes
+                    .iter()
+                    .flat_map(|index| std::iter::once(&index.partition_key).chain(&index.sort_key)),
+            )
+            .chain(self.local_indexes.iter().map(|index| &index.sort_key));
+        let mut definitions = Vec::<AttributeDefinition>::new();
+        for (name, key_type) in keys {
+            if definitions
+                .iter()
+                .any(|definition| definition.attribute_name == *name)
+            {
+                continue;
+            }
+            definitions.push(
+                AttributeDefinition::builder()
+                    .attribute_name(name)
+                    .attribute_type(key_type.clone())
+                    .build()?,
+            );
+        }
+        Ok(definitions)
+    }
+
+    /// SDKのbuilderへ定義を設定します
+    pub(crate) fn apply(
+        &self,
+        builder: CreateTableFluentBuilder,
+    ) -> Result<CreateTableFluentBuilder, Error> {
+        let attribute_definitions = self.attribute_definitions()?;
+        let table_throughput = match self.table_type {
+            TableType::OnDemand => None,
+            TableType::Provisioned(read, write) => Some((read, write)),
+        };
+        let global_indexes = self
+            .global_indexes
+            .iter()
+            .map(|index| index.build(table_throughput))
+            .collect::<Result<Vec<_>, _>>()?;
+        let local_indexes = self
+            .local_indexes
+            .iter()
+            .map(|index| index.build(&self.partition_key))
+            .collect::<Result<Vec<_>, _>>()?;
+        let builder = builder
+            .table_name(&self.table_name)
+            .set_key_schema(Some(key_schema(&self.partition_key, &self.sort_key)?))
+            .set_attribute_definitions(Some(attribute_definitions))
+            .set_global_secondary_indexes(Some(global_indexes).filter(|it| !it.is_empty()))
+            .set_local_secondary_indexes(Some(local_indexes).filter(|it| !it.is_empty()))
+            .set_stream_specification(self.stream_specification()?)
+            .set_sse_specification(self.sse.clone().map(|key_id| {
+                SseSpecification::builder()
+                    .enabled(true)
+                    .sse_type(SseType::Kms)
+                    .set_kms_master_key_id(key_id)
+                    .build()
+            }))
+            .set_tags(
+                Some(
+                    self.tags
+                        .iter()
+                        .map(|(key, value)| Tag::builder().key(key).value(value).build())
+                        .collect::<Result<Vec<_>, _>>()?,
+                )
+                .filter(|it| !it.is_empty()),
+            );
+        Ok(match table_throughput {
+            None => builder.billing_mode(BillingMode::PayPerRequest),
+            Some((read, write)) => builder
+                .billing_mode(BillingMode::Provisioned)
+                .provisioned_throughput(provisioned_throughput(read, write)?),
+        })
+    }
+
+    fn stream_specification(&self) -> Result<Option<StreamSpecification>, Error> {
+        Ok(self
+            .stream
+            .clone()
+            .map(|view_type| {
+                StreamSpecification::builder()
+                    .stream_enabled(true)
+                    .stream_view_type(view_type)
+                    .build()
+            })
+            .transpose()?)
+    }
+
+    /// この定義で作られたテーブルの情報
+    pub(crate) fn description(&self, status: TableStatus) -> Result<TableDescription, Error> {
+        let global_indexes = self
+            .global_indexes
+            .iter()
+            .map(|index| {
+                Ok(GlobalSecondaryIndexDescription::builder()
+                    .index_name(&index.index_name)
+                    .set_key_schema(Some(key_schema(&index.partition_key, &index.sort_key)?))
+                    .projection(index.projection.build())
+                    .index_status(IndexStatus::Active)
+                    .build())
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        let local_indexes = self
+            .local_indexes
+            .iter()
+            .map(|index| {
+                Ok(LocalSecondaryIndexDescription::builder()
+                    .index_name(&index.index_name)
+                    .set_key_schema(Some(key_schema(
+                        &self.partition_key,
+                        &Some(index.sort_key.clone()),
+                    )?))
+                    .projection(index.projection.build())
+                    .build())
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        Ok(TableDescription::builder()
+            .table_name(&self.table_name)
+            .table_status(status)
+            .set_key_schema(Some(key_schema(&self.partition_key, &self.sort_key)?))
+            .set_attribute_definitions(Some(self.attribute_definitions()?))
+            .set_global_secondary_indexes(Some(global_indexes).filter(|it| !it.is_empty()))
+            .set_local_secondary_indexes(Some(local_indexes).filter(|it| !it.is_empty()))
+            .set_stream_specification(self.stream_specification()?)
+            .build())
+    }
+
+    /// 既存のテーブルとの、keyとindexとstreamの違い
+    pub(crate) fn drift(&self, table: &TableDescription) -> Vec<TableDrift> {
+        let attributes = table.attribute_definitions();
+        let mut drift = vec![];
+
+        let expected = std::iter::once(self.partition_key.clone())
+            .chain(self.sort_key.clone())
+            .collect::<Vec<_>>();
+        let actual = described_keys(table.key_schema(), attributes);
+        if expected != actual {
+            drift.push(TableDrift::KeySchema { expected, actual });
+        }
+
+        let actual_indexes = table
+            .global_secondary_indexes()
+            .iter()
+            .map(|index| {
+                (
+                    index.index_name().unwrap_or_default(),
+                    (
+                        described_keys(index.key_schema(), attributes),
+                        Projection::from_description(index.projection()),
+                    ),
+                )
+            })
+            .collect::<HashMap<_, _>>();
+        let expected_indexes = self
+            .global_indexes
+            .iter()
+            .map(|index| {
+                (
+                    index.index_name.as_str(),
+                    (
+                        std::iter::once(index.partition_key.clone())
+                            .chain(index.sort_key.clone())
+                            .collect::<Vec<_>>(),
+                        index.projection.normalized(),
+                    ),
+                )
+            })
+            .collect::<HashMap<_, _>>();
+        drift.extend(index_drift(
+            &expected_indexes,
+            &actual_indexes,
+            TableDrift::MissingGlobalIndex,
+            TableDrift::UnexpectedGlobalIndex,
+            TableDrift::GlobalIndexChanged,
+        ));
+
+        let actual_indexes = table
+            .local_secondary_indexes()
+            .iter()
+            .map(|index| {
+                (
+                    index.index_name().unwrap_or_default(),
+                    (
+                        described_keys(index.key_schema(), attributes),
+                        Projection::from_description(index.projection()),
+                    ),
+                )
+            })
+            .collect::<HashMap<_, _>>();
+        let expected_indexes = self
+            .local_indexes
+            .iter()
+            .map(|index| {
+                (
+                    index.index_name.as_str(),
+                    (
+                        vec![self.partition_key.clone(), index.sort_key.clone()],
+                        index.projection.normalized(),
+                    ),
+                )
+            })
+            .collect::<HashMap<_, _>>();
+        drift.extend(index_drift(
+            &expected_indexes,
+            &actual_indexes,
+            TableDrift::MissingLocalIndex,
+            TableDrift::UnexpectedLocalIndex,
+            TableDrift::LocalIndexChanged,
+        ));
+
+        let actual_stream = table
+            .stream_specification()
+            .filter(|stream| stream.stream_enabled)
+            .and_then(|stream| stream.stream_view_type.clone());
+        if self.stream != actual_stream {
+            drift.push(TableDrift::Stream {
+                expected: self.stream.clone(),
+                actual: actual_stream,
+            });
+        }
+        drift
+    }
+}
+
+/// [`Client::ensure_table`](`crate::Client::ensure_table`)で見つかった、既存のテーブルと定義の違い
+#[derive(Debug, Clone, PartialEq)]
+pub enum TableDrift {
+    /// テーブルのkeyの項目名か型が違う
+    KeySchema {
+        expected: Vec<(String, ScalarAttributeType)>,
+        actual: Vec<(String, ScalarAttributeType)>,
+    },
+    /// 定義にあるGSIが無い
+    MissingGlobalIndex(String),
+    /// 定義に無いGSIがある
+    UnexpectedGlobalIndex(String),
+    /// GSIのkeyかprojectionが違う
+    GlobalIndexChanged(String),
+    /// 定義にあるLSIが無い
+    MissingLocalIndex(String),
+    /// 定義に無いLSIがある
+    UnexpectedLocalIndex(String),
+    /// LSIのkeyかprojectionが違う
+    LocalIndexChanged(String),
+    /// streamの設定が違う
+    Stream {
+        expected: Option<StreamViewType>,
+        actual: Option<StreamViewType>,
+    },
+    /// TTLの項目が違う。無効の場合は`None`です。
+    Ttl {
+        expected: Option<String>,
+        actual: Option<String>,
+    },
+}
+
+type IndexShape = (Vec<(String, ScalarAttributeType)>, Projection);
+
+fn index_drift(
+    expected: &HashMap<&str, IndexShape>,
+    actual: &HashMap<&str, IndexShape>,
+    missing: fn(String) -> TableDrift,
+    unexpected: fn(String) -> TableDrift,
+    changed: fn(String) -> TableDrift,
+) -> Vec<TableDrift> {
+    let mut drift = vec![];
+    for (name, shape) in expected {
+        match actual.get(name) {
+            None => drift.push((*name, missing)),
+            Some(actual) if actual != shape => drift.push((*name, changed)),
+            Some(_) => {}
+        }
+    }
+    for name in actual.keys().filter(|name| !expected.contains_key(*name)) {
+        drift.push((*name, unexpected));
+    }
+    // HashMapの順番によらないように、index名の順に並べます
+    drift.sort_by_key(|(name, _)| *name);
+    drift
+        .into_iter()
+        .map(|(name, drift)| drift(name.to_owned()))
+        .collect()
+}
+
+/// DescribeTableのkeyを、項目の型と合わせてpartition key、sort keyの順に並べます
+fn described_keys(
+    key_schema: &[KeySchemaElement],
+    attributes: &[AttributeDefinition],
+) -> Vec<(String, ScalarAttributeType)> {
+    let mut keys = key_schema
+        .iter()
+        .filter_map(|element| {
+            let attribute_type = attributes
+                .iter()
+                .find(|attribute| attribute.attribute_name == element.attribute_name)?
+                .attribute_type
+                .clone();
+            Some((
+                element.key_type == KeyType::Range,
+                (element.attribute_name.clone(), attribute_type),
+            ))
+        })
+        .collect::<Vec<_>>();
+    keys.sort_by_key(|(is_range, _)| *is_range);
+    keys.into_iter().map(|(_, key)| key).collect()
+}
+
+/// indexに含める項目
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Projection {
+    /// 全ての項目
+    #[default]
+    All,
+    /// keyのみ
+    KeysOnly,
+    /// keyと指定した項目
+    Include(Vec<String>),
+}
+
+impl Projection {
+    fn build(&self) -> crate::sdk::types::Projection {
+        let builder = crate::sdk::types::Projection::builder();
+        match self {
+            Projection::All => builder.projection_type(ProjectionType::All),
+            Projection::KeysOnly => builder.projection_type(ProjectionType::KeysOnly),
+            Projection::Include(attributes) => builder
+                .projection_type(ProjectionType::Include)
+                .set_non_key_attributes(Some(attributes.clone())),
+        }
+        .build()
+    }
+
+    /// 比較できるように、項目名を並べ替えます
+    fn normalized(&self) -> Self {
+        match self {
+            Projection::Include(attributes) => {
+                let mut attributes = attributes.clone();
+                attributes.sort();
+                Projection::Include(attributes)
+            }
+            projection => projection.clone(),
+        }
+    }
+
+    fn from_description(projection: Option<&crate::sdk::types::Projection>) -> Self {
+        match projection.and_then(|projection| projection.projection_type()) {
+            Some(ProjectionType::KeysOnly) => Projection::KeysOnly,
+            Some(ProjectionType::Include) => Projection::Include(
+                projection
+                    .map(|projection| projection.non_key_attributes().to_vec())
+                    .unwrap_or_default(),
+            )
+            .normalized(),
+            _ => Projection::All,
+        }
+    }
+}
+
+/// GSIの定義
+#[derive(Debug, Clone)]
+pub struct GlobalIndex {
+    pub(crate) index_name: String,
+    pub(crate) partition_key: (String, ScalarAttributeType),
+    pub(crate) sort_key: Option<(String, ScalarAttributeType)>,
+    pub(crate) projection: Projection,
+    pub(crate) throughput: Option<(i64, i64)>,
+}
+
+impl GlobalIndex {
+    /// partition keyを指定して作ります。デフォルトでは全ての項目を含めます。
+    pub fn new(
+        index_name: impl Into<String>,
+        partition_key: impl Into<String>,
+        key_type: ScalarAttributeType,
+    ) -> Self {
+        Self {
+            index_name: index_name.into(),
+            partition_key: (partition_key.into(), key_type),
+            sort_key: None,
+            projection: Projection::All,
+            throughput: None,
+        }
+    }
+
+    /// sort keyを指定します
+    pub fn sort_key(mut self, name: impl Into<String>, key_type: ScalarAttributeType) -> Self {
+        self.sort_key = Some((name.into(), key_type));
+        self
+    }
+
+    /// indexに含める項目を指定します
+    pub fn projection(mut self, projection: Projection) -> Self {
+        self.projection = projection;
+        self
+    }
+
+    /// プロビジョニングのテーブルで、indexのキャパシティを指定します
+    ///
+    /// 指定しない場合はテーブルと同じにします。
+    pub fn provisioned(mut self, read_capacity: i64, write_capacity: i64) -> Self {
+        self.throughput = Some((read_capacity, write_capacity));
+        self
+    }
+
+    fn build(&self, table_throughput: Option<(i64, i64)>) -> Result<GlobalSecondaryIndex, Error> {
+        Ok(GlobalSecondaryIndex::builder()
+            .index_name(&self.index_name)
+            .set_key_schema(Some(key_schema(&self.partition_key, &self.sort_key)?))
+            .projection(self.projection.build())
+            .set_provisioned_throughput(
+                table_throughput
+                    .map(|table| self.throughput.unwrap_or(table))
+                    .map(|(read, write)| provisioned_throughput(read, write))
+                    .transpose()?,
+            )
+            .build()?)
+    }
+}
+
+/// LSIの定義
+///
+/// partition keyはテーブルと同じになります。
+#[derive(Debug, Clone)]
+pub struct LocalIndex {
+    pub(crate) index_name: String,
+    pub(crate) sort_key: (String, ScalarAttributeType),
+    pub(crate) projection: Projection,
+}
+
+impl LocalIndex {
+    /// sort keyを指定して作ります。デフォルトでは全ての項目を含めます。
+    pub fn new(
+        index_name: impl Into<String>,
+        sort_key: impl Into<String>,
+        key_type: ScalarAttributeType,
+    ) -> Self {
+        Self {
+            index_name: index_name.into(),
+            sort_key: (sort_key.into(), key_type),
+            projection: Projection::All,
+        }
+    }
+
+    /// indexに含める項目を指定します
+    pub fn projection(mut self, projection: Projection) -> Self {
+        self.projection = projection;
+        self
+    }
+
+    fn build(
+        &self,
+        partition_key: &(String, ScalarAttributeType),
+    ) -> Result<LocalSecondaryIndex, Error> {
+        Ok(LocalSecondaryIndex::builder()
+            .index_name(&self.index_name)
+            .set_key_schema(Some(key_schema(
+                partition_key,
+                &Some(self.sort_key.clone()),
+            )?))
+            .projection(self.projection.build())
+            .build()?)
+    }
+}
+
+fn key_schema(
+    (partition_key, _): &(String, ScalarAttributeType),
+    sort_key: &Option<(String, ScalarAttributeType)>,
+) -> Result<Vec<KeySchemaElement>, Error> {
+    let mut schema = vec![KeySchemaElement::builder()
+        .attribute_name(partition_key)
+        .key_type(KeyType::Hash)
+        .build()?];
+    if let Some((sort_key, _)) = sort_key {
+        schema.push(
+            KeySchemaElement::builder()
+                .attribute_name(sort_key)
+                .key_type(KeyType::Range)
+                .build()?,
+        );
+    }
+    Ok(schema)
+}
+
+fn provisioned_throughput(read: i64, write: i64) -> Result<ProvisionedThroughput, Error> {
+    Ok(ProvisionedThroughput::builder()
+        .read_capacity_units(read)
+        .write_capacity_units(write)
+        .build()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Client, WaitConfig};
+
+    #[test]
+    fn index_drift_is_sorted_by_name() {
+        let shape = |key: &str| {
+            (
+                vec![(key.to_owned(), ScalarAttributeType::S)],
+                Projection::All,
+            )
+        };
+        let expected = HashMap::from([("c", shape("c")), ("a", shape("a")), ("d", shape("d"))]);
+        let actual = HashMap::from([("b", shape("b")), ("d", shape("x")), ("e", shape("e"))]);
+        assert_eq!(
+            index_drift(
+                &expected,
+                &actual,
+                TableDrift::MissingGlobalIndex,
+                TableDrift::UnexpectedGlobalIndex,
+                TableDrift::GlobalIndexChanged,
+            ),
+            [
+                TableDrift::MissingGlobalIndex("a".into()),
+                TableDrift::UnexpectedGlobalIndex("b".into()),
+                TableDrift::MissingGlobalIndex("c".into()),
+                TableDrift::GlobalIndexChanged("d".into()),
+                TableDrift::UnexpectedGlobalIndex("e".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn ttl_is_enabled_after_create() {
+        tokio_test::block_on(async {
+            let client = Client::mock();
+            let definition =
+                TableDefinition::new("sessions", "id", ScalarAttributeType::S).ttl("expires_at");
+            client.create_table_with(definition.clone()).await.unwrap();
+            let ttl = client.describe_ttl("sessions").await.unwrap();
+            assert_eq!(ttl.attribute_name(), Some("expires_at"));
+
+            let output = client
+                .ensure_table(definition.clone().ttl("deleted_at"), WaitConfig::default())
+                .await
+                .unwrap();
+            assert_eq!(
+                output.drift,
+                [TableDrift::Ttl {
+                    expected: Some("deleted_at".into()),
+                    actual: Some("expires_at".into()),
+                }]
+            );
+
+            let output = client
+                .ensure_table(
+                    TableDefinition::new("events", "id", ScalarAttributeType::S).ttl("expires_at"),
+                    WaitConfig::default(),
+                )
+                .await
+                .unwrap();
+            assert!(output.created);
+            let ttl = client.describe_ttl("events").await.unwrap();
+            assert_eq!(ttl.attribute_name(), Some("expires_at"));
+        });
+    }
+}
@@ -0,0 +1,540 @@
+use crate::{
+    client::old_item_on_failure,
+    expression::ExpressionAttributes,
+    sdk::{
+        operation::transact_write_items::TransactWriteItemsOutput,
+        types::{
+            AttributeValue, ConditionCheck, Delete, Get, Put, ReturnValuesOnConditionCheckFailure,
+            TransactGetItem, TransactWriteItem,
+        },
+    },
+    Autoscale, Client, Condition, Error, Key, Update,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::HashMap;
+
+/// TransactWriteItemsで行う書き込みの一覧
+///
+/// 全ての書き込みが成功するか、全て行われないかのどちらかになります。
+/// ```
+/// # use dynamodb_utils::{Condition, TransactWrite, Update};
+/// let transaction = TransactWrite::new()
+///     .update(
+///         "accounts",
+///         ("id", "from"),
+///         Update::new()
+///             .add("balance", -100)
+///             .condition(Condition::ge("balance", 100)),
+///     )
+///     .update("accounts", ("id", "to"), Update::new().add("balance", 100))
+///     .client_request_token("transfer-0001");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct TransactWrite {
+    pub(crate) operations: Vec<TransactOperation>,
+    client_request_token: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum TransactOperation {
+    Put {
+        table_name: String,
+        item: HashMap<String, AttributeValue>,
+        condition: Option<Condition>,
+    },
+    Update {
+        table_name: String,
+        key: Key,
+        update: Update,
+    },
+    Delete {
+        table_name: String,
+        key: Key,
+        condition: Option<Condition>,
+    },
+    ConditionCheck {
+        table_name: String,
+        key: Key,
+        condition: Condition,
+    },
+}
+
+impl TransactWrite {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// itemを登録します。シリアライズされます。
+    pub fn put<T: Serialize>(self, table_name: impl Into<String>, item: T) -> Result<Self, Error> {
+        Ok(self.put_raw(
+            table_name,
+            crate::serde_dynamo::aws_sdk_dynamodb_1::to_item(item)?,
+        ))
+    }
+
+    /// 生のitemを登録します
+    pub fn put_raw(
+        self,
+        table_name: impl Into<String>,
+        item: HashMap<String, AttributeValue>,
+    ) -> Self {
+        self.push(TransactOperation::Put {
+            table_name: table_name.into(),
+            item,
+            condition: None,
+        })
+    }
+
+    /// 条件を満たす場合のみitemを登録します。シリアライズされます。
+    pub fn put_if<T: Serialize>(
+        self,
+        table_name: impl Into<String>,
+        item: T,
+        condition: Condition,
+    ) -> Result<Self, Error> {
+        Ok(self.push(TransactOperation::Put {
+            table_name: table_name.into(),
+            item: crate::serde_dynamo::aws_sdk_dynamodb_1::to_item(item)?,
+            condition: Some(condition),
+        }))
+    }
+
+    /// itemを更新します。[`Update::condition`]も使えます。
+    pub fn update(
+        self,
+        table_name: impl Into<String>,
+        key: impl Into<Key>,
+        update: Update,
+    ) -> Self {
+        self.push(TransactOperation::Update {
+            table_name: table_name.into(),
+            key: key.into(),
+            update,
+        })
+    }
+
+    /// itemを削除します
+    pub fn delete(self, table_name: impl Into<String>, key: impl Into<Key>) -> Self {
+        self.push(TransactOperation::Delete {
+            table_name: table_name.into(),
+            key: key.into(),
+            condition: None,
+        })
+    }
+
+    /// 条件を満たす場合のみitemを削除します
+    pub fn delete_if(
+        self,
+        table_name: impl Into<String>,
+        key: impl Into<Key>,
+        condition: Condition,
+    ) -> Self {
+        self.push(TransactOperation::Delete {
+            table_name: table_name.into(),
+            key: key.into(),
+            condition: Some(condition),
+        })
+    }
+
+    /// 書き込みはせず、itemが条件を満たしているかだけを確認します
+    pub fn condition_check(
+        self,
+        table_name: impl Into<String>,
+        key: impl Into<Key>,
+        condition: Condition,
+    ) -> Self {
+        self.push(TransactOperation::ConditionCheck {
+            table_name: table_name.into(),
+            key: key.into(),
+            condition,
+        })
+    }
+
+    /// 冪等性のためのtokenを指定します
+    ///
+    /// 同じtokenのリクエストは10分間、1度しか実行されません。
+    /// 指定しない場合はSDKが自動で生成するので、同じリクエストのSDK内での再送のみ冪等になります。
+    pub fn client_request_token(mut self, token: impl Into<String>) -> Self {
+        self.client_request_token = Some(token.into());
+        self
+    }
+
+    fn push(mut self, operation: TransactOperation) -> Self {
+        self.operations.push(operation);
+        self
+    }
+}
+
+impl TransactOperation {
+    fn table_name(&self) -> &str {
+        match self {
+            TransactOperation::Put { table_name, .. }
+            | TransactOperation::Update { table_name, .. }
+            | TransactOperation::Delete { table_name, .. }
+            | TransactOperation::ConditionCheck { table_name, .. } => table_name,
+        }
+    }
+
+    fn into_transact_write_item(self) -> Result<TransactWriteItem, Error> {
+        let mut attributes = ExpressionAttributes::default();
+        let item = match self {
+            TransactOperation::Put {
+                table_name,
+                item,
+                condition,
+            } => {
+                let condition = condition.map(|condition| condition.expression(&mut attributes));
+                let (names, values) = attributes.into_parts();
+                TransactWriteItem::builder().put(
+                    Put::builder()
+                        .table_name(table_name)
+                        .set_item(Some(item))
+                        .set_return_values_on_condition_check_failure(old_item_on_failure(
+                            &condition,
+                        ))
+                        .set_condition_expression(condition)
+                        .set_expression_attribute_names(names)
+                        .set_expression_attribute_values(values)
+                        .build()?,
+                )
+            }
+            TransactOperation::Update {
+                table_name,
+                key,
+                update,
+            } => {
+                if update.is_empty() {
+                    return Err(Error::EmptyUpdate);
+                }
+                let update_expression = update.expression(&mut attributes);
+                let condition = update
+                    .condition
+                    .map(|condition| condition.expression(&mut attributes));
+                let (names, values) = attributes.into_parts();
+                TransactWriteItem::builder().update(
+                    crate::sdk::types::Update::builder()
+                        .table_name(table_name)
+                        .set_key(Some(key.into_item()))
+                        .update_expression(update_expression)
+                        .set_return_values_on_condition_check_failure(old_item_on_failure(
+                            &condition,
+                        ))
+                        .set_condition_expression(condition)
+                        .set_expression_attribute_names(names)
+                        .set_expression_attribute_values(values)
+                        .build()?,
+                )
+            }
+            TransactOperation::Delete {
+                table_name,
+                key,
+                condition,
+            } => {
+                let condition = condition.map(|condition| condition.expression(&mut attributes));
+                let (names, values) = attributes.into_parts();
+                TransactWriteItem::builder().delete(
+                    Delete::builder()
+                        .table_name(table_name)
+                        .set_key(Some(key.into_item()))
+                        .set_return_values_on_condition_check_failure(old_item_on_failure(
+                            &condition,
+                        ))
+                        .set_condition_expression(condition)
+                        .set_expression_attribute_names(names)
+                        .set_expression_attribute_values(values)
+                        .build()?,
+                )
+            }
+            TransactOperation::ConditionCheck {
+                table_name,
+                key,
+                condition,
+            } => {
+                let condition = condition.expression(&mut attributes);
+                let (names, values) = attributes.into_parts();
+                TransactWriteItem::builder().condition_check(
+                    ConditionCheck::builder()
+                        .table_name(table_name)
+                        .set_key(Some(key.into_item()))
+                        .condition_expression(condition)
+                        .return_values_on_condition_check_failure(
+                            ReturnValuesOnConditionCheckFailure::AllOld,
+                        )
+                        .set_expression_attribute_names(names)
+                        .set_expression_attribute_values(values)
+                        .build()?,
+                )
+            }
+        };
+        Ok(item.build())
+    }
+}
+
+/// TransactGetItemsで取得するitemのkeyの一覧
+#[derive(Debug, Clone, Default)]
+pub struct TransactGet {
+    pub(crate) keys: Vec<(String, Key)>,
+}
+
+impl TransactGet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `table_name`のitemのkeyを追加します
+    pub fn get(mut self, table_name: impl Into<String>, key: impl Into<Key>) -> Self {
+        self.keys.push((table_name.into(), key.into()));
+        self
+    }
+}
+
+/// [`Client::transact_get`]の結果として受け取れる型
+///
+/// `Vec<Option<T>>`と、`(Option<A>, Option<B>, ..)`のタプルが使えます。
+/// 存在しないitemは`None`になります。
+pub trait FromTransactItems: Sized {
+    fn from_transact_items(
+        items: Vec<Option<HashMap<String, AttributeValue>>>,
+    ) -> Result<Self, Error>;
+}
+
+impl<T: DeserializeOwned> FromTransactItems for Vec<Option<T>> {
+    fn from_transact_items(
+        items: Vec<Option<HashMap<String, AttributeValue>>>,
+    ) -> Result<Self, Error> {
+        items.into_iter().map(from_optional_item).collect()
+    }
+}
+
+fn from_optional_item<T: DeserializeOwned>(
+    item: Option<HashMap<String, AttributeValue>>,
+) -> Result<Option<T>, Error> {
+    item.map(crate::serde_dynamo::aws_sdk_dynamodb_1::from_item)
+        .transpose()
+        .map_err(Into::into)
+}
+
+macro_rules! tuple_from_transact_items {
+    ($len: expr; $($t: ident),*) => {
+        impl<$($t: DeserializeOwned),*> FromTransactItems for ($(Option<$t>,)*) {
+            fn from_transact_items(
+                items: Vec<Option<HashMap<String, AttributeValue>>>,
+            ) -> Result<Self, Error> {
+                if items.len() != $len {
+                    return Err(Error::ItemCountMismatch {
+                        expected: $len,
+                        actual: items.len(),
+                    });
+                }
+                let mut items = items.into_iter();
+                Ok(($(from_optional_item::<$t>(items.next().flatten())?,)*))
+            }
+        }
+    };
+}
+
+tuple_from_transact_items!(1; A);
+tuple_from_transact_items!(2; A, B);
+tuple_from_transact_items!(3; A, B, C);
+tuple_from_transact_items!(4; A, B, C, D);
+tuple_from_transact_items!(5; A, B, C, D, E);
+tuple_from_transact_items!(6; A, B, C, D, E, F);
+
+/// トランザクションがキャンセルされた理由
+///
+/// トランザクションの各操作と同じ順番で並びます。
+/// 問題の無かった操作は`code`が`None`(または`"None"`)になります。
+#[derive(Debug, Clone, PartialEq)]
+pub struct CancellationReason {
+    pub code: Option<String>,
+    pub message: Option<String>,
+    pub item: Option<HashMap<String, AttributeValue>>,
+}
+
+impl CancellationReason {
+    /// 条件を満たさなかったことが原因かどうか
+    pub fn is_condition_failed(&self) -> bool {
+        self.code.as_deref() == Some("ConditionalCheckFailed")
+    }
+
+    /// この操作が原因かどうか
+    pub fn is_cause(&self) -> bool {
+        !matches!(self.code.as_deref(), None | Some("None"))
+    }
+}
+
+impl From<crate::sdk::types::CancellationReason> for CancellationReason {
+    fn from(value: crate::sdk::types::CancellationReason) -> Self {
+        Self {
+            code: value.code,
+            message: value.message,
+            item: value.item,
+        }
+    }
+}
+
+impl<A: Autoscale> Client<A> {
+    /// TransactWriteItemsで書き込みます
+    ///
+    /// キャンセルされた場合は[`Error::TransactionCanceled`]に、各操作の理由が入ります。
+    pub async fn transact_write(
+        &self,
+        transaction: TransactWrite,
+    ) -> Result<TransactWriteItemsOutput, Error> {
+        self.reject_mock("TransactWriteItems")?;
+        let mut table_names = transaction
+            .operations
+            .iter()
+            .map(TransactOperation::table_name)
+            .map(str::to_owned)
+            .collect::<Vec<_>>();
+        table_names.sort();
+        table_names.dedup();
+        let items = transaction
+            .operations
+            .into_iter()
+            .map(TransactOperation::into_transact_write_item)
+            .collect::<Result<Vec<_>, _>>()?;
+        self.send_with_autoscale(
+            &table_names.iter().map(String::as_str).collect::<Vec<_>>(),
+            self.dynamodb
+                .transact_write_items()
+                .set_transact_items(Some(items))
+                .set_client_request_token(transaction.client_request_token)
+                .send(),
+        )
+        .await
+    }
+
+    /// TransactGetItemsで、一貫性のある状態のitemをまとめて取得します
+    ///
+    /// 指定した順番で、存在しないitemは`None`になります。
+    pub async fn transact_get_raw(
+        &self,
+        request: TransactGet,
+    ) -> Result<Vec<Option<HashMap<String, AttributeValue>>>, Error> {
+        self.reject_mock("TransactGetItems")?;
+        let mut table_names = request
+            .keys
+            .iter()
+            .map(|(table_name, _)| table_name.clone())
+            .collect::<Vec<_>>();
+        table_names.sort();
+        table_names.dedup();
+        let items = request
+            .keys
+            .into_iter()
+            .map(|(table_name, key)| {
+                Ok(TransactGetItem::builder()
+                    .get(
+                        Get::builder()
+                            .table_name(table_name)
+                            .set_key(Some(key.into_item()))
+                            .build()?,
+                    )
+                    .build())
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        let output = self
+            .send_with_autoscale(
+                &table_names.iter().map(String::as_str).collect::<Vec<_>>(),
+                self.dynamodb
+                    .transact_get_items()
+                    .set_transact_items(Some(items))
+                    .send(),
+            )
+            .await?;
+        Ok(output
+            .responses
+            .unwrap_or_default()
+            .into_iter()
+            .map(|response| response.item)
+            .collect())
+    }
+
+    /// TransactGetItemsで、一貫性のある状態のitemをまとめて取得し、デシリアライズします
+    ///
+    /// ```no_run
+    /// # use dynamodb_utils::*;
+    /// # #[derive(serde::Deserialize)]
+    /// # struct User {}
+    /// # #[derive(serde::Deserialize)]
+    /// # struct Account {}
+    /// # tokio_test::block_on(async {
+    /// let client = Client::from_env().await;
+    /// let (user, account): (Option<User>, Option<Account>) = client
+    ///     .transact_get(
+    ///         TransactGet::new()
+    ///             .get("users", ("id", "a"))
+    ///             .get("accounts", ("user_id", "a")),
+    ///     )
+    ///     .await?;
+    /// # Ok::<(), Error>(())
+    /// # });
+    /// ```
+    pub async fn transact_get<T: FromTransactItems>(
+        &self,
+        request: TransactGet,
+    ) -> Result<T, Error> {
+        T::from_transact_items(self.transact_get_raw(request).await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conditional_writes_return_old_item_on_failure() {
+        let items = TransactWrite::new()
+            .put_if(
+                "t",
+                serde_json::json!({ "id": "a" }),
+                Condition::attribute_not_exists("id"),
+            )
+            .unwrap()
+            .update(
+                "t",
+                ("id", "b"),
+                Update::new()
+                    .add("count", 1)
+                    .condition(Condition::attribute_exists("id")),
+            )
+            .delete("t", ("id", "c"))
+            .condition_check("t", ("id", "d"), Condition::attribute_exists("id"))
+            .operations
+            .into_iter()
+            .map(|operation| operation.into_transact_write_item().unwrap())
+            .collect::<Vec<_>>();
+        let all_old = Some(&ReturnValuesOnConditionCheckFailure::AllOld);
+        assert_eq!(
+            items[0]
+                .put()
+                .unwrap()
+                .return_values_on_condition_check_failure(),
+            all_old
+        );
+        assert_eq!(
+            items[1]
+                .update()
+                .unwrap()
+                .return_values_on_condition_check_failure(),
+            all_old
+        );
+        // 条件が無ければ要求しません
+        assert_eq!(
+            items[2]
+                .delete()
+                .unwrap()
+                .return_values_on_condition_check_failure(),
+            None
+        );
+        assert_eq!(
+            items[3]
+                .condition_check()
+                .unwrap()
+                .return_values_on_condition_check_failure(),
+            all_old
+        );
+    }
+}
@@ -0,0 +1,255 @@
+use crate::{
+    client::from_sdk_error,
+    sdk::{
+        operation::{put_item::PutItemOutput, update_time_to_live::UpdateTimeToLiveOutput},
+        types::{TimeToLiveDescription, TimeToLiveSpecification},
+    },
+    Autoscale, Client, Condition, Error, IntoValue, Key, Query, Scan,
+};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// TTLの期限
+///
+/// DynamoDBにはUNIX時間の秒で保存されます。
+/// ```
+/// # use dynamodb_utils::Expiry;
+/// # use std::time::Duration;
+/// let expiry = Expiry::from(Duration::from_secs(3600));
+/// assert!(expiry.epoch_seconds() > Expiry::now().epoch_seconds());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Expiry {
+    epoch_seconds: i64,
+}
+
+impl Expiry {
+    /// UNIX時間の秒から作ります
+    pub fn from_epoch_seconds(epoch_seconds: i64) -> Self {
+        Self { epoch_seconds }
+    }
+
+    /// 現在時刻
+    pub fn now() -> Self {
+        SystemTime::now().into()
+    }
+
+    /// UNIX時間の秒
+    pub fn epoch_seconds(&self) -> i64 {
+        self.epoch_seconds
+    }
+}
+
+/// 現在から`Duration`後
+///
+/// 表せないほど先の時刻になる場合は`i64::MAX`秒になります。
+impl From<Duration> for Expiry {
+    fn from(value: Duration) -> Self {
+        let seconds = i64::try_from(value.as_secs()).unwrap_or(i64::MAX);
+        Self {
+            epoch_seconds: Self::now().epoch_seconds.saturating_add(seconds),
+        }
+    }
+}
+
+impl From<SystemTime> for Expiry {
+    fn from(value: SystemTime) -> Self {
+        let epoch_seconds = match value.duration_since(UNIX_EPOCH) {
+            Ok(duration) => i64::try_from(duration.as_secs()).unwrap_or(i64::MAX),
+            Err(e) => -(e.duration().as_secs() as i64),
+        };
+        Self { epoch_seconds }
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl<Tz: chrono::TimeZone> From<chrono::DateTime<Tz>> for Expiry {
+    fn from(value: chrono::DateTime<Tz>) -> Self {
+        Self {
+            epoch_seconds: value.timestamp(),
+        }
+    }
+}
+
+impl IntoValue for Expiry {
+    fn into_value(self) -> crate::sdk::types::AttributeValue {
+        self.epoch_seconds.into_value()
+    }
+}
+
+/// TTLの項目が無いか、期限がまだ来ていないitemの条件
+///
+/// DynamoDBは期限切れのitemをすぐには削除しないので、読み込み時にこの条件で除外します。
+fn not_expired(ttl_attribute: impl Into<String>) -> Condition {
+    let ttl_attribute = ttl_attribute.into();
+    Condition::attribute_not_exists(ttl_attribute.as_str())
+        .or(Condition::gt(ttl_attribute.as_str(), Expiry::now()))
+}
+
+impl Query {
+    /// `ttl_attribute`の期限が過ぎたitemを除外します
+    pub fn not_expired(self, ttl_attribute: impl Into<String>) -> Self {
+        self.filter(not_expired(ttl_attribute))
+    }
+}
+
+impl Scan {
+    /// `ttl_attribute`の期限が過ぎたitemを除外します
+    pub fn not_expired(self, ttl_attribute: impl Into<String>) -> Self {
+        self.filter(not_expired(ttl_attribute))
+    }
+}
+
+impl<A: Autoscale> Client<A> {
+    /// `ttl_attribute`の項目でTTLを有効にします
+    pub async fn enable_ttl(
+        &self,
+        table_name: impl Into<String>,
+        ttl_attribute: impl Into<String>,
+    ) -> Result<UpdateTimeToLiveOutput, Error> {
+        self.update_ttl(table_name, ttl_attribute, true).await
+    }
+
+    /// TTLを無効にします。`ttl_attribute`は有効にしたときと同じ項目名を指定してください。
+    pub async fn disable_ttl(
+        &self,
+        table_name: impl Into<String>,
+        ttl_attribute: impl Into<String>,
+    ) -> Result<UpdateTimeToLiveOutput, Error> {
+        self.update_ttl(table_name, ttl_attribute, false).await
+    }
+
+    async fn update_ttl(
+        &self,
+        table_name: impl Into<String>,
+        ttl_attribute: impl Into<String>,
+        enabled: bool,
+    ) -> Result<UpdateTimeToLiveOutput, Error> {
+        let specification = TimeToLiveSpecification::builder()
+            .enabled(enabled)
+            .attribute_name(ttl_attribute)
+            .build()?;
+        if let Some(mock) = &self.mock {
+            return mock.update_time_to_live(&table_name.into(), specification);
+        }
+        self.dynamodb
+            .update_time_to_live()
+            .table_name(table_name)
+            .time_to_live_specification(specification)
+            .send()
+            .await
+            .map_err(from_sdk_error)
+    }
+
+    /// TTLの設定を取得します
+    pub async fn describe_ttl(
+        &self,
+        table_name: impl Into<String>,
+    ) -> Result<TimeToLiveDescription, Error> {
+        if let Some(mock) = &self.mock {
+            return mock.describe_time_to_live(&table_name.into());
+        }
+        self.dynamodb
+            .describe_time_to_live()
+            .table_name(table_name)
+            .send()
+            .await
+            .map_err(from_sdk_error)?
+            .time_to_live_description
+            .ok_or(Error::NotFound)
+    }
+
+    /// `ttl_attribute`に期限を入れてitemを登録します
+    ///
+    /// 期限は[`Duration`]なら現在からの時間、[`SystemTime`]やchronoの`DateTime`なら時刻です。
+    /// ```
+    /// # use dynamodb_utils::*;
+    /// # use std::time::Duration;
+    /// #[derive(serde::Serialize, serde::Deserialize)]
+    /// struct Session {
+    ///     id: String,
+    /// }
+    ///
+    /// # tokio_test::block_on(async {
+    /// let client = Client::mock();
+    /// client
+    ///     .create_table("sessions", "id", None::<String>, TableType::OnDemand)
+    ///     .await?;
+    /// client.enable_ttl("sessions", "expires_at").await?;
+    /// let session = Session { id: "s1".into() };
+    /// client
+    ///     .put_item_with_ttl("sessions", session, "expires_at", Duration::from_secs(3600))
+    ///     .await?;
+    /// let expired = Session { id: "s2".into() };
+    /// client
+    ///     .put_item_with_ttl("sessions", expired, "expires_at", Expiry::from_epoch_seconds(0))
+    ///     .await?;
+    ///
+    /// let session: Session = client
+    ///     .get_item_unexpired("sessions", ("id", "s1"), "expires_at")
+    ///     .await?;
+    /// assert_eq!(session.id, "s1");
+    /// assert!(matches!(
+    ///     client
+    ///         .get_item_unexpired::<Session>("sessions", ("id", "s2"), "expires_at")
+    ///         .await,
+    ///     Err(Error::NotFound)
+    /// ));
+    /// # Ok::<(), Error>(())
+    /// # });
+    /// ```
+    pub async fn put_item_with_ttl<T: Serialize>(
+        &self,
+        table_name: impl Into<String>,
+        data: T,
+        ttl_attribute: impl Into<String>,
+        expiry: impl Into<Expiry>,
+    ) -> Result<PutItemOutput, Error> {
+        let mut item = crate::serde_dynamo::aws_sdk_dynamodb_1::to_item(data)?;
+        item.insert(ttl_attribute.into(), expiry.into().into_value());
+        self.put_item_raw(table_name, item).await
+    }
+
+    /// itemを取得します。`ttl_attribute`の期限が過ぎている場合は[`Error::NotFound`]になります。
+    pub async fn get_item_unexpired<T>(
+        &self,
+        table_name: impl Into<String>,
+        key: impl Into<Key>,
+        ttl_attribute: &str,
+    ) -> Result<T, Error>
+    where
+        for<'de> T: Deserialize<'de>,
+    {
+        let item = self
+            .get_item_raw(table_name, key)
+            .await?
+            .item
+            .ok_or(Error::NotFound)?;
+        if let Some(expiry) = item.get(ttl_attribute).and_then(|value| value.as_n().ok()) {
+            if expiry
+                .parse::<i64>()
+                .is_ok_and(|expiry| Expiry::from_epoch_seconds(expiry) <= Expiry::now())
+            {
+                return Err(Error::NotFound);
+            }
+        }
+        crate::serde_dynamo::aws_sdk_dynamodb_1::from_item(item).map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn large_duration_saturates() {
+        assert_eq!(Expiry::from(Duration::MAX).epoch_seconds(), i64::MAX);
+    }
+
+    #[test]
+    fn duration_is_added_to_now() {
+        let now = Expiry::now().epoch_seconds();
+        let expiry = Expiry::from(Duration::from_secs(60)).epoch_seconds();
+        assert!((now + 60..=now + 61).contains(&expiry));
+    }
+}
@@ -0,0 +1,301 @@
+use crate::{
+    expression::{ExpressionAttributes, Path},
+    sdk::types::{AttributeValue, ReturnValue},
+    Condition, IntoValue,
+};
+
+/// UpdateExpressionのbuilder
+///
+/// 複数の項目への変更をまとめて、atomicに適用できます。
+/// 項目名や値は`#name`、`:value`のplaceholderに自動で置き換えられるので、予約語も使えます。
+/// ```
+/// # use dynamodb_utils::{sdk::types::AttributeValue, Update};
+/// let update = Update::new()
+///     .set("name", "taro")
+///     .set_if_not_exists("created_at", 1716877593)
+///     .list_append("histories", AttributeValue::L(vec![AttributeValue::S("login".into())]))
+///     .add("count", 1)
+///     .remove("profile.tmp");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Update {
+    pub(crate) actions: Vec<UpdateAction>,
+    pub(crate) condition: Option<Condition>,
+    pub(crate) return_values: Option<ReturnValue>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum UpdateAction {
+    Set(Path, SetValue),
+    Remove(Path),
+    Add(Path, AttributeValue),
+    Delete(Path, AttributeValue),
+}
+
+#[derive(Debug, Clone)]
+pub(crate) enum SetValue {
+    Value(AttributeValue),
+    IfNotExists(AttributeValue),
+    ListAppend(AttributeValue),
+    ListPrepend(AttributeValue),
+}
+
+impl Update {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `SET path = value`
+    pub fn set(self, path: impl Into<Path>, value: impl IntoValue) -> Self {
+        self.push(UpdateAction::Set(
+            path.into(),
+            SetValue::Value(value.into_value()),
+        ))
+    }
+
+    /// `SET path = if_not_exists(path, value)`
+    ///
+    /// まだ値が無いときだけ設定します。
+    pub fn set_if_not_exists(self, path: impl Into<Path>, value: impl IntoValue) -> Self {
+        self.push(UpdateAction::Set(
+            path.into(),
+            SetValue::IfNotExists(value.into_value()),
+        ))
+    }
+
+    /// `SET path = list_append(path, value)`
+    ///
+    /// listの末尾に追加します。項目が無い場合は空のlistとして扱います。
+    pub fn list_append(self, path: impl Into<Path>, values: impl IntoValue) -> Self {
+        self.push(UpdateAction::Set(
+            path.into(),
+            SetValue::ListAppend(values.into_value()),
+        ))
+    }
+
+    /// `SET path = list_append(value, path)`
+    ///
+    /// listの先頭に追加します。項目が無い場合は空のlistとして扱います。
+    pub fn list_prepend(self, path: impl Into<Path>, values: impl IntoValue) -> Self {
+        self.push(UpdateAction::Set(
+            path.into(),
+            SetValue::ListPrepend(values.into_value()),
+        ))
+    }
+
+    /// `REMOVE path`
+    pub fn remove(self, path: impl Into<Path>) -> Self {
+        self.push(UpdateAction::Remove(path.into()))
+    }
+
+    /// `ADD path value`
+    ///
+    /// 数値なら加算、setなら要素の追加になります。
+    pub fn add(self, path: impl Into<Path>, value: impl IntoValue) -> Self {
+        self.push(UpdateAction::Add(path.into(), value.into_value()))
+    }
+
+    /// `DELETE path value`
+    ///
+    /// setから要素を取り除きます。
+    pub fn delete(self, path: impl Into<Path>, value: impl IntoValue) -> Self {
+        self.push(UpdateAction::Delete(path.into(), value.into_value()))
+    }
+
+    /// 更新の条件を指定します
+    ///
+    /// すでに条件がある場合は`AND`で結合されます。
+    pub fn condition(mut self, condition: Condition) -> Self {
+        self.condition = Some(match self.condition {
+            Some(prev) => prev.and(condition),
+            None => condition,
+        });
+        self
+    }
+
+    /// 更新結果として返す項目を指定します
+    pub(crate) fn return_values(mut self, return_values: ReturnValue) -> Self {
+        self.return_values = Some(return_values);
+        self
+    }
+
+    /// 変更が一つも無いかどうか
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+
+    fn push(mut self, action: UpdateAction) -> Self {
+        self.actions.push(action);
+        self
+    }
+
+    /// UpdateExpressionを組み立てます
+    pub(crate) fn expression(&self, attributes: &mut ExpressionAttributes) -> String {
+        let mut set = vec![];
+        let mut remove = vec![];
+        let mut add = vec![];
+        let mut delete = vec![];
+        for action in &self.actions {
+            match action {
+                UpdateAction::Set(path, value) => {
+                    let path = attributes.path(path);
+                    let operand = match value {
+                        SetValue::Value(value) => attributes.value(value.clone()),
+                        SetValue::IfNotExists(value) => {
+                            format!("if_not_exists({path}, {})", attributes.value(value.clone()))
+                        }
+                        SetValue::ListAppend(value) => format!(
+                            "list_append(if_not_exists({path}, {}), {})",
+                            attributes.value(AttributeValue::L(vec![])),
+                            attributes.value(value.clone())
+                        ),
+                        SetValue::ListPrepend(value) => format!(
+                            "list_append({}, if_not_exists({path}, {}))",
+                            attributes.value(value.clone()),
+                            attributes.value(AttributeValue::L(vec![]))
+                        ),
+                    };
+                    set.push(format!("{path} = {operand}"));
+                }
+                UpdateAction::Remove(path) => remove.push(attributes.path(path)),
+                UpdateAction::Add(path, value) => add.push(format!(
+                    "{} {}",
+                    attributes.path(path),
+                    attributes.value(value.clone())
+                )),
+                UpdateAction::Delete(path, value) => delete.push(format!(
+                    "{} {}",
+                    attributes.path(path),
+                    attributes.value(value.clone())
+                )),
+            }
+        }
+        [
+            ("SET", set),
+            ("REMOVE", remove),
+            ("ADD", add),
+            ("DELETE", delete),
+        ]
+        .into_iter()
+        .filter(|(_, clauses)| !clauses.is_empty())
+        .map(|(keyword, clauses)| format!("{keyword} {}", clauses.join(", ")))
+        .collect::<Vec<_>>()
+        .join(" ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[allow(clippy::type_complexity)]
+    fn expression(
+        update: &Update,
+    ) -> (
+        String,
+        Option<HashMap<String, String>>,
+        Option<HashMap<String, AttributeValue>>,
+    ) {
+        let mut attributes = ExpressionAttributes::default();
+        let expression = update.expression(&mut attributes);
+        let (names, values) = attributes.into_parts();
+        (expression, names, values)
+    }
+
+    fn map<V>(pairs: Vec<(&str, V)>) -> Option<HashMap<String, V>> {
+        Some(
+            pairs
+                .into_iter()
+                .map(|(placeholder, value)| (placeholder.to_string(), value))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn groups_actions_by_clause() {
+        let tags = AttributeValue::Ss(vec!["old".into()]);
+        let update = Update::new()
+            .add("count", 1)
+            .set("name", "taro")
+            .remove("tmp")
+            .delete("tags", tags.clone())
+            .set("profile.age", 20)
+            .remove("items[0]");
+        let (expression, names, values) = expression(&update);
+        assert_eq!(
+            expression,
+            "SET #n1 = :v1, #n4.#n5 = :v3 REMOVE #n2, #n6[0] ADD #n0 :v0 DELETE #n3 :v2"
+        );
+        assert_eq!(
+            names,
+            map(vec![
+                ("#n0", "count".to_string()),
+                ("#n1", "name".to_string()),
+                ("#n2", "tmp".to_string()),
+                ("#n3", "tags".to_string()),
+                ("#n4", "profile".to_string()),
+                ("#n5", "age".to_string()),
+                ("#n6", "items".to_string()),
+            ])
+        );
+        assert_eq!(
+            values,
+            map(vec![
+                (":v0", 1.into_value()),
+                (":v1", "taro".into_value()),
+                (":v2", tags),
+                (":v3", 20.into_value()),
+            ])
+        );
+    }
+
+    #[test]
+    fn if_not_exists() {
+        let (expression, names, values) =
+            expression(&Update::new().set_if_not_exists("created_at", 1716877593));
+        assert_eq!(expression, "SET #n0 = if_not_exists(#n0, :v0)");
+        assert_eq!(names, map(vec![("#n0", "created_at".to_string())]));
+        assert_eq!(values, map(vec![(":v0", 1716877593.into_value())]));
+    }
+
+    #[test]
+    fn list_append_and_prepend() {
+        let first = AttributeValue::L(vec!["a".into_value()]);
+        let last = AttributeValue::L(vec!["z".into_value()]);
+        let (expression, names, values) = expression(
+            &Update::new()
+                .list_append("histories", last.clone())
+                .list_prepend("queue", first.clone()),
+        );
+        assert_eq!(
+            expression,
+            "SET #n0 = list_append(if_not_exists(#n0, :v0), :v1), \
+             #n1 = list_append(:v2, if_not_exists(#n1, :v3))"
+        );
+        assert_eq!(
+            names,
+            map(vec![
+                ("#n0", "histories".to_string()),
+                ("#n1", "queue".to_string()),
+            ])
+        );
+        assert_eq!(
+            values,
+            map(vec![
+                (":v0", AttributeValue::L(vec![])),
+                (":v1", last),
+                (":v2", first),
+                (":v3", AttributeValue::L(vec![])),
+            ])
+        );
+    }
+
+    #[test]
+    fn empty_update_has_no_expression() {
+        let (expression, names, values) = expression(&Update::new());
+        assert_eq!(expression, "");
+        assert_eq!(names, None);
+        assert_eq!(values, None);
+    }
+}
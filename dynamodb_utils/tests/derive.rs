@@ -0,0 +1,64 @@
+//! `#[derive(DynamoEntity)]`の展開結果を確認します
+#![cfg(feature = "derive")]
+
+use dynamodb_utils::{
+    sdk::types::{AttributeValue, ScalarAttributeType},
+    serde_dynamo::aws_sdk_dynamodb_1::to_item,
+    DynamoEntity, Key, KeySchema,
+};
+use std::collections::HashMap;
+
+#[derive(serde::Serialize, serde::Deserialize, DynamoEntity)]
+#[dynamo(table = "messages")]
+#[serde(rename_all = "camelCase")]
+struct Message {
+    #[dynamo(hash_key)]
+    room_id: String,
+    #[dynamo(range_key)]
+    #[serde(rename(serialize = "sentAt", deserialize = "sentAt"))]
+    sent_at: i64,
+    body: String,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, DynamoEntity)]
+#[dynamo(table = "users")]
+#[serde(rename_all(serialize = "PascalCase", deserialize = "PascalCase"))]
+struct User {
+    #[dynamo(hash_key)]
+    user_id: String,
+}
+
+#[test]
+fn key_names_follow_serde() {
+    assert_eq!(Message::TABLE_NAME, "messages");
+    assert_eq!(
+        Message::key_schema(),
+        KeySchema::new("roomId").with_sort("sentAt")
+    );
+    assert_eq!(
+        Message::key_attribute_types(),
+        (ScalarAttributeType::S, Some(ScalarAttributeType::N))
+    );
+    assert_eq!(User::key_schema(), KeySchema::new("UserId"));
+}
+
+#[test]
+fn key_matches_serialized_item() {
+    let message = Message {
+        room_id: "r1".into(),
+        sent_at: 1,
+        body: "hello".into(),
+    };
+    let item: HashMap<String, AttributeValue> = to_item(&message).unwrap();
+    let key = message.key();
+    let (name, value) = key.partition();
+    assert_eq!(item.get(name), Some(value));
+    let (name, value) = key.sort().unwrap();
+    assert_eq!(item.get(name), Some(value));
+    assert_eq!(key, Key::new("roomId", "r1").with_sort("sentAt", 1));
+}
+
+#[test]
+fn invalid_attributes_are_rejected() {
+    trybuild::TestCases::new().compile_fail("tests/ui/*.rs");
+}
@@ -0,0 +1,115 @@
+//! dynamodb-localに対して[`StreamConsumer`]を動かします
+//!
+//! `DYNAMODB_LOCAL_ENDPOINT`(デフォルトは`http://localhost:8000`)でdynamodb-localを起動して、
+//! `cargo test -- --ignored`で実行してください。
+//! dynamodb-localではshardを分割できないので、分割の扱いはstreams.rsの単体テストで確認しています。
+#![cfg(feature = "streams")]
+
+use dynamodb_utils::{
+    sdk::{
+        config::{Credentials, Region},
+        types::{ScalarAttributeType, StreamViewType},
+    },
+    sdk_config::{BehaviorVersion, SdkConfig},
+    ChangeEvent, Client, Error, MemoryCheckpoints, StreamConsumer, TableDefinition, WaitConfig,
+};
+use futures_util::{Stream, TryStreamExt};
+use std::{
+    pin::pin,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct User {
+    id: String,
+}
+
+async fn local_config() -> SdkConfig {
+    let endpoint = std::env::var("DYNAMODB_LOCAL_ENDPOINT")
+        .unwrap_or_else(|_| "http://localhost:8000".to_owned());
+    dynamodb_utils::sdk_config::defaults(BehaviorVersion::latest())
+        .endpoint_url(endpoint)
+        .region(Region::new("us-east-1"))
+        .credentials_provider(Credentials::new("local", "local", None, None, "local"))
+        .load()
+        .await
+}
+
+/// 追加されたitemのidを`count`件読みます
+async fn inserted_ids(
+    events: &mut (impl Stream<Item = Result<ChangeEvent<User>, Error>> + Unpin),
+    count: usize,
+) -> Vec<String> {
+    let mut ids = vec![];
+    while ids.len() < count {
+        let event = tokio::time::timeout(Duration::from_secs(30), events.try_next())
+            .await
+            .expect("timed out waiting for stream records")
+            .unwrap()
+            .expect("stream ended");
+        if let ChangeEvent::Insert {
+            new_image: Some(user),
+            ..
+        } = event
+        {
+            ids.push(user.id);
+        }
+    }
+    ids
+}
+
+#[test]
+#[ignore = "requires dynamodb-local"]
+fn consumer_resumes_from_checkpoint() {
+    tokio_test::block_on(async {
+        let config = local_config().await;
+        let client = Client::from_conf(&config);
+        let suffix = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_millis();
+        let table_name = format!("streams_local_{suffix}");
+        client
+            .create_table_with(
+                TableDefinition::new(&table_name, "id", ScalarAttributeType::S)
+                    .stream(StreamViewType::NewAndOldImages),
+            )
+            .await
+            .unwrap();
+        client
+            .wait_until_active(&table_name, WaitConfig::default())
+            .await
+            .unwrap();
+        let stream_arn = client.latest_stream_arn(&table_name).await.unwrap();
+        let checkpoints = MemoryCheckpoints::default();
+
+        for id in ["a", "b"] {
+            client
+                .put_item(&table_name, User { id: id.to_owned() })
+                .await
+                .unwrap();
+        }
+        {
+            let consumer = StreamConsumer::from_conf(&config, &stream_arn)
+                .checkpoints(checkpoints.clone())
+                .poll_interval(Duration::from_millis(100));
+            let mut events = pin!(consumer.events::<User>());
+            assert_eq!(inserted_ids(&mut events, 2).await, ["a", "b"]);
+            // 次を要求した時点で、読んだところまでがcheckpointに保存されます
+            let next = tokio::time::timeout(Duration::from_secs(2), events.try_next()).await;
+            assert!(next.is_err(), "no more records expected");
+        }
+
+        client
+            .put_item(&table_name, User { id: "c".to_owned() })
+            .await
+            .unwrap();
+        let consumer = StreamConsumer::from_conf(&config, &stream_arn)
+            .checkpoints(checkpoints)
+            .poll_interval(Duration::from_millis(100));
+        let mut events = pin!(consumer.events::<User>());
+        assert_eq!(inserted_ids(&mut events, 1).await, ["c"]);
+
+        client.delete_table(&table_name).await.unwrap();
+    });
+}
@@ -0,0 +1,9 @@
+use dynamodb_utils::DynamoEntity;
+
+#[derive(serde::Serialize, serde::Deserialize, DynamoEntity)]
+#[dynamo(table = "users")]
+struct User {
+    id: String,
+}
+
+fn main() {}
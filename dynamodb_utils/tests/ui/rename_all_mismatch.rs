@@ -0,0 +1,11 @@
+use dynamodb_utils::DynamoEntity;
+
+#[derive(serde::Serialize, serde::Deserialize, DynamoEntity)]
+#[dynamo(table = "users")]
+#[serde(rename_all(serialize = "camelCase", deserialize = "snake_case"))]
+struct User {
+    #[dynamo(hash_key)]
+    user_id: String,
+}
+
+fn main() {}
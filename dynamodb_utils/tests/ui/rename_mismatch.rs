@@ -0,0 +1,11 @@
+use dynamodb_utils::DynamoEntity;
+
+#[derive(serde::Serialize, serde::Deserialize, DynamoEntity)]
+#[dynamo(table = "users")]
+struct User {
+    #[dynamo(hash_key)]
+    #[serde(rename(serialize = "Id"))]
+    id: String,
+}
+
+fn main() {}
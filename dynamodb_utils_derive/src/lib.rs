@@ -0,0 +1,313 @@
+//! `dynamodb_utils`の`DynamoEntity`のderive macro
+//!
+//! `dynamodb_utils`の`derive` featureから使ってください。
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::{
+    parse_macro_input, punctuated::Punctuated, spanned::Spanned, Attribute, Data, DeriveInput,
+    Expr, ExprLit, Fields, Lit, LitStr, Meta, MetaNameValue, Token, Type,
+};
+
+/// `#[dynamo(table = "...")]`と、fieldの`#[dynamo(hash_key)]`, `#[dynamo(range_key)]`から
+/// `DynamoEntity`を実装します
+///
+/// 項目名は`#[serde(rename = "...")]`と`#[serde(rename_all = "...")]`に従います。
+/// serializeとdeserializeで別の名前を指定した場合はcompile errorになります。
+#[proc_macro_derive(DynamoEntity, attributes(dynamo))]
+pub fn derive_dynamo_entity(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+struct KeyField {
+    attribute_name: String,
+    ident: syn::Ident,
+    ty: Type,
+}
+
+fn expand(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let table = table_name(&input.attrs)?
+        .ok_or_else(|| syn::Error::new(Span::call_site(), "missing #[dynamo(table = \"...\")]"))?;
+    let rename_all = serde_rename_all(&input.attrs)?;
+
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new(
+            Span::call_site(),
+            "DynamoEntity can only be derived for structs",
+        ));
+    };
+    let Fields::Named(fields) = &data.fields else {
+        return Err(syn::Error::new(
+            data.fields.span(),
+            "DynamoEntity requires named fields",
+        ));
+    };
+
+    let mut hash_key = None;
+    let mut range_key = None;
+    for field in &fields.named {
+        let (is_hash, is_range) = key_kind(&field.attrs)?;
+        if !is_hash && !is_range {
+            continue;
+        }
+        if is_hash && is_range {
+            return Err(syn::Error::new(
+                field.span(),
+                "a field cannot be both hash_key and range_key",
+            ));
+        }
+        let ident = field.ident.clone().expect("named field");
+        let attribute_name = match serde_rename(&field.attrs)? {
+            Some(rename) => rename,
+            None => {
+                let name = ident.to_string();
+                let name = name.strip_prefix("r#").unwrap_or(&name).to_owned();
+                match &rename_all {
+                    Some(rule) => apply_rename_all(rule, &name)?,
+                    None => name,
+                }
+            }
+        };
+        let key = KeyField {
+            attribute_name,
+            ident,
+            ty: field.ty.clone(),
+        };
+        let slot = if is_hash {
+            &mut hash_key
+        } else {
+            &mut range_key
+        };
+        if slot.is_some() {
+            return Err(syn::Error::new(
+                field.span(),
+                if is_hash {
+                    "duplicate hash_key"
+                } else {
+                    "duplicate range_key"
+                },
+            ));
+        }
+        *slot = Some(key);
+    }
+    let hash_key = hash_key
+        .ok_or_else(|| syn::Error::new(Span::call_site(), "missing #[dynamo(hash_key)] field"))?;
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let hash_name = &hash_key.attribute_name;
+    let hash_ident = &hash_key.ident;
+    let hash_ty = &hash_key.ty;
+    let (key_schema, attribute_types, key) = match &range_key {
+        Some(range_key) => {
+            let range_name = &range_key.attribute_name;
+            let range_ident = &range_key.ident;
+            let range_ty = &range_key.ty;
+            (
+                quote! {
+                    ::dynamodb_utils::KeySchema::new(#hash_name).with_sort(#range_name)
+                },
+                quote! {
+                    (
+                        <#hash_ty as ::dynamodb_utils::KeyAttribute>::attribute_type(),
+                        ::core::option::Option::Some(
+                            <#range_ty as ::dynamodb_utils::KeyAttribute>::attribute_type(),
+                        ),
+                    )
+                },
+                quote! {
+                    ::dynamodb_utils::Key::composite(
+                        #hash_name,
+                        ::core::clone::Clone::clone(&self.#hash_ident),
+                        #range_name,
+                        ::core::clone::Clone::clone(&self.#range_ident),
+                    )
+                },
+            )
+        }
+        None => (
+            quote! {
+                ::dynamodb_utils::KeySchema::new(#hash_name)
+            },
+            quote! {
+                (
+                    <#hash_ty as ::dynamodb_utils::KeyAttribute>::attribute_type(),
+                    ::core::option::Option::None,
+                )
+            },
+            quote! {
+                ::dynamodb_utils::Key::new(
+                    #hash_name,
+                    ::core::clone::Clone::clone(&self.#hash_ident),
+                )
+            },
+        ),
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::dynamodb_utils::DynamoEntity for #name #ty_generics #where_clause {
+            const TABLE_NAME: &'static str = #table;
+
+            fn key_schema() -> ::dynamodb_utils::KeySchema {
+                #key_schema
+            }
+
+            fn key_attribute_types() -> (
+                ::dynamodb_utils::sdk::types::ScalarAttributeType,
+                ::core::option::Option<::dynamodb_utils::sdk::types::ScalarAttributeType>,
+            ) {
+                #attribute_types
+            }
+
+            fn key(&self) -> ::dynamodb_utils::Key {
+                #key
+            }
+        }
+    })
+}
+
+/// structの`#[dynamo(table = "...")]`
+fn table_name(attrs: &[Attribute]) -> syn::Result<Option<LitStr>> {
+    let mut table = None;
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("dynamo")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("table") {
+                table = Some(meta.value()?.parse::<LitStr>()?);
+                Ok(())
+            } else {
+                Err(meta.error("unsupported dynamo attribute"))
+            }
+        })?;
+    }
+    Ok(table)
+}
+
+/// fieldの`#[dynamo(hash_key)]`, `#[dynamo(range_key)]`
+fn key_kind(attrs: &[Attribute]) -> syn::Result<(bool, bool)> {
+    let mut hash = false;
+    let mut range = false;
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("dynamo")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("hash_key") {
+                hash = true;
+                Ok(())
+            } else if meta.path.is_ident("range_key") {
+                range = true;
+                Ok(())
+            } else {
+                Err(meta.error("unsupported dynamo attribute"))
+            }
+        })?;
+    }
+    Ok((hash, range))
+}
+
+/// `#[serde(...)]`の中の`name = "..."`の値
+///
+/// `name(serialize = "...", deserialize = "...")`の形は、両方が同じ値の場合のみ受け付けます。
+/// keyの項目名は書き込みと読み込みで同じである必要があるためです。
+fn serde_str(attrs: &[Attribute], name: &str) -> syn::Result<Option<String>> {
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("serde")) {
+        let metas = attr.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)?;
+        for meta in metas {
+            match meta {
+                Meta::NameValue(meta) if meta.path.is_ident(name) => {
+                    return lit_str(&meta.value).map(Some);
+                }
+                Meta::List(list) if list.path.is_ident(name) => {
+                    let metas = list.parse_args_with(
+                        Punctuated::<MetaNameValue, Token![,]>::parse_terminated,
+                    )?;
+                    let mut serialize = None;
+                    let mut deserialize = None;
+                    for meta in metas {
+                        let slot = if meta.path.is_ident("serialize") {
+                            &mut serialize
+                        } else if meta.path.is_ident("deserialize") {
+                            &mut deserialize
+                        } else {
+                            return Err(syn::Error::new_spanned(
+                                meta.path,
+                                "expected `serialize` or `deserialize`",
+                            ));
+                        };
+                        *slot = Some(lit_str(&meta.value)?);
+                    }
+                    return match (serialize, deserialize) {
+                        (Some(serialize), Some(deserialize)) if serialize == deserialize => {
+                            Ok(Some(serialize))
+                        }
+                        _ => Err(syn::Error::new_spanned(
+                            list,
+                            format!(
+                                "DynamoEntity requires the same {name} for serialize and deserialize"
+                            ),
+                        )),
+                    };
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(None)
+}
+
+fn lit_str(expr: &Expr) -> syn::Result<String> {
+    match expr {
+        Expr::Lit(ExprLit {
+            lit: Lit::Str(value),
+            ..
+        }) => Ok(value.value()),
+        _ => Err(syn::Error::new_spanned(expr, "expected a string literal")),
+    }
+}
+
+fn serde_rename(attrs: &[Attribute]) -> syn::Result<Option<String>> {
+    serde_str(attrs, "rename")
+}
+
+fn serde_rename_all(attrs: &[Attribute]) -> syn::Result<Option<String>> {
+    serde_str(attrs, "rename_all")
+}
+
+/// serdeの`rename_all`と同じ規則でfield名を変換します
+fn apply_rename_all(rule: &str, field: &str) -> syn::Result<String> {
+    let pascal = || {
+        field
+            .split('_')
+            .map(|word| {
+                let mut chars = word.chars();
+                match chars.next() {
+                    Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+                    None => String::new(),
+                }
+            })
+            .collect::<String>()
+    };
+    Ok(match rule {
+        "lowercase" | "snake_case" => field.to_ascii_lowercase(),
+        "UPPERCASE" | "SCREAMING_SNAKE_CASE" => field.to_ascii_uppercase(),
+        "PascalCase" => pascal(),
+        "camelCase" => {
+            let pascal = pascal();
+            let mut chars = pascal.chars();
+            match chars.next() {
+                Some(first) => first.to_ascii_lowercase().to_string() + chars.as_str(),
+                None => String::new(),
+            }
+        }
+        "kebab-case" => field.replace('_', "-"),
+        "SCREAMING-KEBAB-CASE" => field.to_ascii_uppercase().replace('_', "-"),
+        _ => {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                format!("unsupported rename_all rule: {rule}"),
+            ))
+        }
+    })
+}
@@ -112,7 +112,7 @@ impl Cache for EternalCache {
 impl CachedClient {
     /// キャッシュを取得する
     /// これがdropされないと[`Client::get`]が待機します。
-    pub fn get_mut_cache(&self) -> Option<RwLockWriteGuard<HashMap<String, String>>> {
+    pub fn get_mut_cache(&self) -> Option<RwLockWriteGuard<'_, HashMap<String, String>>> {
         self.cache.write().ok()
     }
 